@@ -110,8 +110,12 @@ def _conditioning(peek_mode:str, up:str)->str:
     return "no-dealer-BJ" if (peek_mode=="US" and up in ("A","T")) else "unconditioned"
 
 def _add_to(t:int,s:bool,r:str)->Tuple[int,bool]:
+    # s: one ace already counts 11, so a further ace joins as 1 and stays soft
     v = 11 if r=="A" else (10 if r=="T" else int(r))
-    t2=t+v; s2=s or (r=="A")
+    t2=t+v; s2=s
+    if r=="A":
+        if s2: t2-=10
+        else: s2=True
     if t2>21 and s2: t2-=10; s2=False
     return t2,s2
 
@@ -11,17 +11,20 @@ _INDEX = {r:i for i,r in enumerate(_RANKS)}
 HC_NONE     = 0
 HC_NOT_TEN  = 1
 HC_NOT_ACE  = 2
+HC_AUTO     = -1  # derived by rustcore from the upcard and the peek flags
+HC_KNOWN    = 16  # HC_KNOWN + h: hole known to be rank index h (double exposure)
+
+# Live counts already exclude every card seen, the dealer upcard included, so
+# simulators are built with remove_upcard=False.
 
 def _counts_to_vec(counts: Dict[str, int]) -> list[int]:
     """Order counts as [A,2,3,4,5,6,7,8,9,T]"""
     return [int(counts.get(r, 0)) for r in _RANKS]
 
-def _hole_constraint(peek_mode: str, up: str) -> int:
-    pm = (peek_mode or "US").upper()
-    if pm == "US":
-        if up == "A": return HC_NOT_TEN
-        if up == "T": return HC_NOT_ACE
-    return HC_NONE
+def _peek_rules(peek_mode: str) -> Dict[str, bool]:
+    """Simulator peek flags for a peek mode: US peeks under A and T, anything else is ENHC."""
+    us = (peek_mode or "US").upper() == "US"
+    return {"peek_on_ace": us, "peek_on_ten": us}
 
 def p_bj(counts: Dict[str, int], up: str) -> float:
     """Dealer BJ probability from current counts (used for insurance/meta)."""
@@ -53,9 +56,8 @@ def stand_ev(pt_total: int, pt_soft: bool, up: str,
     """
     _require()
     vec = _counts_to_vec(counts)
-    sim = _Sim(vec, bool(h17), dp_depth, None)
-    hc  = _hole_constraint(peek_mode, up)
-    return float(sim.stand_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, int(hc), dp_depth))
+    sim = _Sim(vec, bool(h17), dp_depth, None, remove_upcard=False, **_peek_rules(peek_mode))
+    return float(sim.stand_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, HC_AUTO, dp_depth))
 
 def hit_then_stand_ev(pt_total: int, pt_soft: bool, up: str,
                       counts: Dict[str,int], peek_mode: str, *,
@@ -66,26 +68,22 @@ def hit_then_stand_ev(pt_total: int, pt_soft: bool, up: str,
     """
     _require()
     vec = _counts_to_vec(counts)
-    sim = _Sim(vec, bool(h17), dp_depth, None)
-    hc  = _hole_constraint(peek_mode, up)
-    return float(sim.hit_then_stand_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, int(hc), dp_depth))
+    sim = _Sim(vec, bool(h17), dp_depth, None, remove_upcard=False, **_peek_rules(peek_mode))
+    return float(sim.hit_then_stand_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, HC_AUTO, dp_depth))
 
 def double_ev_per_stake(pt_total: int, pt_soft: bool, up: str,
                         counts: Dict[str,int], peek_mode: str, *,
                         h17: bool = True,
                         dp_depth_dbl: Optional[int] = None) -> float:
     """
-    Per-stake EV for double (one card then stand).
-    If the rust function returns total ±2 units, we normalize to per-stake.
-    In our rustcore, double_ev == hit_then_stand_ev per-stake, so this is direct.
+    EV for double (one card then stand) in units of the original bet.
+    rustcore already scales the settled result by 2 for the doubled stake,
+    so this compares directly against stand/hit EVs.
     """
     _require()
     vec = _counts_to_vec(counts)
-    sim = _Sim(vec, bool(h17), None, dp_depth_dbl)
-    hc  = _hole_constraint(peek_mode, up)
-    ev  = float(sim.double_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, int(hc), dp_depth_dbl))
-    # If you ever switch rust to ±2 total semantics, divide by 2 here.
-    return ev
+    sim = _Sim(vec, bool(h17), None, dp_depth_dbl, remove_upcard=False, **_peek_rules(peek_mode))
+    return float(sim.double_ev(int(pt_total), bool(pt_soft), _INDEX[up], vec, HC_AUTO, dp_depth_dbl))
 
 def split_ev_per_stake(pair_rank: str, up: str,
                        counts: Dict[str,int], peek_mode: str, rules: Dict,
@@ -99,13 +97,15 @@ def split_ev_per_stake(pair_rank: str, up: str,
     if not hasattr(_Sim, "split_ev"):
         return None
     vec  = _counts_to_vec(counts)
-    sim  = _Sim(vec, bool(h17), None, None)
-    hc   = _hole_constraint(peek_mode, up)
     up_i = _INDEX[up]
     pr_i = _INDEX[pair_rank]
+    # `counts` are unseen cards, so the pair is already out; split_ev takes it back out itself.
+    # Put it back before building the simulator, whose shoe must hold every deck passed in.
+    vec[pr_i] += 2
+    sim  = _Sim(vec, bool(h17), None, None, remove_upcard=False, **_peek_rules(peek_mode))
     das  = bool(rules.get("das", True))
     spl1 = bool(rules.get("split_aces_one", True))
     try:
-        return float(sim.split_ev(int(pr_i), int(up_i), vec, int(hc), das, spl1, dp_depth_split))
+        return float(sim.split_ev(int(pr_i), int(up_i), vec, HC_AUTO, das, spl1, dp_depth_split))
     except Exception:
         return None
@@ -1,9 +1,16 @@
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
-use std::collections::HashMap;
+use pyo3::types::{PyBytes, PyDict};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
 
 // ---------- Rank helpers ----------
 type Count = i32;
 
+const RANK_LABELS: [&str; 10] = ["A", "2", "3", "4", "5", "6", "7", "8", "9", "T"];
+
 #[inline]
 fn rank_val(i: usize) -> i32 {
     match i {
@@ -13,10 +20,69 @@ fn rank_val(i: usize) -> i32 {
     }
 }
 
+/// Mean `rank_val` of the cards left; 0 for an empty deck.
+fn mean_card_value(counts: &[Count; 10]) -> f64 {
+    let rem: i32 = counts.iter().sum();
+    if rem <= 0 {
+        return 0.0;
+    }
+    let sum: i32 = counts.iter().enumerate().map(|(i, c)| rank_val(i) * c).sum();
+    (sum as f64) / (rem as f64)
+}
+
+/// C(n, k) as f64; 0 outside 0..=n.
+fn choose(n: i32, k: i32) -> f64 {
+    if k < 0 || k > n {
+        return 0.0;
+    }
+    (0..k).fold(1.0, |acc, i| acc * ((n - i) as f64) / ((i + 1) as f64))
+}
+
+/// Calls `f(rest, p)` for every multiset of `k` cards drawn blind from
+/// `counts`: `rest` is what is left and `p` its hypergeometric probability.
+fn for_each_removal(counts: &[Count; 10], k: i32, f: &mut impl FnMut(&[Count; 10], f64)) {
+    fn go(rest: &mut [Count; 10], r: usize, k: i32, w: f64, f: &mut impl FnMut(&[Count; 10], f64)) {
+        if k == 0 {
+            return f(rest, w);
+        }
+        if r == 10 {
+            return;
+        }
+        let c = rest[r].max(0);
+        for j in 0..=c.min(k) {
+            rest[r] = c - j;
+            go(rest, r + 1, k - j, w * choose(c, j), f);
+        }
+        rest[r] = c;
+    }
+    let n: i32 = counts.iter().map(|&c| c.max(0)).sum();
+    let mut rest = *counts;
+    go(&mut rest, 0, k, 1.0 / choose(n, k), f);
+}
+
+/// Aces count 1: the hard value a card adds to a hand.
+#[inline]
+fn hard_val(i: usize) -> i32 {
+    if i == 0 {
+        1
+    } else {
+        rank_val(i)
+    }
+}
+
+/// Add one card to a hand. `soft` means one ace is currently counted as 11,
+/// so a second ace always joins as 1 and the hand stays soft.
 #[inline]
 fn add_to(total: i32, soft: bool, r: usize) -> (i32, bool) {
     let mut t = total + rank_val(r);
-    let mut s = soft || r == 0;
+    let mut s = soft;
+    if r == 0 {
+        if s {
+            t -= 10;
+        } else {
+            s = true;
+        }
+    }
     if t > 21 && s {
         t -= 10;
         s = false;
@@ -28,78 +94,609 @@ fn add_to(total: i32, soft: bool, r: usize) -> (i32, bool) {
 const HC_NONE: i32 = 0;
 const HC_NOT_TEN: i32 = 1;
 const HC_NOT_ACE: i32 = 2;
+/// Derive the constraint from the upcard and the simulator's peek flags.
+const HC_AUTO: i32 = -1;
+/// `HC_KNOWN + h`: the hole is known to be rank `h` (double exposure).
+const HC_KNOWN: i32 = 16;
 
 #[inline]
 fn hole_allowed(hc: i32, idx: usize) -> bool {
     match hc {
         HC_NOT_TEN => idx != 9,
         HC_NOT_ACE => idx != 0,
+        hc if hc >= HC_KNOWN => idx as i32 == hc - HC_KNOWN,
         _ => true,
     }
 }
 
+// ---------- Shoe & count models ----------
+fn standard_shoe(num_decks: usize) -> [Count; 10] {
+    let nd = num_decks as Count;
+    let mut arr = [4 * nd; 10];
+    arr[9] = 16 * nd;
+    arr
+}
+
+/// Linear model of a `num_decks` shoe at a Hi-Lo true count. A true count of
+/// TC means H - L = TC per remaining deck, so each deck gains TC/2 high cards
+/// (split A:T as 4:16) and loses TC/2 low cards (split evenly over 2-6); 7-9
+/// are untouched. Counts are rounded to whole cards and floored at zero.
+fn shoe_at_true_count(true_count: f64, num_decks: usize) -> [Count; 10] {
+    shoe_at_count(true_count, num_decks, &COUNT_SYSTEMS[0].1)
+}
+
+/// `shoe_at_true_count` for any balanced count: each rank moves against its
+/// tag in proportion to tag × share of the shoe, scaled so the cards gone
+/// carry a running count of TC per deck. For Hi-Lo this is exactly the
+/// model above.
+fn shoe_at_count(true_count: f64, num_decks: usize, tags: &[f64; 10]) -> [Count; 10] {
+    let base = standard_shoe(num_decks);
+    let weight: f64 = (0..10).map(|r| tags[r] * tags[r] * base[r] as f64).sum();
+    let mut arr = [0; 10];
+    for (r, a) in arr.iter_mut().enumerate() {
+        let shift = -true_count * (num_decks as f64) * tags[r] * (base[r] as f64) / weight;
+        *a = ((base[r] as f64) + shift).round().max(0.0) as Count;
+    }
+    arr
+}
+
+/// Per-card tags (A,2..9,T) of the counts `running_count` knows, and whether
+/// each is balanced (tags sum to zero over a deck).
+const COUNT_SYSTEMS: [(&str, [f64; 10], bool); 5] = [
+    ("hi_lo", [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0], true),
+    ("ko", [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, -1.0], false),
+    ("zen", [-1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 0.0, 0.0, -2.0], true),
+    ("hi_opt_ii", [0.0, 1.0, 1.0, 2.0, 2.0, 1.0, 1.0, 0.0, 0.0, -2.0], true),
+    ("halves", [-1.0, 0.5, 1.0, 1.0, 1.5, 1.0, 0.5, 0.0, -0.5, -1.0], true),
+];
+
+fn count_tags(name: &str) -> PyResult<([f64; 10], bool)> {
+    COUNT_SYSTEMS
+        .iter()
+        .find(|(n, _, _)| *n == name)
+        .map(|&(_, tags, balanced)| (tags, balanced))
+        .ok_or_else(|| {
+            let names: Vec<&str> = COUNT_SYSTEMS.iter().map(|(n, _, _)| *n).collect();
+            PyValueError::new_err(format!("count system must be one of {:?}, got {:?}", names, name))
+        })
+}
+
+/// Running count of the dealt `cards` (rank indices) under `system`: the sum
+/// of their tags. Systems: "hi_lo", "ko", "zen", "hi_opt_ii", "halves".
+#[pyfunction]
+#[pyo3(signature = (cards, system="hi_lo"))]
+fn running_count(cards: Vec<usize>, system: &str) -> PyResult<f64> {
+    let (tags, _) = count_tags(system)?;
+    cards.iter().try_fold(0.0, |rc, &r| match tags.get(r) {
+        Some(t) => Ok(rc + t),
+        None => Err(PyValueError::new_err(format!("card rank index {} out of range 0..9", r))),
+    })
+}
+
+/// True count after `cards` have been dealt from a `num_decks` shoe: the
+/// running count per deck still undealt. Balanced systems only; an
+/// unbalanced count such as KO is played off its running count.
+#[pyfunction]
+#[pyo3(signature = (cards, num_decks, system="hi_lo"))]
+fn true_count(cards: Vec<usize>, num_decks: usize, system: &str) -> PyResult<f64> {
+    let (_, balanced) = count_tags(system)?;
+    if !balanced {
+        return Err(PyValueError::new_err(format!("{:?} is unbalanced; use running_count", system)));
+    }
+    let left = (52 * num_decks) as f64 - cards.len() as f64;
+    if left <= 0.0 {
+        return Err(PyValueError::new_err("no cards left in the shoe"));
+    }
+    Ok(running_count(cards, system)? / (left / 52.0))
+}
+
+// ---------- Dealer policy ----------
+// Indexed by [total][soft as usize] -> dealer hits? Totals below 17 must hit.
+type HitTable = [[bool; 2]; 22];
+
+/// What happens when the dealer must hit but the shoe is empty.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum EmptyShoe {
+    /// Treat the current total as final, clamped up to 17.
+    Clamp,
+    /// The round is void and every hand pushes.
+    Push,
+}
+
+impl EmptyShoe {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "clamp" => Ok(EmptyShoe::Clamp),
+            "push" => Ok(EmptyShoe::Push),
+            _ => Err(PyValueError::new_err(format!(
+                "empty_shoe_policy must be \"clamp\" or \"push\", got {:?}",
+                name
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EmptyShoe::Clamp => "clamp",
+            EmptyShoe::Push => "push",
+        }
+    }
+}
+
+/// How every 21-vs-21 meeting settles, per unit of the hand's stake.
+///
+/// | player      | dealer natural       | dealer drawn 21      |
+/// |-------------|----------------------|----------------------|
+/// | natural     | `natural_vs_natural` | `bj_payout` (*)      |
+/// | drawn 21    | `drawn21_vs_natural` | `drawn21_vs_drawn21` |
+///
+/// (*) A natural is paid before the dealer draws, so it never meets a drawn
+/// 21. `drawn21_vs_natural` is scaled by the hand's usual natural loss, so
+/// OBO and full-refund rules still apply on top of it.
+#[pyclass]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Settlement {
+    #[pyo3(get, set)]
+    natural_vs_natural: f64,
+    #[pyo3(get, set)]
+    drawn21_vs_natural: f64,
+    #[pyo3(get, set)]
+    drawn21_vs_drawn21: f64,
+}
+
+impl Default for Settlement {
+    fn default() -> Self {
+        Settlement {
+            natural_vs_natural: 0.0,
+            drawn21_vs_natural: -1.0,
+            drawn21_vs_drawn21: 0.0,
+        }
+    }
+}
+
+#[pymethods]
+impl Settlement {
+    /// Defaults are the standard rules: naturals push, a dealer natural beats a
+    /// drawn 21, and drawn 21s push.
+    #[new]
+    #[pyo3(signature = (natural_vs_natural=0.0, drawn21_vs_natural=-1.0, drawn21_vs_drawn21=0.0))]
+    fn new(natural_vs_natural: f64, drawn21_vs_natural: f64, drawn21_vs_drawn21: f64) -> Self {
+        Settlement {
+            natural_vs_natural,
+            drawn21_vs_natural,
+            drawn21_vs_drawn21,
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Settlement(natural_vs_natural={:?}, drawn21_vs_natural={:?}, drawn21_vs_drawn21={:?})",
+            self.natural_vs_natural, self.drawn21_vs_natural, self.drawn21_vs_drawn21,
+        )
+    }
+
+    fn __eq__(&self, other: PyRef<'_, Self>) -> bool {
+        *self == *other
+    }
+}
+
+/// Everything the dealer runout needs to know about how the dealer plays,
+/// plus the 21-vs-21 settlement table the result is settled against.
+#[derive(Clone, Copy)]
+struct DealerPolicy {
+    hits: HitTable,
+    empty_shoe: EmptyShoe,
+    settle21: Settlement,
+    /// Hard-ace game: aces only ever count 1, so no hand is soft and no two
+    /// cards make a natural.
+    aces_hard: bool,
+    /// Dealer 22 pushes every hand still standing. It is booked as a void
+    /// runout (no mass in any bin), like an empty-shoe push.
+    push_22: bool,
+    /// Runouts come from `exact_runout` and are rounded to f64 only at the end.
+    exact: bool,
+    soft17_cards: Soft17Cards,
+    /// Double exposure: the dealer wins every tie of stood totals.
+    ties_lose: bool,
+    /// Per-upcard soft-17 rule; `for_up` applies it to `hits`.
+    h17_by_upcard: Option<[bool; 10]>,
+    /// Share of a win the player keeps, 1 - `win_commission`.
+    win_pay: f64,
+}
+
+impl DealerPolicy {
+    #[inline]
+    fn hits(&self, total: i32, soft: bool) -> bool {
+        self.hits[total as usize][soft as usize]
+    }
+
+    /// `hits` for a dealer holding `cards` cards (see `Soft17Cards`).
+    #[inline]
+    fn hits_with(&self, total: i32, soft: bool, cards: u8) -> bool {
+        match self.soft17_cards {
+            Soft17Cards::HitMulti if total == 17 && soft => cards > 2,
+            Soft17Cards::HitTwo if total == 17 && soft => cards == 2,
+            _ => self.hits(total, soft),
+        }
+    }
+
+    /// Card count as the memo sees it: only 1, 2 and "3 or more" can decide
+    /// anything, and nothing at all without a card-count rule.
+    #[inline]
+    fn cards_key(&self, cards: u8) -> u8 {
+        if self.soft17_cards == Soft17Cards::Any {
+            0
+        } else {
+            cards.min(3)
+        }
+    }
+
+    /// `add_to` under this game's ace rule.
+    #[inline]
+    fn add(&self, total: i32, soft: bool, r: usize) -> (i32, bool) {
+        if self.aces_hard {
+            (total + hard_val(r), false)
+        } else {
+            add_to(total, soft, r)
+        }
+    }
+
+    #[inline]
+    fn natural(&self, up: usize, hole: usize) -> bool {
+        !self.aces_hard && is_natural(up, hole)
+    }
+
+    /// A settlement after commission: wins scaled by `win_pay`, the rest whole.
+    #[inline]
+    fn pay(&self, v: f64) -> f64 {
+        if v > 0.0 {
+            v * self.win_pay
+        } else {
+            v
+        }
+    }
+
+    /// `hits` against `up`, for callers that keep one policy for every upcard.
+    #[inline]
+    fn hits_vs(&self, up: usize, total: i32, soft: bool) -> bool {
+        match self.h17_by_upcard {
+            Some(by_up) if total == 17 && soft => by_up[up],
+            _ => self.hits(total, soft),
+        }
+    }
+
+    /// The table in force against `up`: soft 17 hits as `h17_by_upcard` says.
+    #[inline]
+    fn for_up(&self, up: usize) -> DealerPolicy {
+        let mut p = *self;
+        if let Some(by_up) = self.h17_by_upcard {
+            p.hits[17][1] = by_up[up];
+        }
+        p
+    }
+
+    /// Memo tag for the soft-17 entry under a per-upcard rule, so runouts
+    /// against upcards with different rules never share an entry.
+    #[inline]
+    fn rule_key(&self) -> u8 {
+        self.h17_by_upcard.map_or(0, |_| 1 + self.hits(17, true) as u8)
+    }
+}
+
+fn policy_s17() -> HitTable {
+    let mut p = [[false; 2]; 22];
+    for row in p.iter_mut().take(17) {
+        *row = [true, true];
+    }
+    p
+}
+
+fn policy_h17() -> HitTable {
+    let mut p = policy_s17();
+    p[17][1] = true;
+    p
+}
+
+fn policy_from_rows(rows: &[[bool; 2]]) -> PyResult<HitTable> {
+    if rows.len() != 22 {
+        return Err(PyValueError::new_err("dealer_policy must have 22 rows (totals 0..21)"));
+    }
+    let mut p = [[false; 2]; 22];
+    p.copy_from_slice(rows);
+    if p.iter().take(17).any(|row| !row[0] || !row[1]) {
+        return Err(PyValueError::new_err("dealer_policy must hit every total below 17"));
+    }
+    Ok(p)
+}
+
+/// S17 dealer policy table, as rows of [hard hits, soft hits] for totals 0..21.
+#[pyfunction]
+fn s17_policy() -> Vec<[bool; 2]> {
+    policy_s17().to_vec()
+}
+
+/// H17 dealer policy table, as rows of [hard hits, soft hits] for totals 0..21.
+#[pyfunction]
+fn h17_policy() -> Vec<[bool; 2]> {
+    policy_h17().to_vec()
+}
+
+/// Dealer policy table that hits every soft total below `floor` (17..=21)
+/// and hard totals below 17: 17 is S17, 18 is H17, 19 also hits soft 18.
+#[pyfunction]
+fn dealer_hit_soft_below(floor: i32) -> PyResult<Vec<[bool; 2]>> {
+    if !(17..=21).contains(&floor) {
+        return Err(PyValueError::new_err(format!("soft-stand floor must be in 17..=21, got {}", floor)));
+    }
+    let mut p = policy_s17();
+    for row in p.iter_mut().take(floor as usize).skip(17) {
+        row[1] = true;
+    }
+    Ok(p.to_vec())
+}
+
+/// Soft 17 decided by the dealer's card count, overriding the table's soft-17
+/// entry: `HitMulti` stands on a two-card soft 17 (A,6) and hits one of three
+/// or more cards; `HitTwo` is the reverse. `Any` leaves it to the table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Soft17Cards {
+    Any,
+    HitMulti,
+    HitTwo,
+}
+
+impl Soft17Cards {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "any" => Ok(Soft17Cards::Any),
+            "hit_multi" => Ok(Soft17Cards::HitMulti),
+            "hit_two" => Ok(Soft17Cards::HitTwo),
+            _ => Err(PyValueError::new_err(format!(
+                "soft17_cards must be \"any\", \"hit_multi\" or \"hit_two\", got {:?}",
+                name
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Soft17Cards::Any => "any",
+            Soft17Cards::HitMulti => "hit_multi",
+            Soft17Cards::HitTwo => "hit_two",
+        }
+    }
+}
+
+/// Validates a `max_player_cards` rule.
+fn card_cap(cap: Option<usize>) -> PyResult<Option<usize>> {
+    match cap {
+        Some(m) if m < 3 => Err(PyValueError::new_err(format!("max_player_cards must be at least 3, got {}", m))),
+        _ => Ok(cap),
+    }
+}
+
+// ---------- Split rules ----------
+/// Which split hands may double. `split_depth` counts the splits that led to
+/// a hand: 1 for the two hands of the first split, 2+ only once a resplit
+/// has happened (`split_ev` with `max_hands` above 2; every other split
+/// path is a single split, where `FirstSplitOnly` plays like `All`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DasRule {
+    None,
+    All,
+    FirstSplitOnly,
+}
+
+impl DasRule {
+    fn from_flag(das: bool) -> Self {
+        if das {
+            DasRule::All
+        } else {
+            DasRule::None
+        }
+    }
+
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "none" => Ok(DasRule::None),
+            "all" => Ok(DasRule::All),
+            "first_split_only" => Ok(DasRule::FirstSplitOnly),
+            _ => Err(PyValueError::new_err(format!(
+                "das_rule must be \"none\", \"all\" or \"first_split_only\", got {:?}",
+                name
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DasRule::None => "none",
+            DasRule::All => "all",
+            DasRule::FirstSplitOnly => "first_split_only",
+        }
+    }
+
+    fn allows(self, split_depth: usize) -> bool {
+        match self {
+            DasRule::None => false,
+            DasRule::All => true,
+            DasRule::FirstSplitOnly => split_depth <= 1,
+        }
+    }
+}
+
+/// `split_ev`'s `das` argument: the legacy bool or a `DasRule` name.
+#[derive(FromPyObject)]
+enum DasArg {
+    Flag(bool),
+    Rule(String),
+}
+
+/// One `optimal_action_batch` entry, passed from Python as the tuple
+/// `(pt_total, pt_soft, is_pair, pair_rank, up, deck, hole_constraint)`.
+#[derive(FromPyObject)]
+struct Scenario(i32, bool, bool, usize, usize, Vec<Count>, i32);
+
+impl DasArg {
+    fn rule(&self) -> PyResult<DasRule> {
+        match self {
+            DasArg::Flag(b) => Ok(DasRule::from_flag(*b)),
+            DasArg::Rule(name) => DasRule::parse(name),
+        }
+    }
+}
+
+// ---------- Double rules ----------
+/// Which two-card totals may double. The initial hand and split hands each
+/// carry their own rule, since many casinos restrict post-split doubles more.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DoubleRule {
+    Any,
+    NineToEleven,
+    TenToEleven,
+}
+
+impl DoubleRule {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "any" => Ok(DoubleRule::Any),
+            "9-11" => Ok(DoubleRule::NineToEleven),
+            "10-11" => Ok(DoubleRule::TenToEleven),
+            _ => Err(PyValueError::new_err(format!(
+                "double rule must be \"any\", \"9-11\" or \"10-11\", got {:?}",
+                name
+            ))),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DoubleRule::Any => "any",
+            DoubleRule::NineToEleven => "9-11",
+            DoubleRule::TenToEleven => "10-11",
+        }
+    }
+
+    /// Soft hands are 12 or more, so the restricted rules are hard-only.
+    fn allows(self, total: i32) -> bool {
+        match self {
+            DoubleRule::Any => true,
+            DoubleRule::NineToEleven => (9..=11).contains(&total),
+            DoubleRule::TenToEleven => (10..=11).contains(&total),
+        }
+    }
+}
+
+// ---------- Instrumentation (feature "instrument") ----------
+// Per-thread counters for the dealer recursion. Compiled out entirely in the
+// default build, so the hot path carries no extra work.
+#[cfg(feature = "instrument")]
+mod instrument {
+    use std::cell::Cell;
+
+    thread_local! {
+        static NODES: Cell<u64> = const { Cell::new(0) };
+        static HITS: Cell<u64> = const { Cell::new(0) };
+        static MISSES: Cell<u64> = const { Cell::new(0) };
+    }
+
+    fn bump(c: &'static std::thread::LocalKey<Cell<u64>>) {
+        c.with(|v| v.set(v.get() + 1));
+    }
+
+    pub fn node() {
+        bump(&NODES);
+    }
+
+    pub fn hit() {
+        bump(&HITS);
+    }
+
+    pub fn miss() {
+        bump(&MISSES);
+    }
+
+    pub fn reset() {
+        for c in [&NODES, &HITS, &MISSES] {
+            c.with(|v| v.set(0));
+        }
+    }
+
+    /// (nodes, memo hits, memo misses) since the last reset.
+    pub fn snapshot() -> (u64, u64, u64) {
+        (NODES.with(Cell::get), HITS.with(Cell::get), MISSES.with(Cell::get))
+    }
+}
+
 // ---------- Dealer runout (exact, memoized) ----------
 #[derive(Hash, PartialEq, Eq)]
 struct DealerKey {
-    counts: [i16; 10],
+    // Full-width counts: narrowing them would let distinct oversized decks
+    // collide on one key and return each other's runouts.
+    counts: [Count; 10],
     total: i16,
     soft: u8,
-    h17: u8,
+    /// Dealer cards held, for a card-count soft-17 rule (0 without one).
+    cards: u8,
+    /// `DealerPolicy::rule_key`.
+    rule: u8,
 }
 
-fn encode_counts(counts: &[Count; 10]) -> [i16; 10] {
-    let mut out = [0i16; 10];
-    for (i, c) in counts.iter().enumerate() {
-        out[i] = *c as i16;
-    }
-    out
-}
+type Memo = HashMap<DealerKey, [f64; 6]>;
 
 fn dealer_dist_from_total(
     counts: &mut [Count; 10],
     total: i32,
     soft: bool,
-    h17: bool,
-    memo: &mut HashMap<DealerKey, [f64; 6]>, // bins: 17,18,19,20,21,22(bust)
+    cards: u8,
+    policy: &DealerPolicy,
+    memo: &mut Memo, // bins: 17,18,19,20,21,22(bust)
 ) -> [f64; 6] {
+    #[cfg(feature = "instrument")]
+    instrument::node();
     if total > 21 {
         let mut v = [0.0; 6];
-        v[5] = 1.0;
+        if !(policy.push_22 && total == 22) {
+            v[5] = 1.0;
+        }
         return v;
     }
-    if total > 17 {
+    if !policy.hits_with(total, soft, cards) {
         let mut v = [0.0; 6];
         v[(total - 17) as usize] = 1.0;
         return v;
     }
-    if total == 17 {
-        if soft && h17 {
-            // must hit
-        } else {
-            let mut v = [0.0; 6];
-            v[0] = 1.0;
-            return v;
-        }
-    }
 
     let key = DealerKey {
-        counts: encode_counts(counts),
+        counts: *counts,
         total: total as i16,
         soft: soft as u8,
-        h17: h17 as u8,
+        cards: policy.cards_key(cards),
+        rule: policy.rule_key(),
     };
     if let Some(v) = memo.get(&key) {
+        #[cfg(feature = "instrument")]
+        instrument::hit();
         return *v;
     }
+    #[cfg(feature = "instrument")]
+    instrument::miss();
+
+    if policy.exact {
+        if let Some((w, den)) = exact_runout(counts, total, soft, cards, policy) {
+            let v = w.map(|x| (x as f64) / (den as f64));
+            memo.insert(key, v);
+            return v;
+        }
+    }
 
     let rem: i32 = counts.iter().sum();
     if rem <= 0 {
+        // The dealer must hit but nothing is left. A void round carries no
+        // mass in any bin, so it settles as a push everywhere downstream.
         let mut v = [0.0; 6];
-        let t = total.clamp(17, 22);
-        if t <= 21 {
-            v[(t - 17) as usize] = 1.0;
-        } else {
-            v[5] = 1.0;
+        if policy.empty_shoe == EmptyShoe::Clamp {
+            v[(total.max(17) - 17) as usize] = 1.0;
         }
         memo.insert(key, v);
         return v;
@@ -113,8 +710,8 @@ fn dealer_dist_from_total(
         }
         let p = (c as f64) / (rem as f64);
         counts[r] -= 1;
-        let (nt, ns) = add_to(total, soft, r);
-        let sub = dealer_dist_from_total(counts, nt, ns, h17, memo);
+        let (nt, ns) = policy.add(total, soft, r);
+        let sub = dealer_dist_from_total(counts, nt, ns, cards.saturating_add(1), policy, memo);
         for i in 0..6 {
             out[i] += p * sub[i];
         }
@@ -124,228 +721,6117 @@ fn dealer_dist_from_total(
     out
 }
 
-#[inline]
-fn dealer_dist_with_two(
+/// Probability the runout from (total, soft) stops on a soft 17: the mass of
+/// the 17 bin that an H17 rule would hit instead. The same recursion as
+/// `dealer_dist_from_total`, tracking only that one arrival under its own memo;
+/// an empty shoe ends nothing softly here, as the clamp lands on hard totals.
+fn dealer_soft17_from(
     counts: &mut [Count; 10],
-    up: usize,
-    hole: usize,
-    h17: bool,
-    memo: &mut HashMap<DealerKey, [f64; 6]>,
-) -> [f64; 6] {
-    let (t, s) = add_to(rank_val(up), false, hole);
-    dealer_dist_from_total(counts, t, s, h17, memo)
-}
-
-#[inline]
-fn settle_vs_player(pt: i32, dealer_bin: usize) -> f64 {
-    if pt > 21 {
-        return -1.0;
+    total: i32,
+    soft: bool,
+    cards: u8,
+    policy: &DealerPolicy,
+    memo: &mut HashMap<DealerKey, f64>,
+) -> f64 {
+    if total > 21 {
+        return 0.0;
     }
-    if dealer_bin == 5 {
-        return 1.0;
+    if !policy.hits_with(total, soft, cards) {
+        return if total == 17 && soft { 1.0 } else { 0.0 };
     }
-    let d = 17 + (dealer_bin as i32);
-    if pt > d {
-        1.0
-    } else if pt < d {
-        -1.0
-    } else {
-        0.0
+    let key = DealerKey {
+        counts: *counts,
+        total: total as i16,
+        soft: soft as u8,
+        cards: policy.cards_key(cards),
+        rule: policy.rule_key(),
+    };
+    if let Some(v) = memo.get(&key) {
+        return *v;
+    }
+    let rem: i32 = counts.iter().sum();
+    let mut out = 0.0;
+    for r in 0..10 {
+        let c = counts[r];
+        if c <= 0 {
+            continue;
+        }
+        let p = (c as f64) / (rem as f64);
+        counts[r] -= 1;
+        let (nt, ns) = policy.add(total, soft, r);
+        out += p * dealer_soft17_from(counts, nt, ns, cards.saturating_add(1), policy, memo);
+        counts[r] += 1;
     }
+    memo.insert(key, out);
+    out
 }
 
-// ---------- PyO3 class ----------
-#[pyclass]
-pub struct BlackjackSimulator {
-    h17: bool,
-    dp_depth: usize,
-    dp_depth_dbl: usize,
+// ---------- Exact runouts ----------
+/// Runout weights in integers. A state with `n` cards left that can draw at
+/// most `k` more stores each bin's probability times `falling(n, k)`; a
+/// draw of rank `r` then scales its child's weights by the rank's count, so
+/// the recursion never divides and the result is exact.
+type ExactMemo = HashMap<DealerKey, [u128; 6]>;
+
+/// n * (n-1) * ... * (n-k+1), or `None` past u128.
+fn falling(n: i32, k: i32) -> Option<u128> {
+    (0..k).try_fold(1u128, |acc, i| acc.checked_mul((n - i) as u128))
 }
 
-#[pymethods]
-impl BlackjackSimulator {
-    #[new]
-    fn new(_shoe_counts: Vec<Count>, h17: bool, dp_depth: Option<usize>, dp_depth_dbl: Option<usize>) -> PyResult<Self> {
-        Ok(Self {
-            h17,
-            dp_depth: dp_depth.unwrap_or(3),
-            dp_depth_dbl: dp_depth_dbl.unwrap_or(4),
-        })
+/// Upper bound on the cards a dealer at (total, soft) can still draw: every
+/// draw but the last leaves the hard total at most 21, so take the smallest
+/// cards while they fit, plus one.
+fn max_draws(counts: &[Count; 10], total: i32, soft: bool) -> i32 {
+    let mut hard = if soft { total - 10 } else { total };
+    let mut n = 0;
+    for (r, &c) in counts.iter().enumerate() {
+        for _ in 0..c.max(0) {
+            if hard + hard_val(r) > 21 {
+                return n + 1;
+            }
+            hard += hard_val(r);
+            n += 1;
+        }
     }
+    n
+}
 
-    /// Stand EV (per-stake), conditional on US peek via hole_constraint.
-    fn stand_ev(
-        &self,
-        pt_total: i32,
-        pt_soft: bool,
-        up: usize,
-        deck: Vec<Count>,
+fn exact_weights(counts: &mut [Count; 10], total: i32, soft: bool, cards: u8, k: i32, policy: &DealerPolicy, memo: &mut ExactMemo) -> [u128; 6] {
+    let rem: i32 = counts.iter().sum();
+    let scale = falling(rem, k).unwrap_or(0);
+    let mut v = [0u128; 6];
+    if total > 21 {
+        if !(policy.push_22 && total == 22) {
+            v[5] = scale;
+        }
+        return v;
+    }
+    if !policy.hits_with(total, soft, cards) {
+        v[(total - 17) as usize] = scale;
+        return v;
+    }
+    if rem <= 0 {
+        if policy.empty_shoe == EmptyShoe::Clamp {
+            v[(total.max(17) - 17) as usize] = scale;
+        }
+        return v;
+    }
+    let key = DealerKey {
+        counts: *counts,
+        total: total as i16,
+        soft: soft as u8,
+        cards: policy.cards_key(cards),
+        rule: policy.rule_key(),
+    };
+    if let Some(w) = memo.get(&key) {
+        return *w;
+    }
+    for r in 0..10 {
+        let c = counts[r];
+        if c <= 0 {
+            continue;
+        }
+        counts[r] -= 1;
+        let (nt, ns) = policy.add(total, soft, r);
+        let sub = exact_weights(counts, nt, ns, cards.saturating_add(1), k - 1, policy, memo);
+        for i in 0..6 {
+            v[i] += (c as u128) * sub[i];
+        }
+        counts[r] += 1;
+    }
+    memo.insert(key, v);
+    v
+}
+
+/// Exact runout from (total, soft) as bin numerators over one common
+/// denominator, or `None` when that denominator would overflow u128 (about
+/// two decks' worth of small cards). Bins still sum to the denominator less
+/// any void mass.
+fn exact_runout(counts: &[Count; 10], total: i32, soft: bool, cards: u8, policy: &DealerPolicy) -> Option<([u128; 6], u128)> {
+    let rem: i32 = counts.iter().sum();
+    let k = max_draws(counts, total, soft).min(rem.max(0));
+    let den = falling(rem, k)?;
+    let mut arr = *counts;
+    Some((exact_weights(&mut arr, total, soft, cards, k, policy, &mut ExactMemo::new()), den))
+}
+
+/// Expected cards the dealer still draws from (total, soft) before stopping
+/// (standing, busting, or an empty shoe), under its own memo.
+fn dealer_cards_from(
+    counts: &mut [Count; 10],
+    total: i32,
+    soft: bool,
+    cards: u8,
+    policy: &DealerPolicy,
+    memo: &mut HashMap<DealerKey, f64>,
+) -> f64 {
+    if total > 21 || !policy.hits_with(total, soft, cards) {
+        return 0.0;
+    }
+    let key = DealerKey {
+        counts: *counts,
+        total: total as i16,
+        soft: soft as u8,
+        cards: policy.cards_key(cards),
+        rule: policy.rule_key(),
+    };
+    if let Some(v) = memo.get(&key) {
+        return *v;
+    }
+    let rem: i32 = counts.iter().sum();
+    let mut out = 0.0;
+    for r in 0..10 {
+        let c = counts[r];
+        if c <= 0 {
+            continue;
+        }
+        let p = (c as f64) / (rem as f64);
+        counts[r] -= 1;
+        let (nt, ns) = policy.add(total, soft, r);
+        out += p * (1.0 + dealer_cards_from(counts, nt, ns, cards.saturating_add(1), policy, memo));
+        counts[r] += 1;
+    }
+    memo.insert(key, out);
+    out
+}
+
+#[inline]
+fn dealer_dist_with_two(
+    counts: &mut [Count; 10],
+    up: usize,
+    hole: usize,
+    policy: &DealerPolicy,
+    memo: &mut Memo,
+) -> [f64; 6] {
+    let policy = &policy.for_up(up);
+    let (t0, s0) = policy.add(0, false, up);
+    let (t, s) = policy.add(t0, s0, hole);
+    dealer_dist_from_total(counts, t, s, 2, policy, memo)
+}
+
+/// Resolution order: a busted player loses first, whatever the dealer ends
+/// on (so player 22 vs dealer 22 is a loss even under push-22); only then is
+/// the dealer's bin compared. Void runout mass (push-22, empty-shoe push) is
+/// never passed here and settles as a push.
+#[inline]
+fn settle_vs_player(pt: i32, dealer_bin: usize, policy: &DealerPolicy) -> f64 {
+    if pt > 21 {
+        return -1.0;
+    }
+    if dealer_bin == 5 {
+        return policy.win_pay;
+    }
+    let d = 17 + (dealer_bin as i32);
+    if pt > d {
+        policy.win_pay
+    } else if pt < d || policy.ties_lose {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+#[inline]
+fn settle_dist(pt: i32, dist: &[f64; 6], policy: &DealerPolicy) -> f64 {
+    dist.iter()
+        .enumerate()
+        .map(|(i, p)| settle_vs_player(pt, i, policy) * p)
+        .sum()
+}
+
+fn dist_array(dealer_dist: &[f64]) -> PyResult<[f64; 6]> {
+    if dealer_dist.len() != 6 {
+        return Err(PyValueError::new_err(format!(
+            "dealer_dist must have 6 bins [17,18,19,20,21,bust], got {}",
+            dealer_dist.len()
+        )));
+    }
+    let mut d = [0.0; 6];
+    d.copy_from_slice(dealer_dist);
+    Ok(d)
+}
+
+/// `deck` as [A,2..9,T] counts; errors unless it has exactly ten buckets,
+/// none negative.
+fn deck_array(deck: &[Count], what: &str) -> PyResult<[Count; 10]> {
+    let arr: [Count; 10] = deck
+        .try_into()
+        .map_err(|_| PyValueError::new_err(format!("{} must have 10 rank buckets [A,2..9,T], got {}", what, deck.len())))?;
+    if let Some(r) = arr.iter().position(|&c| c < 0) {
+        return Err(PyValueError::new_err(format!("{} has a negative count ({}) in bucket {}", what, arr[r], r)));
+    }
+    Ok(arr)
+}
+
+/// Dealer final distribution for one upcard, averaged over the allowed hole
+/// cards and renormalized (US peek conditioning). All zeros if no hole is drawable.
+fn dealer_dist_for_up(
+    counts: &[Count; 10],
+    up: usize,
+    hole_constraint: i32,
+    policy: &DealerPolicy,
+    memo: &mut Memo,
+) -> [f64; 6] {
+    let mut arr = *counts;
+    let mut out = [0.0; 6];
+    let rem: i32 = arr.iter().sum();
+    if rem <= 0 {
+        return out;
+    }
+    let mut denom = 0.0;
+    for h in 0..10 {
+        let c = arr[h];
+        if c <= 0 || !hole_allowed(hole_constraint, h) {
+            continue;
+        }
+        let p = (c as f64) / (rem as f64);
+        arr[h] -= 1;
+        let dist = dealer_dist_with_two(&mut arr, up, h, policy, memo);
+        for (o, d) in out.iter_mut().zip(dist.iter()) {
+            *o += p * d;
+        }
+        denom += p;
+        arr[h] += 1;
+    }
+    if denom > 0.0 {
+        for o in out.iter_mut() {
+            *o /= denom;
+        }
+    }
+    out
+}
+
+#[inline]
+fn is_natural(up: usize, hole: usize) -> bool {
+    (up == 0 && hole == 9) || (up == 9 && hole == 0)
+}
+
+/// Probability the hole completes a two-card 21 (ten under an ace, ace under a ten).
+fn natural_prob(counts: &[Count; 10], up: usize) -> f64 {
+    let rem: i32 = counts.iter().sum();
+    if rem <= 0 {
+        return 0.0;
+    }
+    let hole = match up {
+        0 => 9,
+        9 => 0,
+        _ => return 0.0,
+    };
+    (counts[hole].max(0) as f64) / (rem as f64)
+}
+
+/// Hard 0/1 hole weights equivalent to a `hole_constraint`.
+fn constraint_weights(hole_constraint: i32) -> [f64; 10] {
+    let mut w = [0.0; 10];
+    for (h, x) in w.iter_mut().enumerate() {
+        if hole_allowed(hole_constraint, h) {
+            *x = 1.0;
+        }
+    }
+    w
+}
+
+/// Splits the weighted hole average into the dealer-natural probability and
+/// the distribution given no natural. Each hole's draw probability is scaled by
+/// `weights[h]` and renormalized; with constraint weights under US peek the
+/// natural holes carry no mass, so the probability is 0.
+fn dealer_dist_split_natural(
+    counts: &[Count; 10],
+    up: usize,
+    weights: &[f64; 10],
+    policy: &DealerPolicy,
+    memo: &mut Memo,
+) -> (f64, [f64; 6]) {
+    let mut arr = *counts;
+    let mut out = [0.0; 6];
+    let rem: i32 = arr.iter().sum();
+    if rem <= 0 {
+        return (0.0, out);
+    }
+    let (mut p_nat, mut p_rest) = (0.0, 0.0);
+    for h in 0..10 {
+        let c = arr[h];
+        if c <= 0 || weights[h] <= 0.0 {
+            continue;
+        }
+        let p = weights[h] * (c as f64) / (rem as f64);
+        if policy.natural(up, h) {
+            p_nat += p;
+            continue;
+        }
+        arr[h] -= 1;
+        let dist = dealer_dist_with_two(&mut arr, up, h, policy, memo);
+        for (o, d) in out.iter_mut().zip(dist.iter()) {
+            *o += p * d;
+        }
+        p_rest += p;
+        arr[h] += 1;
+    }
+    if p_rest > 0.0 {
+        for o in out.iter_mut() {
+            *o /= p_rest;
+        }
+    }
+    let denom = p_nat + p_rest;
+    (if denom > 0.0 { p_nat / denom } else { 0.0 }, out)
+}
+
+/// Per-stake result of an initial-hand natural: `bj_payout`, except against a
+/// dealer natural the hole weights still allow (ENHC / no peek), which settles
+/// at `vs_natural` (a push by default).
+fn settle_natural(counts: &[Count; 10], up: usize, weights: &[f64; 10], bj_payout: f64, vs_natural: f64) -> f64 {
+    let p_push = weighted_natural_prob(counts, up, weights);
+    (1.0 - p_push) * bj_payout + p_push * vs_natural
+}
+
+/// Hole weights that rule out the ranks in `ranks` (`what` names them in the
+/// error for an out-of-range index).
+fn exclusion_weights(ranks: &[usize], what: &str) -> PyResult<[f64; 10]> {
+    let mut w = [1.0; 10];
+    for &r in ranks {
+        if r >= 10 {
+            return Err(PyValueError::new_err(format!("{} rank index {} out of range 0..9", what, r)));
+        }
+        w[r] = 0.0;
+    }
+    Ok(w)
+}
+
+/// Chance the hole completes a dealer natural, with each hole rank's draw
+/// probability scaled by `weights[h]` and renormalized.
+fn weighted_natural_prob(counts: &[Count; 10], up: usize, weights: &[f64; 10]) -> f64 {
+    let (mut p_nat, mut p_all) = (0.0, 0.0);
+    for h in 0..10 {
+        if counts[h] <= 0 || weights[h] <= 0.0 {
+            continue;
+        }
+        let p = weights[h] * (counts[h] as f64);
+        p_all += p;
+        if is_natural(up, h) {
+            p_nat += p;
+        }
+    }
+    if p_all > 0.0 {
+        p_nat / p_all
+    } else {
+        0.0
+    }
+}
+
+/// `settle` averaged over the one card the sibling of a split ace draws
+/// before the dealer plays, so each one-card ace hand faces the runout of
+/// the deck both aces left behind. By exchangeability this only matters once
+/// the dealer can run the shoe dry. With no card left for the sibling the
+/// deck is settled as is.
+fn sibling_average(arr: &[Count; 10], mut settle: impl FnMut(&[Count; 10], &mut Memo) -> f64, memo: &mut Memo) -> f64 {
+    let rem: i32 = arr.iter().sum();
+    if rem <= 0 {
+        return settle(arr, memo);
+    }
+    let mut rest = *arr;
+    let mut acc = 0.0;
+    for q in 0..10 {
+        let c = rest[q];
+        if c <= 0 {
+            continue;
+        }
+        rest[q] -= 1;
+        acc += (c as f64) / (rem as f64) * settle(&rest, memo);
+        rest[q] += 1;
+    }
+    acc
+}
+
+/// Per-stake result of a finished player hand. Resolution order: a busted
+/// player loses at once; otherwise an ENHC dealer natural (only reachable with
+/// HC_NONE) costs `nat_loss`; otherwise the hand settles against the runout.
+/// If the player took the last card there is no hole to deal, so the dealer
+/// stops on the upcard alone (the runout's empty-shoe clamp).
+#[allow(clippy::too_many_arguments)]
+fn settle_stood(
+    counts: &[Count; 10],
+    pt: i32,
+    up: usize,
+    hole_constraint: i32,
+    policy: &DealerPolicy,
+    nat_loss: f64,
+    memo: &mut Memo,
+) -> f64 {
+    settle_stood_weighted(counts, pt, up, &constraint_weights(hole_constraint), policy, nat_loss, memo)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn settle_stood_weighted(
+    counts: &[Count; 10],
+    pt: i32,
+    up: usize,
+    weights: &[f64; 10],
+    policy: &DealerPolicy,
+    nat_loss: f64,
+    memo: &mut Memo,
+) -> f64 {
+    if pt > 21 {
+        return -1.0;
+    }
+    if counts.iter().sum::<i32>() <= 0 {
+        let mut empty = [0; 10];
+        let (t, s) = policy.add(0, false, up);
+        let dist = dealer_dist_from_total(&mut empty, t, s, 1, &policy.for_up(up), memo);
+        return settle_dist(pt, &dist, policy);
+    }
+    let (p_nat, dist) = dealer_dist_split_natural(counts, up, weights, policy, memo);
+    settle_split(pt, p_nat, &dist, policy, nat_loss)
+}
+
+/// Settles a standing total against a dealer-natural probability and the
+/// runout given no natural.
+fn settle_split(pt: i32, p_nat: f64, dist: &[f64; 6], policy: &DealerPolicy, nat_loss: f64) -> f64 {
+    if pt == 21 {
+        // A drawn 21 beats every final total but 21, so only that bin matters.
+        let t = &policy.settle21;
+        // Void mass (push-22, empty-shoe push) is a push, so only the bins count.
+        let live: f64 = dist.iter().sum();
+        let settled = (live - dist[4]) * policy.win_pay + dist[4] * t.drawn21_vs_drawn21;
+        return (1.0 - p_nat) * settled + p_nat * nat_loss * (-t.drawn21_vs_natural);
+    }
+    (1.0 - p_nat) * settle_dist(pt, dist, policy) + p_nat * nat_loss
+}
+
+/// `settle_split` broken down by dealer bin (`settle * prob`); the dealer
+/// natural's share is booked under 21. The six values sum to the settled EV.
+fn settle_split_bins(pt: i32, p_nat: f64, dist: &[f64; 6], policy: &DealerPolicy, nat_loss: f64) -> [f64; 6] {
+    let t = &policy.settle21;
+    let mut out = [0.0; 6];
+    for (i, o) in out.iter_mut().enumerate() {
+        let v = if pt == 21 && i == 4 { t.drawn21_vs_drawn21 } else { settle_vs_player(pt, i, policy) };
+        *o = (1.0 - p_nat) * dist[i] * v;
+    }
+    out[4] += p_nat * nat_loss * if pt == 21 { -t.drawn21_vs_natural } else { 1.0 };
+    out
+}
+
+/// `settle_split` as (win, push, loss) probabilities by the sign of each
+/// settlement; void runout mass is a push.
+fn settle_split_outcomes(pt: i32, p_nat: f64, dist: &[f64; 6], policy: &DealerPolicy, nat_loss: f64) -> [f64; 3] {
+    let mut out = [0.0; 3];
+    let mut book = |v: f64, p: f64| out[if v > 0.0 { 0 } else if v == 0.0 { 1 } else { 2 }] += p;
+    if pt > 21 {
+        book(-1.0, 1.0);
+        return out;
+    }
+    let t = &policy.settle21;
+    for (i, &d) in dist.iter().enumerate() {
+        book(if pt == 21 && i == 4 { t.drawn21_vs_drawn21 } else { settle_vs_player(pt, i, policy) }, (1.0 - p_nat) * d);
+    }
+    book(0.0, (1.0 - p_nat) * (1.0 - dist.iter().sum::<f64>()));
+    book(nat_loss * if pt == 21 { -t.drawn21_vs_natural } else { 1.0 }, p_nat);
+    out
+}
+
+/// `settle_stood` as (win, push, loss) probabilities.
+fn stood_outcomes(counts: &[Count; 10], pt: i32, up: usize, hole_constraint: i32, policy: &DealerPolicy, nat_loss: f64, memo: &mut Memo) -> [f64; 3] {
+    let (p_nat, dist) = if counts.iter().sum::<i32>() <= 0 {
+        let (t, s) = policy.add(0, false, up);
+        (0.0, dealer_dist_from_total(&mut [0; 10], t, s, 1, &policy.for_up(up), memo))
+    } else {
+        dealer_dist_split_natural(counts, up, &constraint_weights(hole_constraint), policy, memo)
+    };
+    settle_split_outcomes(pt, p_nat, &dist, policy, nat_loss)
+}
+
+// ---------- Flat dealer table ----------
+/// Every runout a fixed shoe can reach, precomputed into one flat array.
+///
+/// A dealer state is the upcard plus the multiset of cards drawn after it;
+/// the remaining counts, total and softness all follow from that. A hand only
+/// draws while its hard sum is at most `budget`, so the hitting states are the
+/// multisets within that budget. They get a dense mixed-radix rank
+/// (`ways[i][s]` = multisets over ranks `i..` with hard sum <= s), and a
+/// lookup is a few additions instead of a hash of the counts.
+#[pyclass]
+pub struct DealerTable {
+    rules: u64,
+    deck: [Count; 10],
+    policy: DealerPolicy,
+    /// Unseen cards per upcard; `None` if the deck cannot supply that upcard.
+    bases: [Option<[Count; 10]>; 10],
+    budget: i32,
+    ways: Vec<Vec<usize>>,
+    per_up: usize,
+    dists: Vec<[f64; 6]>,
+}
+
+impl DealerTable {
+    fn build(rules: u64, deck: [Count; 10], bases: [Option<[Count; 10]>; 10], policy: DealerPolicy) -> Self {
+        let mut budget = 0;
+        for t in 0..22 {
+            if policy.hits(t, false) {
+                budget = budget.max(t);
+            }
+            if policy.hits(t, true) {
+                budget = budget.max(t - 10);
+            }
+        }
+        // The upcard adds at least 1, leaving the drawn cards one less.
+        budget -= 1;
+        let width = (budget + 1) as usize;
+        let mut ways = vec![vec![0usize; width]; 11];
+        ways[10] = vec![1; width];
+        for i in (0..10).rev() {
+            let v = hard_val(i);
+            let cap = deck[i].max(0);
+            for s in 0..width {
+                let mut n = 0;
+                let mut k = 0;
+                while k <= cap && k * v <= s as i32 {
+                    n += ways[i + 1][s - (k * v) as usize];
+                    k += 1;
+                }
+                ways[i][s] = n;
+            }
+        }
+        let per_up = ways[0][budget as usize];
+        let mut table = DealerTable {
+            rules,
+            deck,
+            policy,
+            bases,
+            budget,
+            ways,
+            per_up,
+            dists: vec![[0.0; 6]; per_up * 10],
+        };
+        let mut filled = vec![false; per_up * 10];
+        for up in 0..10 {
+            let Some(base) = table.bases[up] else { continue };
+            let (t0, s0) = policy.add(0, false, up);
+            for h in 0..10 {
+                if base[h] > 0 {
+                    let mut drawn = [0; 10];
+                    drawn[h] = 1;
+                    let (t, s) = policy.add(t0, s0, h);
+                    table.fill(up, &base, &mut drawn, t, s, &mut filled);
+                }
+            }
+        }
+        table
+    }
+
+    fn index(&self, up: usize, drawn: &[Count; 10]) -> usize {
+        let mut s = self.budget;
+        let mut idx = up * self.per_up;
+        for (i, &d) in drawn.iter().enumerate() {
+            let v = hard_val(i);
+            for k in 0..d {
+                idx += self.ways[i + 1][(s - k * v) as usize];
+            }
+            s -= d * v;
+        }
+        idx
+    }
+
+    /// Same recursion as `dealer_dist_from_total`, with the flat array as memo.
+    fn fill(
+        &mut self,
+        up: usize,
+        base: &[Count; 10],
+        drawn: &mut [Count; 10],
+        total: i32,
+        soft: bool,
+        filled: &mut [bool],
+    ) -> [f64; 6] {
+        if total > 21 || !self.policy.hits_vs(up, total, soft) {
+            return self.terminal(total);
+        }
+        let idx = self.index(up, drawn);
+        if filled[idx] {
+            return self.dists[idx];
+        }
+        let rem: i32 = base.iter().zip(drawn.iter()).map(|(b, d)| b - d).sum();
+        let mut out = [0.0; 6];
+        if rem <= 0 {
+            if self.policy.empty_shoe == EmptyShoe::Clamp {
+                out[(total.max(17) - 17) as usize] = 1.0;
+            }
+        } else {
+            for r in 0..10 {
+                let c = base[r] - drawn[r];
+                if c <= 0 {
+                    continue;
+                }
+                let p = (c as f64) / (rem as f64);
+                drawn[r] += 1;
+                let (nt, ns) = self.policy.add(total, soft, r);
+                let sub = self.fill(up, base, drawn, nt, ns, filled);
+                for i in 0..6 {
+                    out[i] += p * sub[i];
+                }
+                drawn[r] -= 1;
+            }
+        }
+        self.dists[idx] = out;
+        filled[idx] = true;
+        out
+    }
+
+    fn terminal(&self, total: i32) -> [f64; 6] {
+        let mut v = [0.0; 6];
+        if !(self.policy.push_22 && total == 22) {
+            v[(total.min(22) - 17) as usize] = 1.0;
+        }
+        v
+    }
+
+    /// Table counterpart of `dealer_dist_split_natural`.
+    fn split_natural(&self, up: usize, base: &[Count; 10], weights: &[f64; 10]) -> (f64, [f64; 6]) {
+        let mut out = [0.0; 6];
+        let rem: i32 = base.iter().sum();
+        let (t0, s0) = self.policy.add(0, false, up);
+        let (mut p_nat, mut p_rest) = (0.0, 0.0);
+        for h in 0..10 {
+            let c = base[h];
+            if c <= 0 || weights[h] <= 0.0 {
+                continue;
+            }
+            let p = weights[h] * (c as f64) / (rem as f64);
+            if self.policy.natural(up, h) {
+                p_nat += p;
+                continue;
+            }
+            let (t, s) = self.policy.add(t0, s0, h);
+            let dist = if t > 21 || !self.policy.hits_vs(up, t, s) {
+                self.terminal(t)
+            } else {
+                let mut drawn = [0; 10];
+                drawn[h] = 1;
+                self.dists[self.index(up, &drawn)]
+            };
+            for (o, d) in out.iter_mut().zip(dist.iter()) {
+                *o += p * d;
+            }
+            p_rest += p;
+        }
+        if p_rest > 0.0 {
+            for o in out.iter_mut() {
+                *o /= p_rest;
+            }
+        }
+        let denom = p_nat + p_rest;
+        (if denom > 0.0 { p_nat / denom } else { 0.0 }, out)
+    }
+}
+
+#[pymethods]
+impl DealerTable {
+    /// The deck the table was built from.
+    #[getter]
+    fn deck(&self) -> Vec<Count> {
+        self.deck.to_vec()
+    }
+
+    /// Flat entries allocated (ten upcards of `per_up` states each).
+    fn __len__(&self) -> usize {
+        self.dists.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DealerTable(deck={:?}, entries={})", self.deck, self.dists.len())
+    }
+}
+
+// ---------- Strategy grid ----------
+struct GridHand {
+    label: String,
+    total: i32,
+    soft: bool,
+    pair: Option<usize>,
+}
+
+/// Chart rows in order: hard 4-21 ("H4".."H21"), soft 13-21 ("S13".."S21"),
+/// then pairs ("PA", "P2".."PT").
+fn grid_hands() -> Vec<GridHand> {
+    let mut out = Vec::new();
+    for t in 4..=21 {
+        out.push(GridHand { label: format!("H{}", t), total: t, soft: false, pair: None });
+    }
+    for t in 13..=21 {
+        out.push(GridHand { label: format!("S{}", t), total: t, soft: true, pair: None });
+    }
+    for (r, lbl) in RANK_LABELS.iter().enumerate() {
+        let (total, soft) = if r == 0 { (12, true) } else { (2 * rank_val(r), false) };
+        out.push(GridHand { label: format!("P{}", lbl), total, soft, pair: Some(r) });
+    }
+    out
+}
+
+/// Optimal two-card action and EV for every chart row (`hands`) against each
+/// upcard; inner vectors are indexed by upcard A,2..9,T.
+#[pyclass]
+#[derive(Clone)]
+pub struct StrategyGrid {
+    /// Fingerprint of the simulator rules the grid was computed under.
+    #[pyo3(get)]
+    rules_fingerprint: u64,
+    #[pyo3(get)]
+    hands: Vec<String>,
+    #[pyo3(get)]
+    actions: Vec<Vec<String>>,
+    #[pyo3(get)]
+    evs: Vec<Vec<f64>>,
+}
+
+#[pymethods]
+impl StrategyGrid {
+    fn __repr__(&self) -> String {
+        format!("StrategyGrid(hands={}, upcards=10)", self.hands.len())
+    }
+}
+
+// Grid binary format (little endian):
+//   b"ECMG", version u8, rules fingerprint u64, hand count u32,
+//   per hand: label length u16 + UTF-8 label, then 10 cells of action code u8 + EV f64.
+const GRID_MAGIC: &[u8; 4] = b"ECMG";
+const GRID_VERSION: u8 = 1;
+const ACTIONS: [&str; 5] = ["stand", "hit", "double", "split", "surrender"];
+
+/// Serialize a strategy grid to the compact binary format above.
+#[pyfunction]
+fn serialize_grid<'py>(py: Python<'py>, grid: &StrategyGrid) -> PyResult<Bound<'py, PyBytes>> {
+    let mut buf = Vec::new();
+    buf.extend_from_slice(GRID_MAGIC);
+    buf.push(GRID_VERSION);
+    buf.extend_from_slice(&grid.rules_fingerprint.to_le_bytes());
+    buf.extend_from_slice(&(grid.hands.len() as u32).to_le_bytes());
+    for (i, label) in grid.hands.iter().enumerate() {
+        buf.extend_from_slice(&(label.len() as u16).to_le_bytes());
+        buf.extend_from_slice(label.as_bytes());
+        if grid.actions[i].len() != 10 || grid.evs[i].len() != 10 {
+            return Err(PyValueError::new_err(format!("grid row {} must have 10 upcard cells", label)));
+        }
+        for (a, ev) in grid.actions[i].iter().zip(grid.evs[i].iter()) {
+            let code = ACTIONS
+                .iter()
+                .position(|x| x == a)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown action {:?} in grid", a)))?;
+            buf.push(code as u8);
+            buf.extend_from_slice(&ev.to_le_bytes());
+        }
+    }
+    Ok(PyBytes::new_bound(py, &buf))
+}
+
+struct GridReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> GridReader<'a> {
+    fn take(&mut self, n: usize) -> PyResult<&'a [u8]> {
+        if self.pos + n > self.buf.len() {
+            return Err(PyValueError::new_err("truncated strategy grid"));
+        }
+        let out = &self.buf[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(out)
+    }
+
+    fn array<const N: usize>(&mut self) -> PyResult<[u8; N]> {
+        let mut a = [0u8; N];
+        a.copy_from_slice(self.take(N)?);
+        Ok(a)
+    }
+}
+
+/// Rebuild a grid written by `serialize_grid`. If `expected_fingerprint` is
+/// given (e.g. `sim.rules_fingerprint()`), a grid computed under other rules is rejected.
+#[pyfunction]
+#[pyo3(signature = (data, expected_fingerprint=None))]
+fn deserialize_grid(data: &[u8], expected_fingerprint: Option<u64>) -> PyResult<StrategyGrid> {
+    let mut rd = GridReader { buf: data, pos: 0 };
+    if rd.take(4)? != GRID_MAGIC {
+        return Err(PyValueError::new_err("not a strategy grid (bad magic)"));
+    }
+    let version = rd.array::<1>()?[0];
+    if version != GRID_VERSION {
+        return Err(PyValueError::new_err(format!("unsupported strategy grid version {}", version)));
+    }
+    let rules_fingerprint = u64::from_le_bytes(rd.array()?);
+    if let Some(fp) = expected_fingerprint {
+        if fp != rules_fingerprint {
+            return Err(PyValueError::new_err(format!(
+                "strategy grid was computed under rules {:#x}, expected {:#x}",
+                rules_fingerprint, fp
+            )));
+        }
+    }
+    let n = u32::from_le_bytes(rd.array()?) as usize;
+    let mut grid = StrategyGrid { rules_fingerprint, hands: Vec::new(), actions: Vec::new(), evs: Vec::new() };
+    for _ in 0..n {
+        let len = u16::from_le_bytes(rd.array()?) as usize;
+        let label = std::str::from_utf8(rd.take(len)?)
+            .map_err(|_| PyValueError::new_err("strategy grid label is not UTF-8"))?;
+        grid.hands.push(label.to_string());
+        let (mut acts, mut evs) = (Vec::with_capacity(10), Vec::with_capacity(10));
+        for _ in 0..10 {
+            let code = rd.array::<1>()?[0] as usize;
+            let a = ACTIONS
+                .get(code)
+                .ok_or_else(|| PyValueError::new_err(format!("unknown action code {} in grid", code)))?;
+            acts.push(a.to_string());
+            evs.push(f64::from_le_bytes(rd.array()?));
+        }
+        grid.actions.push(acts);
+        grid.evs.push(evs);
+    }
+    if rd.pos != data.len() {
+        return Err(PyValueError::new_err("trailing bytes after strategy grid"));
+    }
+    Ok(grid)
+}
+
+// ---------- Strategy charts ----------
+/// A fixed chart to play by (`ev_under_chart`): per `grid_hands` row and
+/// upcard A,2..9,T, the action wanted and the one taken where it is not
+/// allowed (e.g. "Ds": double, else stand).
+#[pyclass]
+#[derive(Clone)]
+pub struct StrategyChart {
+    rows: HashMap<String, [(&'static str, &'static str); 10]>,
+}
+
+/// A chart cell code as (action, fallback): S, H, D/Dh, Ds, P, R/Rh, Rs, or
+/// the action names themselves.
+fn chart_code(code: &str) -> PyResult<(&'static str, &'static str)> {
+    Ok(match code.trim().to_ascii_lowercase().as_str() {
+        "s" | "stand" => ("stand", "stand"),
+        "h" | "hit" => ("hit", "hit"),
+        "d" | "dh" | "double" => ("double", "hit"),
+        "ds" => ("double", "stand"),
+        "p" | "split" => ("split", "hit"),
+        "r" | "rh" | "surrender" => ("surrender", "hit"),
+        "rs" => ("surrender", "stand"),
+        other => return Err(PyValueError::new_err(format!("unknown chart code {:?}", other))),
+    })
+}
+
+#[pymethods]
+impl StrategyChart {
+    /// Parse a chart from CSV: a header `hand,A,2,...,9,T` ("10" may stand
+    /// for T) and one row per hand label ("H16", "S18", "P8", ...). Blank
+    /// lines and lines starting with `#` are skipped.
+    #[staticmethod]
+    fn from_csv(text: &str) -> PyResult<Self> {
+        let labels: Vec<String> = grid_hands().into_iter().map(|h| h.label).collect();
+        let mut lines = text.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#'));
+        let header: Vec<String> = lines
+            .next()
+            .ok_or_else(|| PyValueError::new_err("chart CSV is empty"))?
+            .split(',')
+            .map(|c| c.trim().to_ascii_uppercase())
+            .collect();
+        let mut cols = [0usize; 10];
+        for (up, col) in cols.iter_mut().enumerate() {
+            *col = header
+                .iter()
+                .skip(1)
+                .position(|h| h == RANK_LABELS[up] || (up == 9 && h == "10"))
+                .ok_or_else(|| PyValueError::new_err(format!("chart header has no {} column", RANK_LABELS[up])))?
+                + 1;
+        }
+        let mut rows = HashMap::new();
+        for line in lines {
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let label = cells[0].to_ascii_uppercase();
+            if !labels.contains(&label) {
+                return Err(PyValueError::new_err(format!("unknown chart row {:?}", cells[0])));
+            }
+            if cells.len() != header.len() {
+                return Err(PyValueError::new_err(format!("chart row {} has {} cells, expected {}", label, cells.len(), header.len())));
+            }
+            let mut row = [("stand", "stand"); 10];
+            for (up, cell) in row.iter_mut().enumerate() {
+                *cell = chart_code(cells[cols[up]])?;
+            }
+            if rows.insert(label.clone(), row).is_some() {
+                return Err(PyValueError::new_err(format!("chart row {} appears twice", label)));
+            }
+        }
+        Ok(StrategyChart { rows })
+    }
+
+    /// The chart holding a grid's actions, with no fallbacks beyond hitting.
+    #[staticmethod]
+    fn from_grid(grid: &StrategyGrid) -> PyResult<Self> {
+        let mut rows = HashMap::new();
+        for (label, acts) in grid.hands.iter().zip(grid.actions.iter()) {
+            let mut row = [("stand", "stand"); 10];
+            for (cell, a) in row.iter_mut().zip(acts.iter()) {
+                *cell = chart_code(a)?;
+            }
+            rows.insert(label.clone(), row);
+        }
+        Ok(StrategyChart { rows })
+    }
+
+    /// `(action, fallback)` for row `hand` against upcard `up`.
+    fn action(&self, hand: &str, up: usize) -> PyResult<(&'static str, &'static str)> {
+        if up >= 10 {
+            return Err(PyValueError::new_err(format!("upcard index {} out of range 0..9", up)));
+        }
+        self.cell(hand, up)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("StrategyChart(hands={}, upcards=10)", self.rows.len())
+    }
+}
+
+impl StrategyChart {
+    fn cell(&self, label: &str, up: usize) -> PyResult<(&'static str, &'static str)> {
+        self.rows
+            .get(label)
+            .map(|row| row[up])
+            .ok_or_else(|| PyValueError::new_err(format!("chart has no row {}", label)))
+    }
+}
+
+// ---------- Composition-dependent play ----------
+/// Total and softness of a hand given as rank indices.
+fn hand_from_cards(cards: &[usize], policy: &DealerPolicy) -> PyResult<(i32, bool)> {
+    let mut hand = (0, false);
+    for &r in cards {
+        if r >= 10 {
+            return Err(PyValueError::new_err(format!("card rank index {} out of range 0..9", r)));
+        }
+        hand = policy.add(hand.0, hand.1, r);
+    }
+    Ok(hand)
+}
+
+/// `arr` minus the given cards; errors if the deck cannot supply one.
+fn remove_cards(mut arr: [Count; 10], cards: &[usize]) -> PyResult<[Count; 10]> {
+    for &r in cards {
+        if arr[r] <= 0 {
+            return Err(PyValueError::new_err(format!("deck holds no {} for the player's hand", RANK_LABELS[r])));
+        }
+        arr[r] -= 1;
+    }
+    Ok(arr)
+}
+
+/// `arr` minus the two cards of a pair about to be split; errors if the
+/// deck cannot supply both.
+fn without_pair(mut arr: [Count; 10], pair_rank: usize) -> PyResult<[Count; 10]> {
+    if pair_rank >= 10 {
+        return Err(PyValueError::new_err(format!("pair rank index {} out of range 0..9", pair_rank)));
+    }
+    if arr[pair_rank] < 2 {
+        return Err(PyValueError::new_err(format!(
+            "cannot split {0},{0}: the deck holds {1} {0}",
+            RANK_LABELS[pair_rank], arr[pair_rank]
+        )));
+    }
+    arr[pair_rank] -= 2;
+    Ok(arr)
+}
+
+/// Hit/stand decision for a hand under the total-only model (deck as given)
+/// and the composition model (the player's exact cards removed).
+#[pyclass]
+#[derive(Clone)]
+pub struct CompositionDecision {
+    #[pyo3(get)]
+    total: i32,
+    #[pyo3(get)]
+    soft: bool,
+    #[pyo3(get)]
+    total_stand_ev: f64,
+    #[pyo3(get)]
+    total_hit_ev: f64,
+    #[pyo3(get)]
+    total_action: String,
+    #[pyo3(get)]
+    composition_stand_ev: f64,
+    #[pyo3(get)]
+    composition_hit_ev: f64,
+    #[pyo3(get)]
+    composition_action: String,
+    /// True when the exact cards flip the total-only decision.
+    #[pyo3(get)]
+    differs: bool,
+}
+
+#[pymethods]
+impl CompositionDecision {
+    fn __repr__(&self) -> String {
+        format!(
+            "CompositionDecision(total={}, soft={}, total_action={:?}, composition_action={:?}, differs={})",
+            self.total,
+            py_bool(self.soft),
+            self.total_action,
+            self.composition_action,
+            py_bool(self.differs),
+        )
+    }
+}
+
+// ---------- PyO3 class ----------
+/// Simulator-lifetime dealer memo. Entries are only valid for the rule set
+/// they were computed under, so the map is tagged with the rules fingerprint.
+#[derive(Default)]
+struct DealerMemo {
+    rules: u64,
+    map: Memo,
+}
+
+#[pyclass]
+pub struct BlackjackSimulator {
+    #[pyo3(get, set)]
+    h17: bool,
+    #[pyo3(get)]
+    dp_depth: usize,
+    #[pyo3(get)]
+    dp_depth_dbl: usize,
+    /// Doubling after split; the `das` bool property maps to None / All.
+    das: DasRule,
+    #[pyo3(get, set)]
+    split_aces_one: bool,
+    /// One-card split aces may still double (one card on a doubled stake),
+    /// subject to `das` and `post_split_double_rule`; ignored under Free Bet.
+    #[pyo3(get, set)]
+    double_split_aces: bool,
+    /// Totals the initial two-card hand may double on.
+    double_rule: DoubleRule,
+    /// Totals a split hand may double on, when `das` lets it double at all.
+    post_split_double_rule: DoubleRule,
+    /// Late surrender on the initial two cards.
+    #[pyo3(get, set)]
+    surrender: bool,
+    /// Fraction of the stake a surrender forfeits (0.5 standard; 0.4 on a
+    /// promotional 60% refund).
+    #[pyo3(get, set)]
+    surrender_fraction: f64,
+    /// Most cards a player hand may hold (None for no cap); at the cap it
+    /// must stand, unless `charlie` makes it a winner there. At least 3, so
+    /// every hand can still hit or double once.
+    max_player_cards: Option<usize>,
+    /// Charlie: a hand that reaches `max_player_cards` without busting wins
+    /// at even money whatever the dealer makes. Needs a card cap to matter.
+    #[pyo3(get, set)]
+    charlie: bool,
+    /// ENHC only: a completed Charlie also beats a revealed dealer natural.
+    /// Off, the natural takes it like any other hand that acted (`nat_loss`).
+    /// Under a peek the dealer's natural ends the round first, so it is moot.
+    #[pyo3(get, set)]
+    charlie_beats_bj: bool,
+    /// Payout of a player natural per unit staked (1.5 = 3:2).
+    #[pyo3(get, set)]
+    bj_payout: f64,
+    /// Extra payout per unit staked on a suited natural. The play deck has no
+    /// suits: calls given a `suited` deck read the chance a natural is
+    /// suited from it (`suited_natural_prob`), the rest assume
+    /// `SUITED_NATURAL_PROB`, exact when suits are spread evenly.
+    #[pyo3(get, set)]
+    blackjack_bonus: f64,
+    /// Commission taken off every main-game win, naturals and doubles
+    /// included (0.05 keeps 95% of the win); losses stay whole.
+    #[pyo3(get, set)]
+    win_commission: f64,
+    /// ENHC "original bets only": a dealer natural takes just the round's
+    /// original stake, refunding doubles and every split hand. A dealer
+    /// natural against a hand that has acted costs, in original-bet units:
+    ///
+    /// | hand                  | default | `obo` | `enhc_refund_extra` | `no_peek_full_refund` |
+    /// |-----------------------|---------|-------|---------------------|-----------------------|
+    /// | stood / hit           | -1      | -1    | -1                  | 0                     |
+    /// | doubled               | -2      | -1    | -1                  | 0                     |
+    /// | split (2 hands)       | -2      | -1    | -2                  | 0                     |
+    /// | split, one doubled    | -3      | -1    | -2                  | 0                     |
+    #[pyo3(get, set)]
+    obo: bool,
+    /// Delayed-hole ENHC that refunds only the money added by doubling: each
+    /// hand's own stake, split hands included, stays at risk (see the table
+    /// under `obo`, which takes precedence).
+    #[pyo3(get, set)]
+    enhc_refund_extra: bool,
+    /// No-peek home rule: a dealer natural refunds every bet, doubles and
+    /// splits included (stronger than `obo`; see the table there).
+    #[pyo3(get, set)]
+    no_peek_full_refund: bool,
+    /// Continuous shuffler: every hand is dealt from the full shoe.
+    #[pyo3(get, set)]
+    csm: bool,
+    /// Decks passed in still hold the dealer upcard, which is taken out before
+    /// the hole and runout are enumerated. Set false if callers already removed it.
+    #[pyo3(get, set)]
+    remove_upcard: bool,
+    /// Peek rules applied to `HC_AUTO`: the dealer checks for a natural under an
+    /// ace (`peek_on_ace`) and/or a ten (`peek_on_ten`). Both false is ENHC.
+    #[pyo3(get, set)]
+    peek_on_ace: bool,
+    #[pyo3(get, set)]
+    peek_on_ten: bool,
+    /// Hard-ace game: aces always count 1 for player and dealer alike, so
+    /// there are no soft totals (pass `pt_soft=False`) and no naturals.
+    #[pyo3(get, set)]
+    aces_hard_only: bool,
+    /// A dealer 22 pushes every standing hand (busted hands still lose).
+    #[pyo3(get, set)]
+    push_22: bool,
+    /// Dealer runouts in exact integer arithmetic (see `exact_runout`), for
+    /// single- and double-deck work where a rounding error could flip a
+    /// close decision. Each memo miss walks its whole subtree in u128, so
+    /// expect a few times the float cost; larger decks fall back to floats.
+    #[pyo3(get, set)]
+    exact_rational: bool,
+    /// Free Bet Blackjack: doubles on hard 9-11 and splits of every pair but
+    /// tens are funded by the house, which pays their wins and takes no
+    /// loss. Combine with `push_22`; see `free_bet_blackjack`.
+    #[pyo3(get, set)]
+    free_bet: bool,
+    /// Double Exposure: both dealer cards are dealt face up, so there is no
+    /// peek and every hand is played against its known hole (`HC_KNOWN + h`
+    /// in the per-hand methods). The dealer wins all ties but natural vs
+    /// natural, and a natural pays 1:1 (turning this on sets `bj_payout`).
+    double_exposure: bool,
+    /// Explicit dealer hit/stand table; `None` derives S17/H17 from `h17`.
+    custom_policy: Option<HitTable>,
+    /// Soft-17 entry of the dealer table per upcard A,2..9,T, overriding
+    /// `h17` (or the custom table); `None` when one rule covers every upcard.
+    h17_by_upcard: Option<[bool; 10]>,
+    /// Dealer forced to hit on an empty shoe: "clamp" or "push".
+    empty_shoe: EmptyShoe,
+    /// Card-count soft-17 rule on top of the dealer table (see `Soft17Cards`).
+    soft17_cards: Soft17Cards,
+    /// 21-vs-21 settlement table (see `Settlement`).
+    #[pyo3(get, set)]
+    settlement: Settlement,
+    shoe: [Count; 10],
+    memo: Mutex<DealerMemo>,
+}
+
+// The memo sits behind a Mutex, so one simulator can be shared by the rayon
+// workers in `strategy_grid` (and any future batch API); `tests` fails to
+// compile if a field ever makes it !Sync or !Send.
+
+/// Chance a natural's ace and ten share a suit when suits are spread evenly
+/// over every rank.
+const SUITED_NATURAL_PROB: f64 = 0.25;
+
+/// EVs closer than this are ties; summation-order noise stays below it, so
+/// decisions do not flap between runs.
+const EV_TIE_EPS: f64 = 1e-12;
+
+/// Highest-EV entry; ties (within `EV_TIE_EPS`) keep the earlier
+/// (higher-priority) action.
+fn best_action(evs: &[(&'static str, f64)]) -> (&'static str, f64) {
+    let mut best = evs[0];
+    for e in &evs[1..] {
+        if e.1 > best.1 + EV_TIE_EPS {
+            best = *e;
+        }
+    }
+    best
+}
+
+/// Round every EV to `decimals` places (no-op for None), ahead of any max
+/// comparison so equal rounded values fall back to the tie-break order.
+fn round_evs(evs: &mut [(&'static str, f64)], decimals: Option<u32>) {
+    if let Some(d) = decimals {
+        let scale = 10f64.powi(d as i32);
+        for e in evs.iter_mut() {
+            e.1 = (e.1 * scale).round() / scale;
+        }
+    }
+}
+
+fn py_bool(b: bool) -> &'static str {
+    if b {
+        "True"
+    } else {
+        "False"
+    }
+}
+
+// FNV-1a: stable across builds, unlike std's DefaultHasher.
+fn fnv1a(bytes: &[u8], mut h: u64) -> u64 {
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    h
+}
+
+impl BlackjackSimulator {
+    /// Named-preset base: a fresh `num_decks` shoe, 3:2, split aces one card,
+    /// US peek conventions and default search depths.
+    fn preset(num_decks: usize, h17: bool, das: bool) -> Self {
+        Self {
+            h17,
+            dp_depth: 3,
+            dp_depth_dbl: 4,
+            das: DasRule::from_flag(das),
+            split_aces_one: true,
+            double_split_aces: false,
+            double_rule: DoubleRule::Any,
+            post_split_double_rule: DoubleRule::Any,
+            surrender: false,
+            surrender_fraction: 0.5,
+            max_player_cards: None,
+            charlie: false,
+            charlie_beats_bj: false,
+            csm: false,
+            obo: false,
+            enhc_refund_extra: false,
+            bj_payout: 1.5,
+            blackjack_bonus: 0.0,
+            win_commission: 0.0,
+            remove_upcard: true,
+            peek_on_ace: true,
+            peek_on_ten: true,
+            aces_hard_only: false,
+            push_22: false,
+            exact_rational: false,
+            free_bet: false,
+            double_exposure: false,
+            no_peek_full_refund: false,
+            custom_policy: None,
+            h17_by_upcard: None,
+            empty_shoe: EmptyShoe::Clamp,
+            soft17_cards: Soft17Cards::Any,
+            settlement: Settlement::default(),
+            shoe: standard_shoe(num_decks),
+            memo: Mutex::new(DealerMemo::default()),
+        }
+    }
+
+    /// The same rules on a fresh, empty memo.
+    fn copy_rules(&self) -> Self {
+        Self {
+            memo: Mutex::new(DealerMemo::default()),
+            ..*self
+        }
+    }
+
+    /// Hash of every rule field (never the memo). Any rule change yields a new value.
+    fn fingerprint(&self) -> u64 {
+        let mut h = 0xcbf2_9ce4_8422_2325;
+        h = fnv1a(&[self.h17 as u8], h);
+        let policy = self.policy();
+        for row in policy.hits {
+            h = fnv1a(&[row[0] as u8, row[1] as u8], h);
+        }
+        h = fnv1a(&[policy.empty_shoe as u8, policy.soft17_cards as u8], h);
+        h = fnv1a(&policy.h17_by_upcard.map_or([2; 10], |b| b.map(u8::from)), h);
+        let t = policy.settle21;
+        for v in [t.natural_vs_natural, t.drawn21_vs_natural, t.drawn21_vs_drawn21] {
+            h = fnv1a(&v.to_bits().to_le_bytes(), h);
+        }
+        h = fnv1a(&(self.dp_depth as u64).to_le_bytes(), h);
+        h = fnv1a(&(self.dp_depth_dbl as u64).to_le_bytes(), h);
+        h = fnv1a(
+            &[self.das as u8, self.split_aces_one as u8, self.csm as u8, self.obo as u8, self.enhc_refund_extra as u8, self.remove_upcard as u8, self.no_peek_full_refund as u8, self.peek_on_ace as u8, self.peek_on_ten as u8, self.aces_hard_only as u8, self.surrender as u8, self.push_22 as u8, self.exact_rational as u8, self.free_bet as u8, self.double_exposure as u8, self.double_split_aces as u8, self.charlie as u8, self.charlie_beats_bj as u8],
+            h,
+        );
+        h = fnv1a(&[self.double_rule as u8, self.post_split_double_rule as u8], h);
+        h = fnv1a(&self.bj_payout.to_bits().to_le_bytes(), h);
+        h = fnv1a(&self.blackjack_bonus.to_bits().to_le_bytes(), h);
+        h = fnv1a(&self.win_commission.to_bits().to_le_bytes(), h);
+        h = fnv1a(&self.surrender_fraction.to_bits().to_le_bytes(), h);
+        h = fnv1a(&(self.max_player_cards.unwrap_or(0) as u64).to_le_bytes(), h);
+        for c in self.shoe {
+            h = fnv1a(&c.to_le_bytes(), h);
+        }
+        h
+    }
+
+    fn same_rules(&self, other: &Self) -> bool {
+        self.h17 == other.h17
+            && self.dp_depth == other.dp_depth
+            && self.dp_depth_dbl == other.dp_depth_dbl
+            && self.das == other.das
+            && self.split_aces_one == other.split_aces_one
+            && self.double_split_aces == other.double_split_aces
+            && self.double_rule == other.double_rule
+            && self.post_split_double_rule == other.post_split_double_rule
+            && self.surrender == other.surrender
+            && self.surrender_fraction == other.surrender_fraction
+            && self.max_player_cards == other.max_player_cards
+            && self.charlie == other.charlie
+            && self.charlie_beats_bj == other.charlie_beats_bj
+            && self.csm == other.csm
+            && self.obo == other.obo
+            && self.enhc_refund_extra == other.enhc_refund_extra
+            && self.no_peek_full_refund == other.no_peek_full_refund
+            && self.remove_upcard == other.remove_upcard
+            && self.peek_on_ace == other.peek_on_ace
+            && self.peek_on_ten == other.peek_on_ten
+            && self.aces_hard_only == other.aces_hard_only
+            && self.push_22 == other.push_22
+            && self.exact_rational == other.exact_rational
+            && self.free_bet == other.free_bet
+            && self.double_exposure == other.double_exposure
+            && self.bj_payout == other.bj_payout
+            && self.blackjack_bonus == other.blackjack_bonus
+            && self.win_commission == other.win_commission
+            && self.custom_policy == other.custom_policy
+            && self.h17_by_upcard == other.h17_by_upcard
+            && self.empty_shoe == other.empty_shoe
+            && self.soft17_cards == other.soft17_cards
+            && self.settlement == other.settlement
+            && self.shoe == other.shoe
+    }
+
+    /// Effective dealer policy: the custom table if set, else S17/H17.
+    fn policy(&self) -> DealerPolicy {
+        let hits = match self.custom_policy {
+            Some(p) => p,
+            None if self.h17 => policy_h17(),
+            None => policy_s17(),
+        };
+        let mut settle21 = self.settlement;
+        if self.double_exposure {
+            settle21.drawn21_vs_drawn21 = -1.0;
+        }
+        let win_pay = 1.0 - self.win_commission;
+        for v in [&mut settle21.natural_vs_natural, &mut settle21.drawn21_vs_natural, &mut settle21.drawn21_vs_drawn21] {
+            if *v > 0.0 {
+                *v *= win_pay;
+            }
+        }
+        DealerPolicy {
+            hits,
+            empty_shoe: self.empty_shoe,
+            settle21,
+            aces_hard: self.aces_hard_only,
+            push_22: self.push_22,
+            exact: self.exact_rational,
+            soft17_cards: self.soft17_cards,
+            ties_lose: self.double_exposure,
+            h17_by_upcard: self.h17_by_upcard,
+            win_pay,
+        }
+    }
+
+    /// A caller's `deck`, checked by `deck_array` and against the shoe: no
+    /// bucket may hold more cards than the shoe the simulator was built with.
+    fn checked_deck(&self, deck: &[Count]) -> PyResult<[Count; 10]> {
+        let arr = deck_array(deck, "deck")?;
+        if let Some(r) = (0..10).find(|&r| arr[r] > self.shoe[r]) {
+            return Err(PyValueError::new_err(format!(
+                "deck holds {} cards of bucket {}, more than the shoe's {}",
+                arr[r], r, self.shoe[r]
+            )));
+        }
+        Ok(arr)
+    }
+
+    /// Deck the main-game EV is computed from: the caller's deck, or the full
+    /// shoe under a CSM (shown cards go straight back in, so nothing depletes).
+    fn play_deck(&self, deck: &[Count]) -> PyResult<[Count; 10]> {
+        Ok(self.play_shoe(self.checked_deck(deck)?))
+    }
+
+    /// `play_deck` for a shoe built here (`standard_shoe`,
+    /// `shoe_at_true_count`), which need not fit inside the simulator's.
+    fn play_shoe(&self, shoe: [Count; 10]) -> [Count; 10] {
+        if self.csm {
+            self.shoe
+        } else {
+            shoe
+        }
+    }
+
+    /// `arr` with the dealer upcard taken out (when `remove_upcard`), so the
+    /// hole weights, the natural probability and the runout all see the same
+    /// cards: the ones actually unseen once the upcard is face up. A deck with
+    /// none of the upcard rank is taken to have had it removed already (it was
+    /// the last one) and is used as-is.
+    fn without_upcard(&self, mut arr: [Count; 10], up: usize) -> PyResult<[Count; 10]> {
+        if up >= 10 {
+            return Err(PyValueError::new_err(format!("upcard rank index {} out of range 0..9", up)));
+        }
+        if self.remove_upcard && arr[up] > 0 {
+            arr[up] -= 1;
+        }
+        Ok(arr)
+    }
+
+    /// Stand EV against dealer cards already known (upcard first). With
+    /// `remove_upcard` every known dealer card is taken out of `deck`. Two known
+    /// cards forming a natural beat the hand outright.
+    fn stand_vs_known(&self, pt_total: i32, dealer_cards: &[usize], deck: &[Count]) -> PyResult<f64> {
+        if dealer_cards.is_empty() {
+            return Err(PyValueError::new_err("dealer_cards must hold at least the upcard"));
+        }
+        let policy = self.policy().for_up(dealer_cards[0]);
+        let (t, s) = hand_from_cards(dealer_cards, &policy)?;
+        let mut arr = self.play_deck(deck)?;
+        if self.remove_upcard {
+            arr = remove_cards(arr, dealer_cards)
+                .map_err(|_| PyValueError::new_err("deck does not hold the known dealer cards"))?;
+        }
+        if pt_total > 21 {
+            return Ok(-1.0);
+        }
+        if dealer_cards.len() == 2 && policy.natural(dealer_cards[0], dealer_cards[1]) {
+            return Ok(self.nat_loss());
+        }
+        let mut memo = self.memo();
+        let dist = dealer_dist_from_total(&mut arr, t, s, dealer_cards.len().min(255) as u8, &policy, &mut memo.map);
+        Ok(settle_dist(pt_total, &dist, &policy))
+    }
+
+    /// `play_deck` as seen after the upcard is dealt.
+    /// `hole_constraint` as passed, or the one the peek flags imply for `up`
+    /// when it is `HC_AUTO`.
+    fn hole_for(&self, up: usize, hole_constraint: i32) -> i32 {
+        if hole_constraint != HC_AUTO {
+            return hole_constraint;
+        }
+        match up {
+            0 if self.peek_on_ace => HC_NOT_TEN,
+            9 if self.peek_on_ten => HC_NOT_ACE,
+            _ => HC_NONE,
+        }
+    }
+
+    fn live_deck(&self, deck: &[Count], up: usize) -> PyResult<[Count; 10]> {
+        self.without_upcard(self.play_deck(deck)?, up)
+    }
+
+    /// Chance a natural dealt against `up` is suited: from the caller's
+    /// `suited` deck (holding `deck`'s cards, upcard included per
+    /// `remove_upcard`), else `SUITED_NATURAL_PROB`.
+    fn suited_for(&self, deck: &[Count], up: usize, suited: Option<Vec<Vec<Count>>>) -> PyResult<f64> {
+        let Some(rows) = suited else {
+            return Ok(SUITED_NATURAL_PROB);
+        };
+        let suits = suits_of(&rows, &self.play_deck(deck)?)?;
+        Ok(suited_natural_prob(&suits, self.remove_upcard.then_some(up)))
+    }
+
+    fn stand_in(&self, pt_total: i32, up: usize, arr: &[Count; 10], hole_constraint: i32, memo: &mut Memo) -> f64 {
+        settle_stood(arr, pt_total, up, hole_constraint, &self.policy(), self.nat_loss(), memo)
+    }
+
+    /// Player natural, resolved at once: the full payout, plus the bonus on
+    /// the `suited` share, unless the hole constraint still allows a dealer
+    /// natural, which pushes.
+    fn natural_in(&self, up: usize, arr: &[Count; 10], hole_constraint: i32, suited: f64) -> f64 {
+        let policy = self.policy();
+        let payout = policy.pay(self.bj_payout + suited * self.blackjack_bonus);
+        settle_natural(arr, up, &constraint_weights(hole_constraint), payout, policy.settle21.natural_vs_natural)
+    }
+
+    /// Standing value of an untouched initial hand: a natural takes the
+    /// `bj_payout` path (plus the expected suited bonus), anything else settles normally. Split children never
+    /// come through here, so a post-split 21 is always paid 1:1.
+    #[allow(clippy::too_many_arguments)]
+    fn initial_stand_in(
+        &self,
+        pt_total: i32,
+        natural: bool,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        memo: &mut Memo,
+    ) -> f64 {
+        if natural {
+            self.natural_in(up, arr, hole_constraint, SUITED_NATURAL_PROB)
+        } else {
+            self.stand_in(pt_total, up, arr, hole_constraint, memo)
+        }
+    }
+
+    /// Per-stake loss of an undoubled hand to an ENHC dealer natural: the
+    /// stake, or nothing when every bet is refunded.
+    fn nat_loss(&self) -> f64 {
+        if self.no_peek_full_refund {
+            0.0
+        } else {
+            -1.0
+        }
+    }
+
+    /// Per-stake loss of a doubled hand to an ENHC dealer natural: the whole
+    /// doubled stake, only the original half under OBO or refund-extra, or
+    /// nothing under a full refund.
+    fn double_nat_loss(&self) -> f64 {
+        if self.no_peek_full_refund {
+            0.0
+        } else if self.obo || self.enhc_refund_extra {
+            -0.5
+        } else {
+            -1.0
+        }
+    }
+
+    /// Per-stake losses of an undoubled and a doubled split hand to an ENHC
+    /// dealer natural.
+    fn split_nat_losses(&self) -> (f64, f64) {
+        if self.no_peek_full_refund {
+            (0.0, 0.0)
+        } else if self.obo {
+            (-0.5, -0.25)
+        } else if self.enhc_refund_extra {
+            (-1.0, -0.5)
+        } else {
+            (-1.0, -1.0)
+        }
+    }
+
+    /// One card to a two-card hand, then stand: `hit_optimal_in` at depth 1,
+    /// sharing its memo.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_once_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        nat_loss: f64,
+        memo: &mut Memo,
+    ) -> f64 {
+        self.hit_optimal_in(pt_total, pt_soft, 2, up, arr, hole_constraint, nat_loss, 1, memo)
+    }
+
+    /// The hit every decision prices: take a card and play on, up to
+    /// `dp_depth` draws in all and never past `max_player_cards` for a hand
+    /// already holding `cards`. Only `hit_then_stand_ev` stops at one card.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_in(&self, pt_total: i32, pt_soft: bool, cards: usize, up: usize, arr: &[Count; 10], hole_constraint: i32, nat_loss: f64, memo: &mut Memo) -> f64 {
+        self.hit_optimal_in(pt_total, pt_soft, cards, up, arr, hole_constraint, nat_loss, self.hit_depth(cards), memo)
+    }
+
+    /// Draws `hit_in` allows a hand holding `cards`: at least one.
+    fn hit_depth(&self, cards: usize) -> usize {
+        self.max_player_cards.map_or(self.dp_depth, |m| self.dp_depth.min(m.saturating_sub(cards))).max(1)
+    }
+
+    /// `depth` draws for a two-card hand, cut to what `max_player_cards`
+    /// leaves it.
+    fn draw_limit(&self, depth: usize) -> usize {
+        self.max_player_cards.map_or(depth, |m| depth.min(m - 2))
+    }
+
+    /// Whether a hand of `cards` cards on `pt_total` is a `charlie` winner.
+    fn is_charlie(&self, pt_total: i32, cards: usize) -> bool {
+        self.charlie && pt_total <= 21 && self.max_player_cards.is_some_and(|m| cards >= m)
+    }
+
+    /// Chance a dealer natural still takes a Charlie: the hole constraint's
+    /// natural probability under ENHC, 0 with `charlie_beats_bj`.
+    fn charlie_nat_prob(&self, up: usize, arr: &[Count; 10], hole_constraint: i32) -> f64 {
+        if self.charlie_beats_bj || self.aces_hard_only {
+            return 0.0;
+        }
+        weighted_natural_prob(arr, up, &constraint_weights(hole_constraint))
+    }
+
+    /// Per-stake EV of a Charlie: an even-money win, or `nat_loss` to a
+    /// dealer natural that beats it (`charlie_nat_prob`).
+    fn charlie_in(&self, up: usize, arr: &[Count; 10], hole_constraint: i32, nat_loss: f64) -> f64 {
+        let p_nat = self.charlie_nat_prob(up, arr, hole_constraint);
+        (1.0 - p_nat) * self.policy().win_pay + p_nat * nat_loss
+    }
+
+    /// `settle_stood` for a finished hand of `cards` cards, paying a Charlie
+    /// where `is_charlie` says so.
+    #[allow(clippy::too_many_arguments)]
+    fn settle_hand(&self, arr: &[Count; 10], pt_total: i32, cards: usize, up: usize, hole_constraint: i32, nat_loss: f64, memo: &mut Memo) -> f64 {
+        if self.is_charlie(pt_total, cards) {
+            return self.charlie_in(up, arr, hole_constraint, nat_loss);
+        }
+        settle_stood(arr, pt_total, up, hole_constraint, &self.policy(), nat_loss, memo)
+    }
+
+    /// Best of stand and hit for a hand of `cards` cards that may only do
+    /// those two, drawing up to `depth` more (each draw re-decides hit vs
+    /// stand). A Charlie is paid as soon as the hand completes one.
+    #[allow(clippy::too_many_arguments)]
+    fn play_out_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        cards: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        nat_loss: f64,
+        depth: usize,
+        memo: &mut Memo,
+    ) -> f64 {
+        let stand = self.settle_hand(arr, pt_total, cards, up, hole_constraint, nat_loss, memo);
+        if depth == 0 || pt_total >= 21 || arr.iter().sum::<i32>() <= 0 || self.is_charlie(pt_total, cards) {
+            return stand;
+        }
+        let hit = self.hit_optimal_in(pt_total, pt_soft, cards, up, arr, hole_constraint, nat_loss, depth, memo);
+        best_action(&[("stand", stand), ("hit", hit)]).1
+    }
+
+    /// Take one card now, then play the result out with `play_out_in` on
+    /// `depth - 1` further cards; `cards` is the hand's size before the draw.
+    /// The drawn hand's (total, soft) comes from `add_to`, so soft 17 + 5
+    /// re-decides as hard 12, not as a bust.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_optimal_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        cards: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        nat_loss: f64,
+        depth: usize,
+        memo: &mut Memo,
+    ) -> f64 {
+        let rem: i32 = arr.iter().sum();
+        if rem <= 0 {
+            return self.settle_hand(arr, pt_total, cards, up, hole_constraint, nat_loss, memo);
+        }
+        let mut arr = *arr;
+        let mut hit = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[r] -= 1;
+            let (t, s) = self.policy().add(pt_total, pt_soft, r);
+            hit += p * self.play_out_in(t, s, cards + 1, up, &arr, hole_constraint, nat_loss, depth.saturating_sub(1), memo);
+            arr[r] += 1;
+        }
+        hit
+    }
+
+    /// `play_out_in` for a player who never risks a bust: hitting is only on
+    /// the table while no card can break the hand (hard 11 or less, or soft),
+    /// otherwise the hand stands, as it does at `max_player_cards` (paid as
+    /// a Charlie where `charlie` applies).
+    #[allow(clippy::too_many_arguments)]
+    fn never_bust_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        cards: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        nat_loss: f64,
+        memo: &mut Memo,
+    ) -> f64 {
+        let stand = self.settle_hand(arr, pt_total, cards, up, hole_constraint, nat_loss, memo);
+        let rem: i32 = arr.iter().sum();
+        if pt_total >= 21 || (!pt_soft && pt_total > 11) || rem <= 0 || self.max_player_cards.is_some_and(|m| cards >= m) {
+            return stand;
+        }
+        let mut arr = *arr;
+        let mut hit = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[r] -= 1;
+            let (t, s) = self.policy().add(pt_total, pt_soft, r);
+            hit += p * self.never_bust_in(t, s, cards + 1, up, &arr, hole_constraint, nat_loss, memo);
+            arr[r] += 1;
+        }
+        best_action(&[("stand", stand), ("hit", hit)]).1
+    }
+
+    /// Mechanical "hit until total >= target, then stand" (per-stake) for a
+    /// hand of `cards` cards, standing (or taking a Charlie) at
+    /// `max_player_cards`. Each draw is
+    /// removed from the deck before the dealer runout, so the settlement stays exact.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_to_total_in(
+        &self,
+        counts: &mut [Count; 10],
+        total: i32,
+        soft: bool,
+        cards: usize,
+        target: i32,
+        up: usize,
+        hole_constraint: i32,
+        nat_loss: f64,
+        memo: &mut Memo,
+    ) -> f64 {
+        let rem: i32 = counts.iter().sum();
+        if total > 21 || total >= target || rem <= 0 || self.max_player_cards.is_some_and(|m| cards >= m) {
+            return self.settle_hand(counts, total, cards, up, hole_constraint, nat_loss, memo);
+        }
+        let mut acc = 0.0;
+        for r in 0..10 {
+            let c = counts[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            counts[r] -= 1;
+            let (nt, ns) = self.policy().add(total, soft, r);
+            acc += p * self.hit_to_total_in(counts, nt, ns, cards + 1, target, up, hole_constraint, nat_loss, memo);
+            counts[r] += 1;
+        }
+        acc
+    }
+
+    /// Player copying the dealer: hit while the dealer policy says hit (and
+    /// `max_player_cards` allows, where a Charlie is paid), then stand; a bust
+    /// loses before the dealer plays (per-stake).
+    #[allow(clippy::too_many_arguments)]
+    fn mimic_dealer_in(
+        &self,
+        counts: &mut [Count; 10],
+        total: i32,
+        soft: bool,
+        cards: usize,
+        up: usize,
+        hole_constraint: i32,
+        nat_loss: f64,
+        memo: &mut Memo,
+    ) -> f64 {
+        let policy = self.policy();
+        let rem: i32 = counts.iter().sum();
+        if total > 21 || !policy.hits(total, soft) || rem <= 0 || self.max_player_cards.is_some_and(|m| cards >= m) {
+            return self.settle_hand(counts, total, cards, up, hole_constraint, nat_loss, memo);
+        }
+        let mut acc = 0.0;
+        for r in 0..10 {
+            let c = counts[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            counts[r] -= 1;
+            let (nt, ns) = policy.add(total, soft, r);
+            acc += p * self.mimic_dealer_in(counts, nt, ns, cards + 1, up, hole_constraint, nat_loss, memo);
+            counts[r] += 1;
+        }
+        acc
+    }
+
+    /// `play_out_in` for a player who only sees `lookahead` cards ahead: each
+    /// node weighs stand against `hit_optimal_in` over that horizon, and a
+    /// hit draws and decides again with a fresh horizon. The EV is the
+    /// policy's own, carried to the end of the hand; a hand of `cards` cards
+    /// sees no further than `max_player_cards` still allows.
+    #[allow(clippy::too_many_arguments)]
+    fn lookahead_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        cards: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        nat_loss: f64,
+        lookahead: usize,
+        memo: &mut Memo,
+    ) -> f64 {
+        let stand = self.settle_hand(arr, pt_total, cards, up, hole_constraint, nat_loss, memo);
+        let rem: i32 = arr.iter().sum();
+        let horizon = self.max_player_cards.map_or(lookahead, |m| lookahead.min(m.saturating_sub(cards)));
+        if horizon == 0 || pt_total >= 21 || rem <= 0 {
+            return stand;
+        }
+        let seen = self.hit_optimal_in(pt_total, pt_soft, cards, up, arr, hole_constraint, nat_loss, horizon, memo);
+        if best_action(&[("stand", stand), ("hit", seen)]).0 == "stand" {
+            return stand;
+        }
+        let mut arr = *arr;
+        let mut hit = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[r] -= 1;
+            let (t, s) = self.policy().add(pt_total, pt_soft, r);
+            hit += p * self.lookahead_in(t, s, cards + 1, up, &arr, hole_constraint, nat_loss, lookahead, memo);
+            arr[r] += 1;
+        }
+        hit
+    }
+
+    /// Probability-weighted EV gained below this node by playing each hand on
+    /// its exact composition (`exact`) instead of its total on `base`. The
+    /// player follows the total-only stand/hit choice, cached per
+    /// (total, soft) in `by_total`, so only hands that choice reaches count.
+    #[allow(clippy::too_many_arguments)]
+    fn composition_gain_in(
+        &self,
+        hand: (i32, bool, usize),
+        p: f64,
+        up: usize,
+        base: &[Count; 10],
+        exact: &mut [Count; 10],
+        hole_constraint: i32,
+        max_cards: usize,
+        by_total: &mut HashMap<(i32, bool), bool>,
+        memo: &mut Memo,
+    ) -> f64 {
+        let (total, soft, n) = hand;
+        if total >= 21 {
+            return 0.0;
+        }
+        let nat = self.nat_loss();
+        let (mut gain, mut hits) = (0.0, true);
+        if n >= 2 {
+            hits = *by_total.entry((total, soft)).or_insert_with(|| {
+                let st = self.stand_in(total, up, base, hole_constraint, memo);
+                let hi = self.hit_in(total, soft, n, up, base, hole_constraint, nat, memo);
+                best_action(&[("stand", st), ("hit", hi)]).0 == "hit"
+            });
+            let st = self.stand_in(total, up, exact, hole_constraint, memo);
+            let hi = self.hit_in(total, soft, n, up, exact, hole_constraint, nat, memo);
+            gain = st.max(hi) - if hits { hi } else { st };
+            hits = hits && n < max_cards;
+        }
+        let rem: i32 = exact.iter().sum();
+        let mut acc = p * gain;
+        if !hits || rem <= 0 {
+            return acc;
+        }
+        for r in 0..10 {
+            let c = exact[r];
+            if c <= 0 {
+                continue;
+            }
+            let q = (c as f64) / (rem as f64);
+            exact[r] -= 1;
+            let (t, s) = self.policy().add(total, soft, r);
+            acc += self.composition_gain_in((t, s, n + 1), p * q, up, base, exact, hole_constraint, max_cards, by_total, memo);
+            exact[r] += 1;
+        }
+        acc
+    }
+
+    /// Double in units of the original bet: one card, then the settled
+    /// result counts twice because the stake is doubled. A free double
+    /// risks only the original unit, so its second unit adds the wins alone.
+    #[allow(clippy::too_many_arguments)]
+    fn double_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        memo: &mut Memo,
+    ) -> f64 {
+        if self.free_double(pt_total, pt_soft) {
+            return self.hit_once_in(pt_total, pt_soft, up, arr, hole_constraint, self.nat_loss(), memo)
+                + self.free_hit_once_in(pt_total, pt_soft, up, arr, hole_constraint, memo);
+        }
+        // A busting card settles at -1 per stake before any dealer natural
+        // (`settle_stood`), so a busted double loses both units.
+        2.0 * self.hit_once_in(pt_total, pt_soft, up, arr, hole_constraint, self.double_nat_loss(), memo)
+    }
+
+    /// Free Bet: a double on hard 9-11 is free.
+    fn free_double(&self, pt_total: i32, pt_soft: bool) -> bool {
+        self.free_bet && !pt_soft && (9..=11).contains(&pt_total)
+    }
+
+    /// The winning side of `stand_in`: what a house-funded unit earns on a
+    /// stood total, since it pays on a win and costs nothing on a loss.
+    fn free_stand_in(&self, pt_total: i32, up: usize, arr: &[Count; 10], hole_constraint: i32, memo: &mut Memo) -> f64 {
+        if pt_total > 21 {
+            return 0.0;
+        }
+        let policy = self.policy();
+        let t = &policy.settle21;
+        let (p_nat, dist) = self.natural_and_runout(up, arr, hole_constraint, &policy, memo);
+        let vs_nat = if pt_total == 21 { -t.drawn21_vs_natural } else { 1.0 };
+        let won: f64 = dist
+            .iter()
+            .enumerate()
+            .map(|(i, d)| {
+                let v = if pt_total == 21 && i == 4 { t.drawn21_vs_drawn21 } else { settle_vs_player(pt_total, i, &policy) };
+                v.max(0.0) * d
+            })
+            .sum();
+        (1.0 - p_nat) * won + p_nat * (self.nat_loss() * vs_nat).max(0.0)
+    }
+
+    /// `hit_once_in` for a house-funded unit (see `free_stand_in`).
+    fn free_hit_once_in(&self, pt_total: i32, pt_soft: bool, up: usize, arr: &[Count; 10], hole_constraint: i32, memo: &mut Memo) -> f64 {
+        let mut arr = *arr;
+        let rem: i32 = arr.iter().sum();
+        if rem <= 0 {
+            return self.free_stand_in(pt_total, up, &arr, hole_constraint, memo);
+        }
+        let mut acc = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            arr[r] -= 1;
+            let (t, _) = self.policy().add(pt_total, pt_soft, r);
+            acc += (c as f64) / (rem as f64) * self.free_stand_in(t, up, &arr, hole_constraint, memo);
+            arr[r] += 1;
+        }
+        acc
+    }
+
+    /// The best play and per-stake EV of one split child that drew `card` to
+    /// its pair card; `arr` has both pair cards and `card` removed.
+    #[allow(clippy::too_many_arguments)]
+    fn split_child_in(
+        &self,
+        pair_rank: usize,
+        card: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        das: DasRule,
+        post_split: DoubleRule,
+        split_aces_one: bool,
+        memo: &mut Memo,
+    ) -> (&'static str, f64) {
+        // Split hands restart from one pair card (an ace is soft 11), and a
+        // two-card 21 here is an ordinary 21, never a natural.
+        let policy = self.policy();
+        let (t0, s0) = policy.add(0, false, pair_rank);
+        let (t, s) = policy.add(t0, s0, card);
+
+        // Under OBO an ENHC natural takes only the original stake, i.e. half
+        // of each undoubled child and a quarter of a doubled one.
+        // A full refund returns both children whatever they did.
+        let (nat, nat_dbl) = self.split_nat_losses();
+        if split_aces_one && pair_rank == 0 {
+            if !self.split_ace_double(t, das, post_split) {
+                return ("stand", sibling_average(arr, |rest, memo| settle_stood(rest, t, up, hole_constraint, &policy, nat, memo), memo));
+            }
+            // Both aces have their card before either doubles, so the choice
+            // is made per sibling card; the label is the better play on average.
+            let mut stand = |rest: &[Count; 10], memo: &mut Memo| settle_stood(rest, t, up, hole_constraint, &policy, nat, memo);
+            let mut double = |rest: &[Count; 10], memo: &mut Memo| 2.0 * self.hit_once_in(t, s, up, rest, hole_constraint, nat_dbl, memo);
+            let es = sibling_average(arr, &mut stand, memo);
+            let ed = sibling_average(arr, &mut double, memo);
+            let ev = sibling_average(arr, |rest, memo| stand(rest, memo).max(double(rest, memo)), memo);
+            return (best_action(&[("stand", es), ("double", ed)]).0, ev);
+        }
+        let es = settle_stood(arr, t, up, hole_constraint, &policy, nat, memo);
+        let eh = self.hit_in(t, s, 2, up, arr, hole_constraint, nat, memo);
+        let ed = if !(das.allows(1) && post_split.allows(t)) {
+            f64::NEG_INFINITY
+        } else if self.free_double(t, s) {
+            self.hit_once_in(t, s, up, arr, hole_constraint, nat, memo) + self.free_hit_once_in(t, s, up, arr, hole_constraint, memo)
+        } else {
+            2.0 * self.hit_once_in(t, s, up, arr, hole_constraint, nat_dbl, memo)
+        };
+        best_action(&[("stand", es), ("hit", eh), ("double", ed)])
+    }
+
+    /// Whether a one-card split ace on `t` may double under `double_split_aces`.
+    fn split_ace_double(&self, t: i32, das: DasRule, post_split: DoubleRule) -> bool {
+        self.double_split_aces && !self.free_bet && das.allows(1) && post_split.allows(t)
+    }
+
+    /// Free Bet's house-funded split hand for the same draw: only its wins
+    /// count, so it takes whichever play wins most, a free double included.
+    #[allow(clippy::too_many_arguments)]
+    fn free_split_child_in(
+        &self,
+        pair_rank: usize,
+        card: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        das: DasRule,
+        post_split: DoubleRule,
+        split_aces_one: bool,
+        memo: &mut Memo,
+    ) -> f64 {
+        let policy = self.policy();
+        let (t0, s0) = policy.add(0, false, pair_rank);
+        let (t, s) = policy.add(t0, s0, card);
+        if split_aces_one && pair_rank == 0 {
+            return sibling_average(arr, |rest, memo| self.free_stand_in(t, up, rest, hole_constraint, memo), memo);
+        }
+        let fs = self.free_stand_in(t, up, arr, hole_constraint, memo);
+        let fh = self.free_hit_once_in(t, s, up, arr, hole_constraint, memo);
+        let fd = if das.allows(1) && post_split.allows(t) && self.free_double(t, s) { 2.0 * fh } else { 0.0 };
+        fs.max(fh).max(fd)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn split_in(
+        &self,
+        pair_rank: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        das: DasRule,
+        post_split: DoubleRule,
+        split_aces_one: bool,
+        memo: &mut Memo,
+    ) -> f64 {
+        let mut arr = *arr;
+        let rem0: i32 = arr.iter().sum();
+        if rem0 <= 0 {
+            return 0.0;
+        }
+
+        // Free Bet splits every pair but tens for free.
+        let free_split = self.free_bet && pair_rank != 9;
+        let (mut total, mut free_total) = (0.0, 0.0);
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem0 as f64);
+            arr[r] -= 1;
+            let (_, ev_child) = self.split_child_in(pair_rank, r, up, &arr, hole_constraint, das, post_split, split_aces_one, memo);
+            if free_split {
+                free_total += p * self.free_split_child_in(pair_rank, r, up, &arr, hole_constraint, das, post_split, split_aces_one, memo);
+            }
+            total += p * ev_child;
+            arr[r] += 1;
+        }
+        if free_split {
+            // Per child, like a paid split: half the two hands' sum.
+            return (total + free_total) / 2.0;
+        }
+        total
+    }
+
+    /// Whether `resplit_in` can resplit this pair under `(max_hands, resplit_aces)`.
+    fn resplits(&self, pair_rank: usize, split_aces_one: bool, (max_hands, resplit_aces): (usize, bool)) -> bool {
+        max_hands > 2 && !self.free_bet && !(split_aces_one && pair_rank == 0 && !resplit_aces)
+    }
+
+    /// `split_in` with resplits up to `max_hands` hands in all, as per-child
+    /// EV (half the sum over every hand). Free Bet splits and one-card aces
+    /// without `resplit_aces` stay single splits.
+    #[allow(clippy::too_many_arguments)]
+    fn resplit_in(
+        &self,
+        pair_rank: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        das: DasRule,
+        post_split: DoubleRule,
+        split_aces_one: bool,
+        (max_hands, resplit_aces): (usize, bool),
+        memo: &mut Memo,
+    ) -> f64 {
+        if !self.resplits(pair_rank, split_aces_one, (max_hands, resplit_aces)) {
+            return self.split_in(pair_rank, up, arr, hole_constraint, das, post_split, split_aces_one, memo);
+        }
+        let rules = (das, post_split, split_aces_one, max_hands);
+        self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, (2, 0, 2), &mut HashMap::new(), memo) / 2.0
+    }
+
+    /// Summed EV of the split hands still waiting for their second card:
+    /// `first` from the first split, `deeper` from resplits, `hands` at the
+    /// table so far. Resplit hands are played first, each resplit takes one
+    /// more pair card out of `arr`, and a hand doubles only if `das` allows
+    /// its split depth. Siblings see the same deck, as in `split_in`; see
+    /// `split_ev` for what that costs once resplits are allowed.
+    #[allow(clippy::too_many_arguments)]
+    fn pending_hands_in(
+        &self,
+        pair_rank: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        rules: (DasRule, DoubleRule, bool, usize),
+        key: (usize, usize, usize),
+        states: &mut HashMap<(usize, usize, usize), f64>,
+        memo: &mut Memo,
+    ) -> f64 {
+        let (first, deeper, hands) = key;
+        if first + deeper == 0 {
+            return 0.0;
+        }
+        if let Some(&v) = states.get(&key) {
+            return v;
+        }
+        let (das, post_split, split_aces_one, max_hands) = rules;
+        let mut deck = *arr;
+        deck[pair_rank] -= (hands - 2) as Count;
+        let rem: i32 = deck.iter().sum();
+        let (first, deeper, depth) = if deeper > 0 { (first, deeper - 1, 2) } else { (first - 1, deeper, 1) };
+        let hand_das = if das.allows(depth) { das } else { DasRule::None };
+        let later = self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, (first, deeper, hands), states, memo);
+        let mut ev = 0.0;
+        for r in 0..10 {
+            let c = deck[r];
+            if c <= 0 {
+                continue;
+            }
+            deck[r] -= 1;
+            let (_, child) = self.split_child_in(pair_rank, r, up, &deck, hole_constraint, hand_das, post_split, split_aces_one, memo);
+            let mut best = child + later;
+            if r == pair_rank && hands < max_hands {
+                let resplit = (first, deeper + 2, hands + 1);
+                best = best.max(self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, resplit, states, memo));
+            }
+            ev += (c as f64) / (rem as f64) * best;
+            deck[r] += 1;
+        }
+        states.insert(key, ev);
+        ev
+    }
+
+    /// `pending_hands_in` at `key` as a `split_tree` node: `{"ev", "branches",
+    /// "next"}`, where `ev` sums the waiting hands, `branches` are the next
+    /// hand's cards (`{"card", "prob", "action", "ev"}`, plus a `"resplit"`
+    /// node on a pair card while `max_hands` allows; `action` is "resplit"
+    /// when that beats playing on) and `next` is the hands after it.
+    #[allow(clippy::too_many_arguments)]
+    fn split_node<'py>(
+        &self,
+        py: Python<'py>,
+        pair_rank: usize,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        rules: (DasRule, DoubleRule, bool, usize),
+        key: (usize, usize, usize),
+        states: &mut HashMap<(usize, usize, usize), f64>,
+        memo: &mut Memo,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let out = PyDict::new_bound(py);
+        out.set_item("ev", self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, key, states, memo))?;
+        let (first, deeper, hands) = key;
+        if first + deeper == 0 {
+            return Ok(out);
+        }
+        let (das, post_split, split_aces_one, max_hands) = rules;
+        let mut deck = *arr;
+        deck[pair_rank] -= (hands - 2) as Count;
+        let rem: i32 = deck.iter().sum();
+        let (first, deeper, depth) = if deeper > 0 { (first, deeper - 1, 2) } else { (first - 1, deeper, 1) };
+        let hand_das = if das.allows(depth) { das } else { DasRule::None };
+        let later = (first, deeper, hands);
+        let later_ev = self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, later, states, memo);
+        let mut branches = Vec::new();
+        for r in 0..10 {
+            let c = deck[r];
+            if c <= 0 {
+                continue;
+            }
+            deck[r] -= 1;
+            let (mut action, child) = self.split_child_in(pair_rank, r, up, &deck, hole_constraint, hand_das, post_split, split_aces_one, memo);
+            let branch = PyDict::new_bound(py);
+            branch.set_item("card", r)?;
+            branch.set_item("prob", (c as f64) / (rem as f64))?;
+            if r == pair_rank && hands < max_hands {
+                let resplit = (first, deeper + 2, hands + 1);
+                if self.pending_hands_in(pair_rank, up, arr, hole_constraint, rules, resplit, states, memo) > child + later_ev {
+                    action = "resplit";
+                }
+                branch.set_item("resplit", self.split_node(py, pair_rank, up, arr, hole_constraint, rules, resplit, states, memo)?)?;
+            }
+            branch.set_item("action", action)?;
+            branch.set_item("ev", child)?;
+            branches.push(branch);
+            deck[r] += 1;
+        }
+        out.set_item("branches", branches)?;
+        if first + deeper > 0 {
+            out.set_item("next", self.split_node(py, pair_rank, up, arr, hole_constraint, rules, later, states, memo)?)?;
+        }
+        Ok(out)
+    }
+
+    /// Per-stake EV of a non-natural hand played by `chart` from here on.
+    /// `node` 0 is the dealt hand (double by `double_rule`, split, surrender),
+    /// 1 a split hand's first decision (double by `das` and
+    /// `post_split_double_rule`), 2 any later one (stand or hit).
+    #[allow(clippy::too_many_arguments)]
+    fn chart_play_in(
+        &self,
+        chart: &StrategyChart,
+        total: i32,
+        soft: bool,
+        cards: usize,
+        pair: Option<usize>,
+        node: u8,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        (nat, nat_dbl): (f64, f64),
+        memo: &mut Memo,
+    ) -> PyResult<f64> {
+        let policy = self.policy();
+        let rem0: i32 = arr.iter().sum();
+        if total >= 21 || rem0 <= 0 || self.max_player_cards.is_some_and(|m| cards >= m) {
+            return Ok(self.settle_hand(arr, total, cards, up, hole_constraint, nat, memo));
+        }
+        let (want, fallback) = match pair {
+            Some(r) => chart.cell(&format!("P{}", RANK_LABELS[r]), up)?,
+            // Only a split ace makes a soft 12 without a pair row.
+            None if soft && total == 12 => ("hit", "hit"),
+            None => chart.cell(&format!("{}{}", if soft { "S" } else { "H" }, total), up)?,
+        };
+        let allowed = match want {
+            "double" => cards == 2 && match node {
+                0 => self.double_rule.allows(total),
+                1 => self.das.allows(1) && self.post_split_double_rule.allows(total),
+                _ => false,
+            },
+            "split" => node == 0 && pair.is_some(),
+            "surrender" => node == 0 && self.surrender,
+            _ => true,
+        };
+        match if allowed { want } else { fallback } {
+            "stand" => Ok(settle_stood(arr, total, up, hole_constraint, &policy, nat, memo)),
+            "double" if node == 0 => Ok(self.double_in(total, soft, up, arr, hole_constraint, memo)),
+            "double" => Ok(2.0 * self.hit_once_in(total, soft, up, arr, hole_constraint, nat_dbl, memo)),
+            "surrender" => Ok(self.surrender_in(up, arr, hole_constraint)),
+            "split" => {
+                let pair_rank = pair.unwrap_or(0);
+                let mut arr = *arr;
+                let rem0: i32 = arr.iter().sum();
+                let split_nat = self.split_nat_losses();
+                let mut child = 0.0;
+                for r in 0..10 {
+                    if arr[r] <= 0 {
+                        continue;
+                    }
+                    let p = (arr[r] as f64) / (rem0 as f64);
+                    arr[r] -= 1;
+                    let (t0, s0) = policy.add(0, false, pair_rank);
+                    let (t, s) = policy.add(t0, s0, r);
+                    child += p * if self.split_aces_one && pair_rank == 0 {
+                        sibling_average(&arr, |rest, memo| settle_stood(rest, t, up, hole_constraint, &policy, split_nat.0, memo), memo)
+                    } else {
+                        self.chart_play_in(chart, t, s, 2, None, 1, up, &arr, hole_constraint, split_nat, memo)?
+                    };
+                    arr[r] += 1;
+                }
+                Ok(2.0 * child)
+            }
+            _ => {
+                let mut arr = *arr;
+                let mut ev = 0.0;
+                for r in 0..10 {
+                    if arr[r] <= 0 {
+                        continue;
+                    }
+                    let p = (arr[r] as f64) / (rem0 as f64);
+                    arr[r] -= 1;
+                    let (t, s) = policy.add(total, soft, r);
+                    ev += p * self.chart_play_in(chart, t, s, cards + 1, None, 2, up, &arr, hole_constraint, (nat, nat_dbl), memo)?;
+                    arr[r] += 1;
+                }
+                Ok(ev)
+            }
+        }
+    }
+
+    /// `deviation_index` on the `shoe_at_count` model for `tags`, searched
+    /// within ±`limit`, with `hole_constraint` already resolved.
+    #[allow(clippy::too_many_arguments)]
+    fn deviation_in(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        pair: Option<usize>,
+        up: usize,
+        num_decks: usize,
+        hole_constraint: i32,
+        tags: &[f64; 10],
+        limit: usize,
+        memo: &mut Memo,
+    ) -> PyResult<Option<f64>> {
+        let mut action_at = |tc: f64| -> PyResult<&'static str> {
+            let arr = self.without_upcard(shoe_at_count(tc, num_decks, tags), up)?;
+            Ok(best_action(&self.action_evs(pt_total, pt_soft, pair, up, &arr, hole_constraint, self.surrender, memo)).0)
+        };
+        let base = action_at(0.0)?;
+        for k in 1..=limit {
+            for dir in [1.0, -1.0] {
+                let mut inside = dir * (k - 1) as f64;
+                let mut outside = dir * k as f64;
+                if action_at(outside)? == base {
+                    continue;
+                }
+                while (outside - inside).abs() > 0.01 {
+                    let mid = 0.5 * (inside + outside);
+                    if action_at(mid)? == base {
+                        inside = mid;
+                    } else {
+                        outside = mid;
+                    }
+                }
+                return Ok(Some(0.5 * (inside + outside)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// EVs of every action legal on a two-card hand, in tie-break priority order.
+    /// `pair_rank` enables split; `can_surrender` adds surrender, which only the
+    /// untouched initial hand may take (never a split child or a hit hand).
+    #[allow(clippy::too_many_arguments)]
+    fn action_evs(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        pair_rank: Option<usize>,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        can_surrender: bool,
+        memo: &mut Memo,
+    ) -> Vec<(&'static str, f64)> {
+        let mut out = vec![
+            ("stand", self.stand_in(pt_total, up, arr, hole_constraint, memo)),
+            ("hit", self.hit_in(pt_total, pt_soft, 2, up, arr, hole_constraint, self.nat_loss(), memo)),
+        ];
+        if self.double_rule.allows(pt_total) {
+            out.push(("double", self.double_in(pt_total, pt_soft, up, arr, hole_constraint, memo)));
+        }
+        if let Some(pr) = pair_rank {
+            let ev = self.split_in(pr, up, arr, hole_constraint, self.das, self.post_split_double_rule, self.split_aces_one, memo);
+            out.push(("split", ev));
+        }
+        if can_surrender {
+            out.push(("surrender", self.surrender_in(up, arr, hole_constraint)));
+        }
+        out
+    }
+
+    /// Net round EV of each action on a dealt (non-natural) starting hand,
+    /// for `initial_deal_ev` and `round_outcome_distribution`. Unlike
+    /// `action_evs`, hitting plays on for up to `depth` cards and a split is
+    /// worth both of its hands.
+    #[allow(clippy::too_many_arguments)]
+    fn deal_options(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        pair_rank: Option<usize>,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        depth: usize,
+        memo: &mut Memo,
+    ) -> Vec<(&'static str, f64)> {
+        let nat = self.nat_loss();
+        let mut out = vec![
+            ("stand", self.stand_in(pt_total, up, arr, hole_constraint, memo)),
+            ("hit", self.hit_optimal_in(pt_total, pt_soft, 2, up, arr, hole_constraint, nat, depth.max(1), memo)),
+        ];
+        if self.double_rule.allows(pt_total) {
+            out.push(("double", self.double_in(pt_total, pt_soft, up, arr, hole_constraint, memo)));
+        }
+        if let Some(pr) = pair_rank {
+            let child = self.split_in(pr, up, arr, hole_constraint, self.das, self.post_split_double_rule, self.split_aces_one, memo);
+            out.push(("split", 2.0 * child));
+        }
+        if self.surrender {
+            out.push(("surrender", self.surrender_in(up, arr, hole_constraint)));
+        }
+        out
+    }
+
+    /// `best_action_ev`'s options: stand, a hit played on for up to
+    /// `dp_depth` cards, then double, split (both hands) and surrender as
+    /// `legal` (can double, pair rank, DAS rule, late surrender) allows.
+    #[allow(clippy::too_many_arguments)]
+    fn legal_action_evs(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        arr: &[Count; 10],
+        hole_constraint: i32,
+        (can_double, split_rank, das, late_surrender): (bool, Option<usize>, DasRule, bool),
+        memo: &mut Memo,
+    ) -> Vec<(&'static str, f64)> {
+        let depth = self.draw_limit(self.dp_depth).max(1);
+        let mut out = vec![
+            ("stand", self.stand_in(pt_total, up, arr, hole_constraint, memo)),
+            ("hit", self.hit_optimal_in(pt_total, pt_soft, 2, up, arr, hole_constraint, self.nat_loss(), depth, memo)),
+        ];
+        if can_double && self.double_rule.allows(pt_total) {
+            out.push(("double", self.double_in(pt_total, pt_soft, up, arr, hole_constraint, memo)));
+        }
+        if let Some(pr) = split_rank {
+            let child = self.split_in(pr, up, arr, hole_constraint, das, self.post_split_double_rule, self.split_aces_one, memo);
+            out.push(("split", 2.0 * child));
+        }
+        if late_surrender {
+            out.push(("surrender", self.surrender_in(up, arr, hole_constraint)));
+        }
+        out
+    }
+
+    /// `soft_strategy_row` on a live deck with `hole_constraint` resolved.
+    fn soft_row_in(&self, soft_total: i32, up: usize, arr: &[Count; 10], hole_constraint: i32, memo: &mut Memo) -> PyResult<(&'static str, f64)> {
+        if !(13..=20).contains(&soft_total) || self.aces_hard_only {
+            return Err(PyValueError::new_err(format!("no two-card soft {} (soft totals run 13-20)", soft_total)));
+        }
+        let arr = remove_cards(*arr, &[0, (soft_total - 12) as usize])?;
+        let depth = self.draw_limit(self.dp_depth);
+        Ok(best_action(&self.deal_options(soft_total, true, None, up, &arr, hole_constraint, depth, memo)))
+    }
+
+    /// `initial_deal_ev` on a play deck of at least four cards, with
+    /// `hole_constraint` resolved per upcard and naturals suited by `suits`
+    /// when given (the same cards as `arr`).
+    fn deal_ev_in(&self, mut arr: [Count; 10], hole_constraint: i32, suits: Option<&[[Count; 4]; 13]>, memo: &mut Memo) -> f64 {
+        if self.double_exposure {
+            return self.exposed_deal_ev(arr, memo);
+        }
+        let policy = self.policy();
+        let n: i32 = arr.iter().sum();
+        let mut ev = 0.0;
+        for up in 0..10 {
+            if arr[up] <= 0 {
+                continue;
+            }
+            let p_up = (arr[up] as f64) / (n as f64);
+            arr[up] -= 1;
+            let hc = self.hole_for(up, hole_constraint);
+            let suited = suits.map_or(SUITED_NATURAL_PROB, |s| suited_natural_prob(s, Some(up)));
+            for a in 0..10 {
+                if arr[a] <= 0 {
+                    continue;
+                }
+                let pa = (arr[a] as f64) / ((n - 1) as f64);
+                arr[a] -= 1;
+                for b in 0..10 {
+                    if arr[b] <= 0 {
+                        continue;
+                    }
+                    let pb = (arr[b] as f64) / ((n - 2) as f64);
+                    arr[b] -= 1;
+                    let (t0, s0) = policy.add(0, false, a);
+                    let (t, s) = policy.add(t0, s0, b);
+                    let hand = if policy.natural(a, b) {
+                        self.natural_in(up, &arr, HC_NONE, suited)
+                    } else {
+                        let pair = if a == b { Some(a) } else { None };
+                        let best = best_action(&self.deal_options(t, s, pair, up, &arr, hc, self.draw_limit(self.dp_depth), memo)).1;
+                        if hc == HC_NONE {
+                            best
+                        } else {
+                            let p_nat = if self.aces_hard_only { 0.0 } else { natural_prob(&arr, up) };
+                            (1.0 - p_nat) * best - p_nat
+                        }
+                    };
+                    ev += p_up * pa * pb * hand;
+                    arr[b] += 1;
+                }
+                arr[a] += 1;
+            }
+            arr[up] += 1;
+        }
+        ev
+    }
+
+    /// `initial_deal_ev` under double exposure: both dealer cards are dealt
+    /// from `arr` face up, and each hand plays against its known hole. A
+    /// dealer natural ends the round before any play.
+    fn exposed_deal_ev(&self, mut arr: [Count; 10], memo: &mut Memo) -> f64 {
+        let policy = self.policy();
+        let n: i32 = arr.iter().sum();
+        let mut ev = 0.0;
+        for up in 0..10 {
+            if arr[up] <= 0 {
+                continue;
+            }
+            let p_up = (arr[up] as f64) / (n as f64);
+            arr[up] -= 1;
+            for hole in 0..10 {
+                if arr[hole] <= 0 {
+                    continue;
+                }
+                let p_dealer = p_up * (arr[hole] as f64) / ((n - 1) as f64);
+                arr[hole] -= 1;
+                let hc = HC_KNOWN + hole as i32;
+                for a in 0..10 {
+                    if arr[a] <= 0 {
+                        continue;
+                    }
+                    let pa = (arr[a] as f64) / ((n - 2) as f64);
+                    arr[a] -= 1;
+                    for b in 0..10 {
+                        if arr[b] <= 0 {
+                            continue;
+                        }
+                        let pb = (arr[b] as f64) / ((n - 3) as f64);
+                        arr[b] -= 1;
+                        let hand = if policy.natural(a, b) {
+                            self.natural_in(up, &arr, hc, SUITED_NATURAL_PROB)
+                        } else if policy.natural(up, hole) {
+                            -1.0
+                        } else {
+                            let (t0, s0) = policy.add(0, false, a);
+                            let (t, s) = policy.add(t0, s0, b);
+                            let pair = if a == b { Some(a) } else { None };
+                            best_action(&self.deal_options(t, s, pair, up, &arr, hc, self.draw_limit(self.dp_depth), memo)).1
+                        };
+                        ev += p_dealer * pa * pb * hand;
+                        arr[b] += 1;
+                    }
+                    arr[a] += 1;
+                }
+                arr[hole] += 1;
+            }
+            arr[up] += 1;
+        }
+        ev
+    }
+
+    /// Best two-card action and EV against each upcard (indexed by upcard),
+    /// each on the deck minus that upcard with its own resolved hole
+    /// constraint. All ten share the simulator memo.
+    fn optimal_by_upcard(&self, pt_total: i32, pt_soft: bool, pair: Option<usize>, deck: &[Count], hole_constraint: i32) -> PyResult<Vec<(&'static str, f64)>> {
+        let mut memo = self.memo();
+        (0..10)
+            .map(|up| {
+                let arr = self.live_deck(deck, up)?;
+                let hc = self.hole_for(up, hole_constraint);
+                let evs = self.action_evs(pt_total, pt_soft, pair, up, &arr, hc, self.surrender, &mut memo.map);
+                Ok(best_action(&evs))
+            })
+            .collect()
+    }
+
+    fn insurance_in(&self, arr: &[Count; 10]) -> f64 {
+        let rem: i32 = arr.iter().sum();
+        if rem <= 0 || self.aces_hard_only {
+            return -1.0;
+        }
+        let p_ten = (arr[9].max(0) as f64) / (rem as f64);
+        2.0 * p_ten - (1.0 - p_ten)
+    }
+
+    /// Late surrender: `surrender_fraction` of the stake lost, unless a dealer
+    /// natural the hole constraint still allows comes up first and costs
+    /// `nat_loss`.
+    fn surrender_in(&self, up: usize, arr: &[Count; 10], hole_constraint: i32) -> f64 {
+        let p_nat = if self.aces_hard_only {
+            0.0
+        } else {
+            weighted_natural_prob(arr, up, &constraint_weights(hole_constraint))
+        };
+        (1.0 - p_nat) * -self.surrender_fraction + p_nat * self.nat_loss()
+    }
+
+    /// Dealer-natural probability and the runout given no natural for one
+    /// upcard. With nothing left to deal the dealer stops on the upcard alone.
+    fn natural_and_runout(&self, up: usize, arr: &[Count; 10], hole_constraint: i32, policy: &DealerPolicy, memo: &mut Memo) -> (f64, [f64; 6]) {
+        if arr.iter().sum::<i32>() <= 0 {
+            let (t, s) = policy.add(0, false, up);
+            (0.0, dealer_dist_from_total(&mut [0; 10], t, s, 1, &policy.for_up(up), memo))
+        } else {
+            dealer_dist_split_natural(arr, up, &constraint_weights(hole_constraint), policy, memo)
+        }
+    }
+
+    /// Lock the shared memo, dropping it first if the rules changed since it was filled.
+    fn memo(&self) -> MutexGuard<'_, DealerMemo> {
+        let mut m = self.memo.lock().unwrap_or_else(|e| e.into_inner());
+        let fp = self.fingerprint();
+        if m.rules != fp {
+            m.map.clear();
+            m.rules = fp;
+        }
+        m
+    }
+}
+
+#[pymethods]
+impl BlackjackSimulator {
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (shoe_counts, h17, dp_depth=None, dp_depth_dbl=None, *, das=true, das_rule=None, split_aces_one=true, double_split_aces=false, double_rule="any", post_split_double_rule=None, surrender=false, surrender_fraction=0.5, max_player_cards=None, charlie=false, charlie_beats_bj=false, csm=false, obo=false, enhc_refund_extra=false, bj_payout=1.5, blackjack_bonus=0.0, win_commission=0.0, dealer_policy=None, remove_upcard=true, no_peek_full_refund=false, empty_shoe_policy="clamp", settlement=None, peek_on_ace=true, peek_on_ten=true, aces_hard_only=false, push_22=false, exact_rational=false, free_bet=false, double_exposure=false, soft17_cards="any", h17_by_upcard=None))]
+    fn new(
+        shoe_counts: Vec<Count>,
+        h17: bool,
+        dp_depth: Option<usize>,
+        dp_depth_dbl: Option<usize>,
+        das: bool,
+        das_rule: Option<&str>,
+        split_aces_one: bool,
+        double_split_aces: bool,
+        double_rule: &str,
+        post_split_double_rule: Option<&str>,
+        surrender: bool,
+        surrender_fraction: f64,
+        max_player_cards: Option<usize>,
+        charlie: bool,
+        charlie_beats_bj: bool,
+        csm: bool,
+        obo: bool,
+        enhc_refund_extra: bool,
+        bj_payout: f64,
+        blackjack_bonus: f64,
+        win_commission: f64,
+        dealer_policy: Option<Vec<[bool; 2]>>,
+        remove_upcard: bool,
+        no_peek_full_refund: bool,
+        empty_shoe_policy: &str,
+        settlement: Option<Settlement>,
+        peek_on_ace: bool,
+        peek_on_ten: bool,
+        aces_hard_only: bool,
+        push_22: bool,
+        exact_rational: bool,
+        free_bet: bool,
+        double_exposure: bool,
+        soft17_cards: &str,
+        h17_by_upcard: Option<[bool; 10]>,
+    ) -> PyResult<Self> {
+        let custom_policy = dealer_policy.as_deref().map(policy_from_rows).transpose()?;
+        let double_rule = DoubleRule::parse(double_rule)?;
+        let mut sim = Self {
+            h17,
+            dp_depth: dp_depth.unwrap_or(3),
+            dp_depth_dbl: dp_depth_dbl.unwrap_or(4),
+            das: das_rule.map_or(Ok(DasRule::from_flag(das)), DasRule::parse)?,
+            split_aces_one,
+            double_split_aces,
+            double_rule,
+            post_split_double_rule: post_split_double_rule.map_or(Ok(double_rule), DoubleRule::parse)?,
+            surrender,
+            surrender_fraction,
+            max_player_cards: card_cap(max_player_cards)?,
+            charlie,
+            charlie_beats_bj,
+            csm,
+            obo,
+            enhc_refund_extra,
+            bj_payout: if double_exposure { 1.0 } else { bj_payout },
+            blackjack_bonus,
+            win_commission,
+            remove_upcard,
+            peek_on_ace,
+            peek_on_ten,
+            aces_hard_only,
+            push_22,
+            exact_rational,
+            free_bet,
+            double_exposure,
+            no_peek_full_refund,
+            custom_policy,
+            h17_by_upcard: None,
+            empty_shoe: EmptyShoe::parse(empty_shoe_policy)?,
+            soft17_cards: Soft17Cards::parse(soft17_cards)?,
+            settlement: settlement.unwrap_or_default(),
+            shoe: deck_array(&shoe_counts, "shoe_counts")?,
+            memo: Mutex::new(DealerMemo::default()),
+        };
+        sim.set_h17_by_upcard(h17_by_upcard);
+        Ok(sim)
+    }
+
+    /// Vegas Strip baseline: 6 decks, S17, 3:2, no DAS, one card to split
+    /// aces, no surrender, no resplit, US peek (pass HC_AUTO).
+    #[staticmethod]
+    fn vegas_strip_6deck() -> Self {
+        Self::preset(6, false, false)
+    }
+
+    /// Downtown Vegas: 2 decks, H17, 3:2, DAS, one card to split aces, US peek.
+    #[staticmethod]
+    fn downtown_vegas() -> Self {
+        Self::preset(2, true, true)
+    }
+
+    /// Atlantic City: 8 decks, S17, 3:2, DAS, one card to split aces, US peek.
+    /// (AC late surrender is not modelled here.)
+    #[staticmethod]
+    fn atlantic_city() -> Self {
+        Self::preset(8, false, true)
+    }
+
+    /// Single deck H17: 1 deck, H17, 3:2, no DAS, one card to split aces, US peek.
+    #[staticmethod]
+    fn single_deck_h17() -> Self {
+        Self::preset(1, true, false)
+    }
+
+    /// Free Bet Blackjack: 6 decks, H17, 3:2, DAS, free doubles on hard 9-11
+    /// and free splits (not tens), dealer 22 pushes, US peek.
+    #[staticmethod]
+    fn free_bet_blackjack() -> Self {
+        Self {
+            free_bet: true,
+            push_22: true,
+            ..Self::preset(6, true, true)
+        }
+    }
+
+    /// Effective dealer policy table (rows of [hard hits, soft hits], totals 0..21).
+    #[getter]
+    fn dealer_policy(&self) -> Vec<[bool; 2]> {
+        self.policy().hits.to_vec()
+    }
+
+    /// Install a custom dealer table, or `None` to fall back to `h17`.
+    #[setter]
+    fn set_dealer_policy(&mut self, rows: Option<Vec<[bool; 2]>>) -> PyResult<()> {
+        self.custom_policy = rows.as_deref().map(policy_from_rows).transpose()?;
+        Ok(())
+    }
+
+    /// Whether the dealer hits soft 17 against each upcard A,2..9,T.
+    #[getter]
+    fn h17_by_upcard(&self) -> [bool; 10] {
+        let policy = self.policy();
+        std::array::from_fn(|up| policy.for_up(up).hits(17, true))
+    }
+
+    /// Per-upcard soft-17 rule, or `None` for the table's own. Without a
+    /// custom table a uniform array just sets `h17`.
+    #[setter]
+    fn set_h17_by_upcard(&mut self, by_up: Option<[bool; 10]>) {
+        self.h17_by_upcard = match by_up {
+            Some(b) if self.custom_policy.is_none() && b.iter().all(|&x| x == b[0]) => {
+                self.h17 = b[0];
+                None
+            }
+            other => other,
+        };
+    }
+
+    /// The shoe the simulator was built with, as [A,2..9,T] counts. EV calls
+    /// never deplete it, so passing it back as `deck` is the "new shoe" state.
+    #[getter]
+    fn shoe(&self) -> Vec<Count> {
+        self.shoe.to_vec()
+    }
+
+    /// `shoe` as a method: the reference composition every `deck` must fit in.
+    fn initial_shoe(&self) -> Vec<Count> {
+        self.shoe.to_vec()
+    }
+
+    /// Whether split hands may double at all (any `das_rule` but "none").
+    #[getter]
+    fn das(&self) -> bool {
+        self.das != DasRule::None
+    }
+
+    #[setter]
+    fn set_das(&mut self, das: bool) {
+        self.das = DasRule::from_flag(das);
+    }
+
+    /// Most cards a player hand may hold (None for no cap); at the cap it must stand.
+    #[getter]
+    fn max_player_cards(&self) -> Option<usize> {
+        self.max_player_cards
+    }
+
+    #[setter]
+    fn set_max_player_cards(&mut self, cap: Option<usize>) -> PyResult<()> {
+        self.max_player_cards = card_cap(cap)?;
+        Ok(())
+    }
+
+    #[getter]
+    fn double_exposure(&self) -> bool {
+        self.double_exposure
+    }
+
+    /// Turning double exposure on also sets `bj_payout` to 1.0; turning it
+    /// off leaves the payout alone.
+    #[setter]
+    fn set_double_exposure(&mut self, on: bool) {
+        self.double_exposure = on;
+        if on {
+            self.bj_payout = 1.0;
+        }
+    }
+
+    /// Doubling after split: "none", "all", or "first_split_only" (hands of
+    /// a resplit may not double).
+    #[getter]
+    fn das_rule(&self) -> &'static str {
+        self.das.name()
+    }
+
+    #[setter]
+    fn set_das_rule(&mut self, name: &str) -> PyResult<()> {
+        self.das = DasRule::parse(name)?;
+        Ok(())
+    }
+
+    /// Totals the initial hand may double on: "any", "9-11" or "10-11".
+    #[getter]
+    fn double_rule(&self) -> &'static str {
+        self.double_rule.name()
+    }
+
+    #[setter]
+    fn set_double_rule(&mut self, name: &str) -> PyResult<()> {
+        self.double_rule = DoubleRule::parse(name)?;
+        Ok(())
+    }
+
+    /// Totals a split hand may double on (given `das`); defaults to `double_rule`.
+    #[getter]
+    fn post_split_double_rule(&self) -> &'static str {
+        self.post_split_double_rule.name()
+    }
+
+    #[setter]
+    fn set_post_split_double_rule(&mut self, name: &str) -> PyResult<()> {
+        self.post_split_double_rule = DoubleRule::parse(name)?;
+        Ok(())
+    }
+
+    /// Dealer forced to hit on an empty shoe: "clamp" stands on the current
+    /// total (raised to 17), "push" voids the round so every hand pushes.
+    #[getter]
+    fn empty_shoe_policy(&self) -> &'static str {
+        self.empty_shoe.name()
+    }
+
+    #[setter]
+    fn set_empty_shoe_policy(&mut self, name: &str) -> PyResult<()> {
+        self.empty_shoe = EmptyShoe::parse(name)?;
+        Ok(())
+    }
+
+    /// Card-count soft-17 rule: "any" (the dealer table decides), "hit_multi"
+    /// (stand on A,6, hit a soft 17 of three or more cards) or "hit_two".
+    #[getter]
+    fn soft17_cards(&self) -> &'static str {
+        self.soft17_cards.name()
+    }
+
+    #[setter]
+    fn set_soft17_cards(&mut self, name: &str) -> PyResult<()> {
+        self.soft17_cards = Soft17Cards::parse(name)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "BlackjackSimulator(h17={}, dp_depth={}, dp_depth_dbl={}, das_rule={:?}, split_aces_one={}, double_split_aces={}, double_rule={:?}, post_split_double_rule={:?}, surrender={}, surrender_fraction={:?}, max_player_cards={}, charlie={}, charlie_beats_bj={}, csm={}, obo={}, enhc_refund_extra={}, no_peek_full_refund={}, remove_upcard={}, peek_on_ace={}, peek_on_ten={}, aces_hard_only={}, push_22={}, exact_rational={}, free_bet={}, double_exposure={}, bj_payout={:?}, blackjack_bonus={:?}, win_commission={:?}, dealer_policy={}, h17_by_upcard={}, empty_shoe_policy={:?}, soft17_cards={:?}, settlement={}, shoe={:?})",
+            py_bool(self.h17),
+            self.dp_depth,
+            self.dp_depth_dbl,
+            self.das.name(),
+            py_bool(self.split_aces_one),
+            py_bool(self.double_split_aces),
+            self.double_rule.name(),
+            self.post_split_double_rule.name(),
+            py_bool(self.surrender),
+            self.surrender_fraction,
+            self.max_player_cards.map_or("None".to_string(), |m| m.to_string()),
+            py_bool(self.charlie),
+            py_bool(self.charlie_beats_bj),
+            py_bool(self.csm),
+            py_bool(self.obo),
+            py_bool(self.enhc_refund_extra),
+            py_bool(self.no_peek_full_refund),
+            py_bool(self.remove_upcard),
+            py_bool(self.peek_on_ace),
+            py_bool(self.peek_on_ten),
+            py_bool(self.aces_hard_only),
+            py_bool(self.push_22),
+            py_bool(self.exact_rational),
+            py_bool(self.free_bet),
+            py_bool(self.double_exposure),
+            self.bj_payout,
+            self.blackjack_bonus,
+            self.win_commission,
+            if self.custom_policy.is_some() { "<custom>" } else { "None" },
+            self.h17_by_upcard.map_or("None".to_string(), |b| format!("[{}]", b.map(py_bool).join(", "))),
+            self.empty_shoe.name(),
+            self.soft17_cards.name(),
+            self.settlement.__repr__(),
+            self.shoe,
+        )
+    }
+
+    /// Equal when every rule field matches; the memo is ignored.
+    fn __eq__(&self, other: PyRef<'_, Self>) -> bool {
+        self.same_rules(&other)
+    }
+
+    fn __hash__(&self) -> u64 {
+        self.fingerprint()
+    }
+
+    /// Fingerprint of the current rule set; the dealer memo is invalidated whenever it changes.
+    fn rules_fingerprint(&self) -> u64 {
+        self.fingerprint()
+    }
+
+    /// Stand EV (per-stake), conditional on US peek via hole_constraint. With
+    /// HC_NONE (ENHC) an A/T upcard's natural is settled as a loss first.
+    /// `natural=True` marks the initial two-card 21, paid at `bj_payout`; a
+    /// drawn 21 is an ordinary hand (it only pushes a dealer 21) and never
+    /// earns `bj_payout` or `blackjack_bonus`.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, _depth=None, *, natural=false))]
+    fn stand_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        _depth: Option<usize>,
+        natural: bool,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let _ = pt_soft; // softness is irrelevant once standing
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.initial_stand_in(pt_total, natural, up, &arr, hole_constraint, &mut memo.map))
+    }
+
+    /// Stand EV split by dealer final total [17,18,19,20,21,bust]: each entry is
+    /// `settle * prob`, with an unpeeked dealer natural counted under 21, so
+    /// the six values sum to `stand_ev` for a non-natural hand.
+    fn stand_ev_contributions(&self, pt_total: i32, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<[f64; 6]> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let mut memo = self.memo();
+        let (p_nat, dist) = self.natural_and_runout(up, &arr, hole_constraint, &policy, &mut memo.map);
+        if pt_total > 21 {
+            // The bust loses before the dealer plays, void runouts included.
+            let mut out = dist.map(|p| -p);
+            out[5] -= 1.0 - dist.iter().sum::<f64>();
+            return Ok(out);
+        }
+        Ok(settle_split_bins(pt_total, p_nat, &dist, &policy, self.nat_loss()))
+    }
+
+    /// Stand EV of every hard total 4..21 from one dealer runout: the
+    /// averaged distribution is computed once and each total is a cheap
+    /// settlement against it. Entries match `stand_ev` for non-naturals and
+    /// run from 4 up.
+    fn stand_ev_all_totals(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<BTreeMap<i32, f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let mut memo = self.memo();
+        let (p_nat, dist) = self.natural_and_runout(up, &arr, hole_constraint, &policy, &mut memo.map);
+        let nat_loss = self.nat_loss();
+        Ok((4..=21).map(|pt| (pt, settle_split(pt, p_nat, &dist, &policy, nat_loss))).collect())
+    }
+
+    /// Precompute every dealer runout reachable from `deck` (any upcard it can
+    /// supply) into a flat `DealerTable` for `stand_ev_table`. Worth it for
+    /// large sweeps over one fixed deck; the table is tied to the current rules.
+    fn build_dealer_table(&self, deck: Vec<Count>) -> PyResult<DealerTable> {
+        if self.soft17_cards != Soft17Cards::Any {
+            // Table states are drawn-card multisets without a card count.
+            return Err(PyValueError::new_err("build_dealer_table does not support a soft17_cards rule"));
+        }
+        let mut bases = [None; 10];
+        for (up, b) in bases.iter_mut().enumerate() {
+            *b = self.live_deck(&deck, up).ok();
+        }
+        Ok(DealerTable::build(self.fingerprint(), self.play_deck(&deck)?, bases, self.policy()))
+    }
+
+    /// `stand_ev` (non-natural hand) for the table's deck, read from `table`
+    /// without touching the hashed memo.
+    #[pyo3(signature = (pt_total, up, table, hole_constraint=HC_NONE))]
+    fn stand_ev_table(&self, pt_total: i32, up: usize, table: PyRef<'_, DealerTable>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        if table.rules != self.rules_fingerprint() {
+            return Err(PyValueError::new_err("dealer table was built under different rules"));
+        }
+        let base = table.bases.get(up).copied().flatten().ok_or_else(|| {
+            PyValueError::new_err(format!(
+                "deck holds no {} for the dealer upcard",
+                RANK_LABELS.get(up).unwrap_or(&"?")
+            ))
+        })?;
+        if pt_total > 21 {
+            return Ok(-1.0);
+        }
+        if base.iter().sum::<i32>() <= 0 {
+            let mut memo = Memo::new();
+            let mut empty = [0; 10];
+            let policy = self.policy().for_up(up);
+            let (t, s) = policy.add(0, false, up);
+            let dist = dealer_dist_from_total(&mut empty, t, s, 1, &policy, &mut memo);
+            return Ok(settle_dist(pt_total, &dist, &policy));
+        }
+        let (p_nat, dist) = table.split_natural(up, &base, &constraint_weights(hole_constraint));
+        Ok(settle_split(pt_total, p_nat, &dist, &table.policy, self.nat_loss()))
+    }
+
+    /// Deal one round from the simulator shoe with a seeded RNG and play it
+    /// out with the engine's best action at every decision (no resplits). The
+    /// same seed always deals the same cards.
+    fn simulate_round(&self, seed: u64) -> PyResult<RoundTrace> {
+        self.play_round(seed)
+    }
+
+    /// `stand_ev` plus recursion counters for this call: dealer nodes visited
+    /// and memo hits / misses (a warm simulator memo shows up as hits).
+    /// Only built with the `instrument` feature.
+    #[cfg(feature = "instrument")]
+    fn stand_ev_instrumented(
+        &self,
+        pt_total: i32,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<(f64, BTreeMap<String, u64>)> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        instrument::reset();
+        let ev = self.stand_in(pt_total, up, &arr, hole_constraint, &mut memo.map);
+        let (nodes, hits, misses) = instrument::snapshot();
+        let stats = [("nodes", nodes), ("memo_hits", hits), ("memo_misses", misses)];
+        Ok((ev, stats.into_iter().map(|(k, v)| (k.to_string(), v)).collect()))
+    }
+
+    /// Optimal stand/hit play of a hand that has already acted, so double and
+    /// split are excluded by design: `max(stand, hit)`, where hitting draws a
+    /// card and decides again, up to `depth` cards (default `dp_depth`).
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, depth=None))]
+    fn play_out_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        depth: Option<usize>,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let depth = self.draw_limit(depth.unwrap_or(self.dp_depth));
+        let mut memo = self.memo();
+        Ok(self.play_out_in(pt_total, pt_soft, 2, up, &arr, hole_constraint, self.nat_loss(), depth, &mut memo.map))
+    }
+
+    /// Buy-back promo: the better of playing on (`play_out_ev`) and cashing
+    /// out for `fraction` of the 2-unit pot, i.e. a net `2 * fraction - 1`.
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, fraction))]
+    fn cashout_ev(&self, pt_total: i32, pt_soft: bool, up: usize, deck: Vec<Count>, hole_constraint: i32, fraction: f64) -> PyResult<f64> {
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(PyValueError::new_err("fraction must be in [0, 1]"));
+        }
+        let play = self.play_out_ev(pt_total, pt_soft, up, deck, hole_constraint, None)?;
+        Ok(play.max(2.0 * fraction - 1.0))
+    }
+
+    /// Hit EV with the rest of the hand played optimally: draw one card, then
+    /// at every node choose stand or hit again from the new (total, soft)
+    /// state, drawing at most `depth` cards in all (default `dp_depth`). A node
+    /// whose deck has run dry stands where it is and settles against a dealer
+    /// facing the same empty shoe (`empty_shoe_policy`).
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, depth=None))]
+    fn hit_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        depth: Option<usize>,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let depth = self.draw_limit(depth.unwrap_or(self.dp_depth)).max(1);
+        let mut memo = self.memo();
+        Ok(self.hit_optimal_in(pt_total, pt_soft, 2, up, &arr, hole_constraint, self.nat_loss(), depth, &mut memo.map))
+    }
+
+    /// Best stand/hit EV for a player who refuses to hit any hand that could
+    /// bust: hard 12+ always stands, hard 11 or less and soft hands choose
+    /// the better of stand and hit. The gap to `play_out_ev` is the price of
+    /// the superstition.
+    fn never_bust_ev(&self, pt_total: i32, pt_soft: bool, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.never_bust_in(pt_total, pt_soft, 2, up, &arr, hole_constraint, self.nat_loss(), &mut memo.map))
+    }
+
+    /// Stand/hit EV of a bounded-lookahead policy on a two-card hand: each
+    /// decision looks `lookahead` cards ahead and plays the best line it
+    /// sees, re-deciding after every card. Set against `play_out_ev` on a
+    /// deep horizon this is what the short sight costs; with a long enough
+    /// `lookahead` the two agree. `lookahead=0` always stands.
+    fn lookahead_ev(&self, pt_total: i32, pt_soft: bool, up: usize, deck: Vec<Count>, hole_constraint: i32, lookahead: usize) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.lookahead_in(pt_total, pt_soft, 2, up, &arr, hole_constraint, self.nat_loss(), lookahead, &mut memo.map))
+    }
+
+    /// EV of a dealt natural, which never takes an action. Under a peek
+    /// constraint the dealer cannot hold a natural, giving `bj_payout`;
+    /// with HC_NONE it is `bj_payout * (1 - p_dealer_bj)`.
+    fn natural_ev(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        Ok(self.natural_in(up, &self.live_deck(&deck, up)?, hole_constraint, SUITED_NATURAL_PROB))
+    }
+
+    /// Best per-stake EV of the dealt hand `c0`, `c1` (already out of
+    /// `deck`). A natural is settled by `natural_ev`; anything else takes
+    /// the best of stand, hit, double, split and surrender, hitting on for
+    /// up to `dp_depth` cards.
+    fn initial_hand_ev(&self, c0: usize, c1: usize, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let policy = self.policy();
+        let (t, s) = hand_from_cards(&[c0, c1], &policy)?;
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        if policy.natural(c0, c1) {
+            return Ok(self.natural_in(up, &arr, hole_constraint, SUITED_NATURAL_PROB));
+        }
+        let pair = if c0 == c1 { Some(c0) } else { None };
+        let mut memo = self.memo();
+        let depth = self.draw_limit(self.dp_depth);
+        Ok(best_action(&self.deal_options(t, s, pair, up, &arr, hole_constraint, depth, &mut memo.map)).1)
+    }
+
+    /// One-card hit then stand (per-stake), conditional on US peek.
+    fn hit_then_stand_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        _depth: Option<usize>,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.hit_once_in(pt_total, pt_soft, up, &arr, hole_constraint, self.nat_loss(), &mut memo.map))
+    }
+
+    /// Double EV in units of the original bet: draw exactly one card, settle,
+    /// and scale by 2 for the doubled stake. Under ENHC
+    /// the player draws first, then a dealer natural takes the doubled stake
+    /// (only the original half with `obo`).
+    fn double_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        _depth_dbl: Option<usize>,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.double_in(pt_total, pt_soft, up, &arr, hole_constraint, &mut memo.map))
+    }
+
+    /// (win, push, loss) probabilities of the doubled hand, which settle at
+    /// +2, 0 and -2 units (less under `obo`, which also books a dealer natural
+    /// as a loss). The same one-card draw as `double_ev`; sums to 1.
+    fn double_outcome_probs(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<(f64, f64, f64)> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let nat = self.double_nat_loss();
+        let mut memo = self.memo();
+        let rem0: i32 = arr.iter().sum();
+        let mut out = [0.0; 3];
+        if rem0 <= 0 {
+            out = stood_outcomes(&arr, pt_total, up, hole_constraint, &policy, nat, &mut memo.map);
+        }
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            arr[r] -= 1;
+            let (t2, _) = policy.add(pt_total, pt_soft, r);
+            let o = stood_outcomes(&arr, t2, up, hole_constraint, &policy, nat, &mut memo.map);
+            for (acc, p) in out.iter_mut().zip(o) {
+                *acc += (c as f64) / (rem0 as f64) * p;
+            }
+            arr[r] += 1;
+        }
+        Ok((out[0], out[1], out[2]))
+    }
+
+    /// Double-down rescue: after seeing the double card the player may forfeit
+    /// half the doubled stake (-1 original bet) instead of settling 2 units.
+    /// The rule quoted here allows the rescue on any card, a busting one too.
+    fn double_rescue_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let nat = self.double_nat_loss();
+        let mut memo = self.memo();
+        let rem0: i32 = arr.iter().sum();
+        if rem0 <= 0 {
+            return Ok(self.double_in(pt_total, pt_soft, up, &arr, hole_constraint, &mut memo.map));
+        }
+        let mut ev = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p_r = (c as f64) / (rem0 as f64);
+            arr[r] -= 1;
+            let (t, _) = policy.add(pt_total, pt_soft, r);
+            let settled = 2.0 * settle_stood(&arr, t, up, hole_constraint, &policy, nat, &mut memo.map);
+            ev += p_r * settled.max(-1.0);
+            arr[r] += 1;
+        }
+        Ok(ev)
+    }
+
+    /// Double-or-hit hybrid (non-standard, no casino offers it): after the
+    /// double card the player keeps whichever is worth more, settling 2 units
+    /// or taking the double back and playing the hand out on 1 unit, drawing
+    /// at most `depth` cards in all (default `dp_depth`). For studying what
+    /// the commitment of a double costs; it is at least both `double_ev` and
+    /// `hit_ev` at the same depth.
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, depth=None))]
+    fn double_or_hit_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        depth: Option<usize>,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let depth = self.draw_limit(depth.unwrap_or(self.dp_depth));
+        let mut memo = self.memo();
+        let rem0: i32 = arr.iter().sum();
+        if rem0 <= 0 {
+            return Ok(self.double_in(pt_total, pt_soft, up, &arr, hole_constraint, &mut memo.map));
+        }
+        let mut ev = 0.0;
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p_r = (c as f64) / (rem0 as f64);
+            arr[r] -= 1;
+            let (t, s) = policy.add(pt_total, pt_soft, r);
+            let doubled = 2.0 * self.settle_hand(&arr, t, 3, up, hole_constraint, self.double_nat_loss(), &mut memo.map);
+            let hit = self.play_out_in(t, s, 3, up, &arr, hole_constraint, self.nat_loss(), depth.saturating_sub(1), &mut memo.map);
+            ev += p_r * doubled.max(hit);
+            arr[r] += 1;
+        }
+        Ok(ev)
+    }
+
+    /// Stand EV on a `num_decks` shoe shifted to a Hi-Lo true count
+    /// (see `shoe_at_true_count` for the linear composition model).
+    fn stand_ev_at_count(
+        &self,
+        pt_total: i32,
+        up: usize,
+        true_count: f64,
+        num_decks: usize,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.without_upcard(shoe_at_true_count(true_count, num_decks), up)?;
+        let mut memo = self.memo();
+        Ok(self.stand_in(pt_total, up, &arr, hole_constraint, &mut memo.map))
+    }
+
+    /// `stand_ev_at_count` through a shoe dealt down from `num_decks` to 1
+    /// deck remaining while the true count trends linearly from 0 to
+    /// `final_true_count`: one entry per whole deck left. Only the hand's
+    /// total matters, as for `stand_ev_at_count`.
+    fn ev_trajectory(&self, hand: Vec<usize>, up: usize, num_decks: usize, final_true_count: f64, hole_constraint: i32) -> PyResult<Vec<f64>> {
+        if num_decks < 2 {
+            return Err(PyValueError::new_err("num_decks must be at least 2 for a count trend"));
+        }
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let (total, _) = hand_from_cards(&hand, &self.policy())?;
+        let mut memo = self.memo();
+        (1..=num_decks)
+            .rev()
+            .map(|k| {
+                let tc = final_true_count * (num_decks - k) as f64 / (num_decks - 1) as f64;
+                let arr = self.without_upcard(shoe_at_true_count(tc, k), up)?;
+                Ok(self.stand_in(total, up, &arr, hole_constraint, &mut memo.map))
+            })
+            .collect()
+    }
+
+    /// Per-stake EV of the fixed policy "hit until the hand reaches at least
+    /// `target`, then stand" (e.g. target 17 mimics a dealer-style player).
+    #[allow(clippy::too_many_arguments)]
+    fn hit_to_total_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        target: i32,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(self.hit_to_total_in(&mut arr, pt_total, pt_soft, 2, target, up, hole_constraint, self.nat_loss(), &mut memo.map))
+    }
+
+    /// Round EV (per stake) for a player who plays exactly like the dealer:
+    /// both player cards are dealt from `deck`, then the player hits by the
+    /// dealer policy with no doubling or splitting. A natural pays `bj_payout`
+    /// (no `blackjack_bonus`) and pushes a dealer natural. Busts lose first,
+    /// which costs the mimic player about 5.5% on a 6-deck shoe.
+    fn mimic_dealer_ev(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let weights = constraint_weights(hole_constraint);
+        let nat = self.nat_loss();
+        let mut memo = self.memo();
+        let rem0: i32 = arr.iter().sum();
+        let mut ev = 0.0;
+        for a in 0..10 {
+            if arr[a] <= 0 {
+                continue;
+            }
+            let pa = (arr[a] as f64) / (rem0 as f64);
+            arr[a] -= 1;
+            let rem1 = rem0 - 1;
+            for b in 0..10 {
+                if arr[b] <= 0 {
+                    continue;
+                }
+                let pb = (arr[b] as f64) / (rem1 as f64);
+                arr[b] -= 1;
+                let (t0, s0) = policy.add(0, false, a);
+                let (t, s) = policy.add(t0, s0, b);
+                ev += pa
+                    * pb
+                    * if t == 21 {
+                        settle_natural(&arr, up, &weights, policy.pay(self.bj_payout), policy.settle21.natural_vs_natural)
+                    } else {
+                        self.mimic_dealer_in(&mut arr, t, s, 2, up, hole_constraint, nat, &mut memo.map)
+                    };
+                arr[b] += 1;
+            }
+            arr[a] += 1;
+        }
+        Ok(ev)
+    }
+
+    /// Round EV (per stake) against `up` for a player who follows `chart` at
+    /// every decision instead of playing optimally, both player cards dealt
+    /// from `deck`. Hits play on until the chart stands (21 always stands),
+    /// so set against `initial_deal_ev` this is what the chart's mistakes cost.
+    /// Rows the hands reach must be in the chart; split hands hit again as
+    /// the chart says but are never resplit.
+    fn ev_under_chart(&self, chart: &StrategyChart, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        if self.free_bet {
+            return Err(PyValueError::new_err("ev_under_chart does not model free_bet stakes"));
+        }
+        let hc = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy();
+        let mut memo = self.memo();
+        let n: i32 = arr.iter().sum();
+        if n < 3 {
+            return Err(PyValueError::new_err("ev_under_chart needs two player cards and a hole card"));
+        }
+        let mut ev = 0.0;
+        for a in 0..10 {
+            if arr[a] <= 0 {
+                continue;
+            }
+            let pa = (arr[a] as f64) / (n as f64);
+            arr[a] -= 1;
+            for b in 0..10 {
+                if arr[b] <= 0 {
+                    continue;
+                }
+                let pb = (arr[b] as f64) / ((n - 1) as f64);
+                arr[b] -= 1;
+                let hand = if policy.natural(a, b) {
+                    self.natural_in(up, &arr, HC_NONE, SUITED_NATURAL_PROB)
+                } else {
+                    let (t0, s0) = policy.add(0, false, a);
+                    let (t, s) = policy.add(t0, s0, b);
+                    let pair = if a == b { Some(a) } else { None };
+                    let played =
+                        self.chart_play_in(chart, t, s, 2, pair, 0, up, &arr, hc, (self.nat_loss(), self.double_nat_loss()), &mut memo.map)?;
+                    if hc == HC_NONE {
+                        played
+                    } else {
+                        let p_nat = if self.aces_hard_only { 0.0 } else { natural_prob(&arr, up) };
+                        (1.0 - p_nat) * played - p_nat
+                    }
+                };
+                ev += pa * pb * hand;
+                arr[b] += 1;
+            }
+            arr[a] += 1;
+        }
+        Ok(ev)
+    }
+
+    /// Split EV (per original stake): average of the two child hands’ per-stake EV.
+    /// `das` is a bool or a `das_rule` name, and `post_split_double_rule`
+    /// overrides the simulator's for this call. `deck` must still hold the
+    /// pair; both cards are removed before the split. A hand that draws
+    /// another pair card may resplit while fewer than `max_hands` hands are
+    /// out; split aces resplit only with `resplit_aces`.
+    ///
+    /// Each hand is priced on the deck left after the pair and any resplit
+    /// pair cards, not on what its siblings drew. That is exact for a single
+    /// split (the hands are exchangeable) but only approximate with resplits:
+    /// on a 30-card deck of tens and sixes, T,T vs 6 at four hands comes out
+    /// about 0.03 per hand low, and the gap shrinks as the deck grows.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pair_rank, up, deck, hole_constraint, das, split_aces_one, _depth_split=None, *, post_split_double_rule=None, max_hands=2, resplit_aces=false))]
+    fn split_ev(
+        &self,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        das: DasArg,
+        split_aces_one: bool,
+        _depth_split: Option<usize>,
+        post_split_double_rule: Option<&str>,
+        max_hands: usize,
+        resplit_aces: bool,
+    ) -> PyResult<f64> {
+        if max_hands < 2 {
+            return Err(PyValueError::new_err(format!("max_hands must be at least 2, got {}", max_hands)));
+        }
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = without_pair(self.live_deck(&deck, up)?, pair_rank)?;
+        let post_split = post_split_double_rule.map_or(Ok(self.post_split_double_rule), DoubleRule::parse)?;
+        let mut memo = self.memo();
+        let limits = (max_hands, resplit_aces);
+        Ok(self.resplit_in(pair_rank, up, &arr, hole_constraint, das.rule()?, post_split, split_aces_one, limits, &mut memo.map))
+    }
+
+    /// `split_ev` broken down by the card each child draws to its pair card:
+    /// `{"ev", "branches"}`, each branch `{"card", "prob", "action", "ev"}`
+    /// with the child's best play (plus `"free_ev"` for a Free Bet split).
+    /// The root `ev` is the branches' weighted sum, i.e. `split_ev`. When
+    /// `max_hands` and `resplit_aces` allow resplits (as in `split_ev`), the
+    /// tree follows the hands in the order `split_ev` plays them instead:
+    /// every node is a `split_node` over the hands still waiting for a card,
+    /// pair-card branches carry their `"resplit"` subtree, and the root `ev`
+    /// is half the root node's sum, again `split_ev`.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pair_rank, up, deck, hole_constraint, das, split_aces_one, *, post_split_double_rule=None, max_hands=2, resplit_aces=false))]
+    fn split_tree<'py>(
+        &self,
+        py: Python<'py>,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        das: DasArg,
+        split_aces_one: bool,
+        post_split_double_rule: Option<&str>,
+        max_hands: usize,
+        resplit_aces: bool,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        if max_hands < 2 {
+            return Err(PyValueError::new_err(format!("max_hands must be at least 2, got {}", max_hands)));
+        }
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = without_pair(self.live_deck(&deck, up)?, pair_rank)?;
+        let post_split = post_split_double_rule.map_or(Ok(self.post_split_double_rule), DoubleRule::parse)?;
+        let das = das.rule()?;
+        let mut memo = self.memo();
+        let memo = &mut memo.map;
+        if self.resplits(pair_rank, split_aces_one, (max_hands, resplit_aces)) {
+            let rules = (das, post_split, split_aces_one, max_hands);
+            let mut states = HashMap::new();
+            let out = self.split_node(py, pair_rank, up, &arr, hole_constraint, rules, (2, 0, 2), &mut states, memo)?;
+            out.set_item("ev", states[&(2, 0, 2)] / 2.0)?;
+            return Ok(out);
+        }
+        let rem: i32 = arr.iter().sum();
+        let free_split = self.free_bet && pair_rank != 9;
+        let (mut total, mut free_total) = (0.0, 0.0);
+        let mut branches = Vec::new();
+        for r in 0..10 {
+            let c = arr[r];
+            if c <= 0 {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[r] -= 1;
+            let (action, ev) = self.split_child_in(pair_rank, r, up, &arr, hole_constraint, das, post_split, split_aces_one, memo);
+            let branch = PyDict::new_bound(py);
+            branch.set_item("card", r)?;
+            branch.set_item("prob", p)?;
+            branch.set_item("action", action)?;
+            branch.set_item("ev", ev)?;
+            if free_split {
+                let free = self.free_split_child_in(pair_rank, r, up, &arr, hole_constraint, das, post_split, split_aces_one, memo);
+                branch.set_item("free_ev", free)?;
+                free_total += p * free;
+            }
+            total += p * ev;
+            branches.push(branch);
+            arr[r] += 1;
+        }
+        let out = PyDict::new_bound(py);
+        out.set_item("ev", if free_split { (total + free_total) / 2.0 } else { total })?;
+        out.set_item("branches", branches)?;
+        Ok(out)
+    }
+
+    /// `split_ev` for every pair rank A..T in one call, in rank order. Each
+    /// pair's two cards come out of `deck` in turn; a rank the deck cannot
+    /// pair is NaN. All ten share the dealer memo.
+    fn split_ev_all_pairs(
+        &self,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        das: DasArg,
+        split_aces_one: bool,
+    ) -> PyResult<Vec<f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let das = das.rule()?;
+        let mut memo = self.memo();
+        Ok((0..10)
+            .map(|pair| match without_pair(arr, pair) {
+                Ok(rest) => self.split_in(pair, up, &rest, hole_constraint, das, self.post_split_double_rule, split_aces_one, &mut memo.map),
+                Err(_) => f64::NAN,
+            })
+            .collect())
+    }
+
+    /// Split EV for any two ten-valued cards (T,T or mixed J/Q/K), which the
+    /// engine buckets together at index 9, under the simulator's own split
+    /// rules. As with `split_ev`, `deck` must still contain the two tens.
+    fn split_tens_ev(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = without_pair(self.live_deck(&deck, up)?, 9)?;
+        let mut memo = self.memo();
+        Ok(self.split_in(9, up, &arr, hole_constraint, self.das, self.post_split_double_rule, self.split_aces_one, &mut memo.map))
+    }
+
+    /// What splitting is worth on a pair: `split_ev` under the simulator's own
+    /// split rules minus the best non-split play of the same two cards
+    /// (`optimal_action` with `is_pair=False`). Both are per stake and run on `deck` minus the pair, which it
+    /// must still hold; positive means split.
+    fn split_value(&self, pair_rank: usize, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = without_pair(self.live_deck(&deck, up)?, pair_rank)?;
+        let (total, soft) = hand_from_cards(&[pair_rank, pair_rank], &self.policy())?;
+        let mut memo = self.memo();
+        let split = self.split_in(pair_rank, up, &arr, hole_constraint, self.das, self.post_split_double_rule, self.split_aces_one, &mut memo.map);
+        let rest = best_action(&self.action_evs(total, soft, None, up, &arr, hole_constraint, self.surrender, &mut memo.map)).1;
+        Ok(split - rest)
+    }
+
+    /// Stand EV with a soft prior on the hole card: `hole_weights[i]` scales
+    /// the deck's draw probability of hole rank i before renormalizing. Uniform
+    /// weights match `stand_ev` with HC_NONE; zeroing the natural hole matches
+    /// the corresponding HC_* constraint.
+    fn stand_ev_weighted_hole(
+        &self,
+        pt_total: i32,
+        up: usize,
+        deck: Vec<Count>,
+        hole_weights: Vec<f64>,
+    ) -> PyResult<f64> {
+        if hole_weights.len() != 10 || hole_weights.iter().any(|w| w.is_nan() || *w < 0.0) {
+            return Err(PyValueError::new_err(
+                "hole_weights must hold 10 non-negative weights ordered A,2..9,T",
+            ));
+        }
+        let mut w = [0.0; 10];
+        w.copy_from_slice(&hole_weights);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(settle_stood_weighted(&arr, pt_total, up, &w, &self.policy(), self.nat_loss(), &mut memo.map))
+    }
+
+    /// Stand EV with an explicit hole-card distribution: `hole_probs[i]` is
+    /// the belief that the hole is rank i, renormalized, in place of the
+    /// deck's draw odds (`stand_ev_weighted_hole` scales those instead). The
+    /// deck's own odds give `stand_ev` with HC_NONE; a one-hot vector gives
+    /// `stand_ev_known_hole`.
+    fn stand_ev_hole_probs(&self, pt_total: i32, up: usize, deck: Vec<Count>, hole_probs: Vec<f64>) -> PyResult<f64> {
+        if hole_probs.len() != 10 || hole_probs.iter().any(|q| q.is_nan() || *q < 0.0) || hole_probs.iter().sum::<f64>() <= 0.0 {
+            return Err(PyValueError::new_err(
+                "hole_probs must hold 10 non-negative probabilities ordered A,2..9,T, not all zero",
+            ));
+        }
+        let arr = self.live_deck(&deck, up)?;
+        let mut w = [0.0; 10];
+        for r in 0..10 {
+            if hole_probs[r] > 0.0 {
+                if arr[r] <= 0 {
+                    return Err(PyValueError::new_err(format!("hole_probs gives weight to a {} the deck cannot supply", RANK_LABELS[r])));
+                }
+                // Undo the deck's draw odds so the weighted draw is hole_probs.
+                w[r] = hole_probs[r] / (arr[r] as f64);
+            }
+        }
+        let mut memo = self.memo();
+        Ok(settle_stood_weighted(&arr, pt_total, up, &w, &self.policy(), self.nat_loss(), &mut memo.map))
+    }
+
+    /// Stand EV under an imperfect peek: `revealed_ranks` are the hole ranks
+    /// the peek device catches, excluded like an HC_* constraint, while a
+    /// dealer natural it misses stays in play and costs `nat_loss`. `[9]`
+    /// under an ace is HC_NOT_TEN and `[0]` under a ten is HC_NOT_ACE.
+    fn stand_ev_partial_peek(&self, pt_total: i32, up: usize, deck: Vec<Count>, revealed_ranks: Vec<usize>) -> PyResult<f64> {
+        let w = exclusion_weights(&revealed_ranks, "revealed")?;
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(settle_stood_weighted(&arr, pt_total, up, &w, &self.policy(), self.nat_loss(), &mut memo.map))
+    }
+
+    /// Stand EV given the hole is known not to be any rank in `excluded`:
+    /// the general peek, e.g. a faulty one that shows every ten and ace (`[0,
+    /// 9]`) under any upcard. `[]` is HC_NONE, `[9]` under an ace HC_NOT_TEN,
+    /// `[0]` under a ten HC_NOT_ACE. Errors if no hole card the deck holds
+    /// is left.
+    fn stand_ev_exclude_holes(&self, pt_total: i32, up: usize, deck: Vec<Count>, excluded: Vec<usize>) -> PyResult<f64> {
+        let w = exclusion_weights(&excluded, "excluded")?;
+        let arr = self.live_deck(&deck, up)?;
+        if arr.iter().sum::<i32>() > 0 && (0..10).all(|h| arr[h] <= 0 || w[h] == 0.0) {
+            return Err(PyValueError::new_err("excluded holes leave no hole card the deck can deal"));
+        }
+        let mut memo = self.memo();
+        Ok(settle_stood_weighted(&arr, pt_total, up, &w, &self.policy(), self.nat_loss(), &mut memo.map))
+    }
+
+    /// Stand EV with `num_other_cards` more cards gone to other players
+    /// unseen: the exact average over every blind removal, each weighted by
+    /// its probability and the chance the hole constraint still holds after
+    /// it. Cards drawn blind do not change the odds of the cards after them,
+    /// so this equals `stand_ev` unless the shoe can run short before the
+    /// dealer finishes; it enumerates C(k+9, 9) removals, so keep k small.
+    fn stand_ev_other_players(&self, pt_total: i32, up: usize, deck: Vec<Count>, num_other_cards: usize, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let rem: i32 = arr.iter().map(|&c| c.max(0)).sum();
+        if num_other_cards as i32 > rem {
+            return Err(PyValueError::new_err(format!("{} other cards requested but only {} are left", num_other_cards, rem)));
+        }
+        let weights = constraint_weights(hole_constraint);
+        let policy = self.policy();
+        let mut memo = self.memo();
+        let (mut ev, mut mass) = (0.0, 0.0);
+        for_each_removal(&arr, num_other_cards as i32, &mut |rest, p| {
+            let n: i32 = rest.iter().sum();
+            let allowed = if n <= 0 {
+                1.0
+            } else {
+                (0..10).map(|h| weights[h] * (rest[h] as f64)).sum::<f64>() / (n as f64)
+            };
+            if allowed > 0.0 {
+                ev += p * allowed * settle_stood(rest, pt_total, up, hole_constraint, &policy, self.nat_loss(), &mut memo.map);
+                mass += p * allowed;
+            }
+        });
+        if mass <= 0.0 {
+            return Err(PyValueError::new_err("no removal leaves a hole card the constraint allows"));
+        }
+        Ok(ev / mass)
+    }
+
+    /// Stand EV against a precomputed six-bin dealer distribution (e.g. a
+    /// `dealer_table` row), so one runout can serve many player totals.
+    fn ev_against_distribution(&self, pt_total: i32, dealer_dist: Vec<f64>) -> PyResult<f64> {
+        Ok(settle_dist(pt_total, &dist_array(&dealer_dist)?, &self.policy()))
+    }
+
+    /// One-card hit then stand against a fixed dealer distribution: the card
+    /// comes from `deck` as given (take the upcard out first), and every
+    /// drawn total settles against the same `dealer_dist`, so one runout
+    /// serves many hands. A bust loses outright. The true runout shifts a
+    /// little with the card drawn, so against one averaged row this is close
+    /// on a deep shoe rather than exact; a one-rank `deck` with the runout
+    /// after that card reproduces `hit_then_stand_ev` term by term.
+    fn hit_once_against_distribution(&self, pt_total: i32, pt_soft: bool, deck: Vec<Count>, dealer_dist: Vec<f64>) -> PyResult<f64> {
+        let dist = dist_array(&dealer_dist)?;
+        let arr = self.play_deck(&deck)?;
+        let policy = self.policy();
+        let rem: i32 = arr.iter().sum();
+        if rem <= 0 {
+            return Ok(settle_dist(pt_total, &dist, &policy));
+        }
+        Ok((0..10)
+            .filter(|&r| arr[r] > 0)
+            .map(|r| {
+                let (t, _) = policy.add(pt_total, pt_soft, r);
+                let ev = if t > 21 { -1.0 } else { settle_dist(t, &dist, &policy) };
+                (arr[r] as f64) / (rem as f64) * ev
+            })
+            .sum())
+    }
+
+    /// Effect of removal: change in stand EV when one card of each rank is taken
+    /// out of the deck (0 for ranks already exhausted). All eleven runouts share
+    /// the simulator memo.
+    fn effect_of_removal(&self, pt_total: i32, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<Vec<f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        let base = self.stand_in(pt_total, up, &arr, hole_constraint, &mut memo.map);
+        let mut out = vec![0.0; 10];
+        for (r, o) in out.iter_mut().enumerate() {
+            if arr[r] <= 0 {
+                continue;
+            }
+            arr[r] -= 1;
+            *o = self.stand_in(pt_total, up, &arr, hole_constraint, &mut memo.map) - base;
+            arr[r] += 1;
+        }
+        Ok(out)
+    }
+
+    /// Canonical `(total, soft)` of a hand given as rank indices, the form
+    /// every `pt_total, pt_soft` argument expects: A,7,5 is a hard 13 and
+    /// A,A a soft 12. Follows the simulator's ace rule.
+    fn normalize_hand(&self, cards: Vec<usize>) -> PyResult<(i32, bool)> {
+        hand_from_cards(&cards, &self.policy())
+    }
+
+    /// Stand EV for a hand given by its exact cards (rank indices), which are
+    /// removed from `deck` before the runout.
+    fn stand_ev_from_cards(&self, cards: Vec<usize>, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let (total, _) = hand_from_cards(&cards, &self.policy())?;
+        let arr = remove_cards(self.live_deck(&deck, up)?, &cards)?;
+        let mut memo = self.memo();
+        Ok(self.stand_in(total, up, &arr, hole_constraint, &mut memo.map))
+    }
+
+    /// `stand_ev_from_cards` on a neutral `standard_shoe` of each whole
+    /// number of decks remaining, from `num_decks` down to 1. With the count
+    /// held neutral only the hand's own cards weigh more as the shoe shrinks,
+    /// so the EV drifts little with depth alone.
+    fn ev_by_decks_remaining(&self, hand: Vec<usize>, up: usize, num_decks: usize, hole_constraint: i32) -> PyResult<Vec<f64>> {
+        if num_decks == 0 {
+            return Err(PyValueError::new_err("num_decks must be at least 1"));
+        }
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let (total, _) = hand_from_cards(&hand, &self.policy())?;
+        let mut memo = self.memo();
+        (1..=num_decks)
+            .rev()
+            .map(|k| {
+                let arr = remove_cards(self.without_upcard(self.play_shoe(standard_shoe(k)), up)?, &hand)?;
+                Ok(self.stand_in(total, up, &arr, hole_constraint, &mut memo.map))
+            })
+            .collect()
+    }
+
+    /// Fewest decks (1..=`max_decks`, on a neutral `standard_shoe`) at which
+    /// `action` is the best play for a hard `pt_total` against `up`, or None.
+    /// Each action's EV is averaged over the ace-free two-card hands making
+    /// the total, weighted by how likely each is dealt and with its cards
+    /// removed, and hands play as in `initial_deal_ev` (hits draw on, up to
+    /// `dp_depth` cards).
+    #[pyo3(signature = (pt_total, up, action, max_decks, hole_constraint=HC_AUTO))]
+    fn min_deck_for_decision(&self, pt_total: i32, up: usize, action: &str, max_decks: usize, hole_constraint: i32) -> PyResult<Option<usize>> {
+        if !(5..=20).contains(&pt_total) {
+            return Err(PyValueError::new_err(format!("no two-card hard total {} without an ace (5-20)", pt_total)));
+        }
+        if !["stand", "hit", "double", "surrender"].contains(&action) {
+            return Err(PyValueError::new_err(format!("unknown action {:?}; expected stand, hit, double or surrender", action)));
+        }
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let depth = self.draw_limit(self.dp_depth);
+        let mut memo = self.memo();
+        for k in 1..=max_decks {
+            let arr = self.without_upcard(self.play_shoe(standard_shoe(k)), up)?;
+            let n: i32 = arr.iter().sum();
+            let mut evs: Vec<(&'static str, f64)> = Vec::new();
+            let mut total_w = 0.0;
+            for a in 1..10 {
+                let v = pt_total - rank_val(a);
+                if !(2..=10).contains(&v) || ((v - 1) as usize) < a {
+                    continue;
+                }
+                let b = (v - 1) as usize;
+                let Ok(rest) = remove_cards(arr, &[a, b]) else { continue };
+                let ways = if a == b { (arr[a] * (arr[a] - 1)) as f64 } else { (2 * arr[a] * arr[b]) as f64 };
+                let w = ways / (n as f64 * (n - 1) as f64);
+                let options = self.deal_options(pt_total, false, None, up, &rest, hole_constraint, depth, &mut memo.map);
+                for (name, ev) in options {
+                    match evs.iter_mut().find(|e| e.0 == name) {
+                        Some(e) => e.1 += w * ev,
+                        None => evs.push((name, w * ev)),
+                    }
+                }
+                total_w += w;
+            }
+            if total_w > 0.0 && best_action(&evs).0 == action {
+                return Ok(Some(k));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Composition-dependent hit/stand check (e.g. T+6 vs 9+7 against a ten).
+    /// The total-only model plays the hand's total on `deck` as given; the
+    /// composition model first removes the player's cards. Hit plays on as
+    /// in `all_action_evs`; ties keep stand.
+    fn composition_decision(
+        &self,
+        cards: Vec<usize>,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<CompositionDecision> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let (total, soft) = hand_from_cards(&cards, &self.policy())?;
+        let base = self.live_deck(&deck, up)?;
+        let exact = remove_cards(base, &cards)?;
+        let nat = self.nat_loss();
+        let mut memo = self.memo();
+        let mut decide = |arr: &[Count; 10]| {
+            let st = self.stand_in(total, up, arr, hole_constraint, &mut memo.map);
+            let hi = self.hit_in(total, soft, cards.len(), up, arr, hole_constraint, nat, &mut memo.map);
+            (st, hi, best_action(&[("stand", st), ("hit", hi)]).0.to_string())
+        };
+        let (total_stand_ev, total_hit_ev, total_action) = decide(&base);
+        let (composition_stand_ev, composition_hit_ev, composition_action) = decide(&exact);
+        Ok(CompositionDecision {
+            total,
+            soft,
+            total_stand_ev,
+            total_hit_ev,
+            differs: total_action != composition_action,
+            total_action,
+            composition_stand_ev,
+            composition_hit_ev,
+            composition_action,
+        })
+    }
+
+    /// Round-level EV a total-only hit/stand player leaves on the table by
+    /// ignoring composition: every hand of up to `max_cards` cards that the
+    /// total-only strategy reaches, weighted by its probability (upcard
+    /// included), adds the gap between the better action on its exact cards
+    /// and the total-only action. Hit plays on as in `composition_decision`;
+    /// doubles and splits are left out.
+    #[pyo3(signature = (deck, hole_constraint, max_cards=5))]
+    fn composition_gain(&self, deck: Vec<Count>, hole_constraint: i32, max_cards: usize) -> PyResult<f64> {
+        let shoe = self.play_deck(&deck)?;
+        let rem: i32 = shoe.iter().sum();
+        let mut memo = self.memo();
+        let mut gain = 0.0;
+        for (up, &n_up) in shoe.iter().enumerate() {
+            if n_up <= 0 {
+                continue;
+            }
+            let hc = self.hole_for(up, hole_constraint);
+            let base = self.live_deck(&deck, up)?;
+            let mut exact = base;
+            let mut by_total = HashMap::new();
+            let p_up = (n_up as f64) / (rem as f64);
+            gain += self.composition_gain_in((0, false, 0), p_up, up, &base, &mut exact, hc, max_cards, &mut by_total, &mut memo.map);
+        }
+        Ok(gain)
+    }
+
+    /// Tens removed from `base_deck` at which the hit/stand decision on a hard
+    /// `pt_total` first flips from its full-deck choice, sweeping 0..=all of
+    /// them; None if it never flips. Hit plays on as in `all_action_evs`, and
+    /// the hole follows the simulator's peek rules.
+    fn hit_stand_boundary(&self, pt_total: i32, up: usize, base_deck: Vec<Count>) -> PyResult<Option<i32>> {
+        let hole_constraint = self.hole_for(up, HC_AUTO);
+        let mut arr = self.checked_deck(&base_deck)?;
+        let nat = self.nat_loss();
+        let mut memo = self.memo();
+        let mut first = None;
+        for removed in 0..=arr[9].max(0) {
+            let live = self.live_deck(&arr, up)?;
+            let st = self.stand_in(pt_total, up, &live, hole_constraint, &mut memo.map);
+            let hi = self.hit_in(pt_total, false, 2, up, &live, hole_constraint, nat, &mut memo.map);
+            let action = best_action(&[("stand", st), ("hit", hi)]).0;
+            match first {
+                None => first = Some(action),
+                Some(a) if a != action => return Ok(Some(removed)),
+                _ => {}
+            }
+            arr[9] -= 1;
+        }
+        Ok(None)
+    }
+
+    /// Stand EV when the dealer's cards so far are known (rank indices, upcard
+    /// first): the runout starts from them, with no hole averaging.
+    fn stand_ev_dealer_cards(&self, pt_total: i32, dealer_cards: Vec<usize>, deck: Vec<Count>) -> PyResult<f64> {
+        self.stand_vs_known(pt_total, &dealer_cards, &deck)
+    }
+
+    /// Stand EV with the dealer's next cards scripted (rank indices, hole card
+    /// first), for what-if scenarios rather than probabilistic EV. Each forced
+    /// card leaves `deck` as it is drawn; once the sequence runs out a dealer
+    /// still drawing runs out from what is left. An empty sequence is the
+    /// ordinary runout, with no peek (HC_NONE). Errors if the dealer would
+    /// stand before using every forced card.
+    fn stand_ev_fixed_next(&self, pt_total: i32, up: usize, deck: Vec<Count>, dealer_draw_sequence: Vec<usize>) -> PyResult<f64> {
+        let mut arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        if dealer_draw_sequence.is_empty() {
+            return Ok(self.stand_in(pt_total, up, &arr, HC_NONE, &mut memo.map));
+        }
+        let policy = self.policy().for_up(up);
+        let (mut t, mut s) = policy.add(0, false, up);
+        let mut cards: u8 = 1;
+        for (i, &r) in dealer_draw_sequence.iter().enumerate() {
+            if r >= 10 {
+                return Err(PyValueError::new_err(format!("card rank index {} out of range 0..9", r)));
+            }
+            if t > 21 || (cards >= 2 && !policy.hits_with(t, s, cards)) {
+                return Err(PyValueError::new_err(format!(
+                    "dealer stands on {} after {} forced card(s); {} left unused",
+                    t,
+                    i,
+                    dealer_draw_sequence.len() - i
+                )));
+            }
+            if arr[r] <= 0 {
+                return Err(PyValueError::new_err(format!("deck holds no {} for the dealer to draw", RANK_LABELS[r])));
+            }
+            arr[r] -= 1;
+            (t, s) = policy.add(t, s, r);
+            cards = cards.saturating_add(1);
+        }
+        if pt_total > 21 {
+            return Ok(-1.0);
+        }
+        if cards == 2 && policy.natural(up, dealer_draw_sequence[0]) {
+            return Ok(self.nat_loss());
+        }
+        let dist = dealer_dist_from_total(&mut arr, t, s, cards, &policy, &mut memo.map);
+        Ok(settle_dist(pt_total, &dist, &policy))
+    }
+
+    /// Stand EV with the hole card known (hole-carding, dealer error).
+    fn stand_ev_known_hole(&self, pt_total: i32, up: usize, hole: usize, deck: Vec<Count>) -> PyResult<f64> {
+        self.stand_vs_known(pt_total, &[up, hole], &deck)
+    }
+
+    /// Stand EV against a dealer who hits, soft or hard, until reaching at
+    /// least `dealer_target` (17..=22; e.g. `pt_total + 1` for "dealer hits
+    /// until beating the player"). Target 17 is ordinary S17 play. The custom
+    /// policy runs on its own memo, leaving the simulator cache untouched.
+    #[pyo3(signature = (pt_total, up, deck, dealer_target, hole_constraint=HC_NONE))]
+    fn stand_ev_dynamic_dealer(
+        &self,
+        pt_total: i32,
+        up: usize,
+        deck: Vec<Count>,
+        dealer_target: i32,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        if !(17..=22).contains(&dealer_target) {
+            return Err(PyValueError::new_err("dealer_target must be in 17..=22"));
+        }
+        let mut hits = [[false; 2]; 22];
+        for (t, row) in hits.iter_mut().enumerate() {
+            *row = [(t as i32) < dealer_target; 2];
+        }
+        let policy = DealerPolicy {
+            hits,
+            h17_by_upcard: None,
+            soft17_cards: Soft17Cards::Any,
+            ..self.policy()
+        };
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = Memo::new();
+        Ok(settle_stood(&arr, pt_total, up, hole_constraint, &policy, self.nat_loss(), &mut memo))
+    }
+
+    /// Stand EV after each card of `cards` (rank indices) is dealt from the
+    /// simulator shoe. Once the dealt fraction reaches `penetration` the cut
+    /// card comes out: that entry is NaN and the shoe resets to full.
+    ///
+    /// `cut_card_effect` (experimental) scales each live entry by the relative
+    /// number of hands expected at that depth, so the entries' mean is the
+    /// hand-weighted EV a player actually realizes. Hands per card are taken
+    /// as proportional to the mean card value left (high cards end hands
+    /// sooner) and the weights are normalized to average 1. This is only an
+    /// approximation of the cut-card effect in Griffin, *The Theory of
+    /// Blackjack*; the correction is on the order of 0.02%.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, up, cards, hole_constraint, penetration=1.0, cut_card_effect=false))]
+    fn sweep_penetration(
+        &self,
+        pt_total: i32,
+        up: usize,
+        cards: Vec<usize>,
+        hole_constraint: i32,
+        penetration: f64,
+        cut_card_effect: bool,
+    ) -> PyResult<Vec<f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        if !(penetration > 0.0 && penetration <= 1.0) {
+            return Err(PyValueError::new_err("penetration must be in (0, 1]"));
+        }
+        let full: i32 = self.shoe.iter().sum();
+        let mut arr = self.shoe;
+        let mut memo = self.memo();
+        let mut out = Vec::with_capacity(cards.len());
+        let mut weights = Vec::with_capacity(cards.len());
+        for r in cards {
+            if r >= 10 {
+                return Err(PyValueError::new_err(format!("card rank index {} out of range 0..9", r)));
+            }
+            if arr[r] <= 0 {
+                return Err(PyValueError::new_err(format!("no {} left to deal", RANK_LABELS[r])));
+            }
+            arr[r] -= 1;
+            let dealt = full - arr.iter().sum::<i32>();
+            if (dealt as f64) >= penetration * (full as f64) {
+                out.push(f64::NAN);
+                weights.push(0.0);
+                arr = self.shoe;
+                continue;
+            }
+            let deck = self.live_deck(&arr, up)?;
+            out.push(self.stand_in(pt_total, up, &deck, hole_constraint, &mut memo.map));
+            weights.push(mean_card_value(&arr));
+        }
+        if cut_card_effect {
+            let live = weights.iter().filter(|w| **w > 0.0).count();
+            let total: f64 = weights.iter().sum();
+            if total > 0.0 {
+                for (ev, w) in out.iter_mut().zip(weights) {
+                    *ev *= w * (live as f64) / total;
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Probability the dealer holds a natural given the upcard and the deck
+    /// the hole is drawn from. This is the mass US peek removes: `1 - p` is the
+    /// allowed-hole weight `stand_ev` renormalizes by.
+    fn dealer_natural_prob(&self, up: usize, deck: Vec<Count>) -> PyResult<f64> {
+        Ok(natural_prob(&self.live_deck(&deck, up)?, up))
+    }
+
+    /// EV contribution of the dealer-blackjack branch that peeked EVs
+    /// renormalize away: `-p_bj` for an ordinary hand, 0 for a natural (push).
+    /// The unconditional EV is `conditional_ev * (1 - p_bj) + dealer_bj_loss`.
+    fn dealer_bj_loss(&self, pt_total: i32, pt_is_natural: bool, up: usize, deck: Vec<Count>) -> PyResult<f64> {
+        let _ = pt_total; // every non-natural total loses the same unit
+        let p = natural_prob(&self.live_deck(&deck, up)?, up);
+        Ok(if pt_is_natural { 0.0 } else { -p })
+    }
+
+    /// Fraction of the remaining shoe held by each rank bucket [A,2..9,T].
+    /// The T bucket covers all four ten-valued ranks, so it sits near 4/13 on a fresh shoe.
+    fn composition_percentages(&self, deck: Vec<Count>) -> PyResult<Vec<f64>> {
+        let arr = self.checked_deck(&deck)?;
+        let rem: i32 = arr.iter().sum();
+        if rem <= 0 {
+            return Ok(vec![0.0; 10]);
+        }
+        Ok(arr.iter().map(|c| (*c as f64) / (rem as f64)).collect())
+    }
+
+    /// Remaining cards expressed in 52-card decks.
+    fn decks_remaining(&self, deck: Vec<Count>) -> PyResult<f64> {
+        let rem: i32 = self.checked_deck(&deck)?.iter().sum();
+        Ok((rem.max(0) as f64) / 52.0)
+    }
+
+    /// EV of every legal two-card action ("stand", "hit", "double" when
+    /// `double_rule` allows the total, "split" when `is_pair`, and "surrender"
+    /// under the `surrender` rule). Split uses the simulator's `das`,
+    /// `post_split_double_rule` and `split_aces_one` rules.
+    /// `round_to` rounds the reported EVs to that many decimals. Actions are
+    /// keyed in sorted order, so the dict is the same on every run.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, is_pair, pair_rank, up, deck, hole_constraint, round_to=None))]
+    fn all_action_evs(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
         hole_constraint: i32,
-        _depth: Option<usize>,
+        round_to: Option<u32>,
+    ) -> PyResult<BTreeMap<String, f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let mut memo = self.memo();
+        let mut evs = self.action_evs(pt_total, pt_soft, pair, up, &arr, hole_constraint, self.surrender, &mut memo.map);
+        round_evs(&mut evs, round_to);
+        Ok(evs.into_iter().map(|(a, ev)| (a.to_string(), ev)).collect())
+    }
+
+    /// Best two-card action and its EV. Ties resolve in the order
+    /// stand, hit, double, split, surrender. With `round_to`, EVs are rounded to that many
+    /// decimals before comparing, so near-ties follow the same order.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, is_pair, pair_rank, up, deck, hole_constraint, round_to=None))]
+    fn optimal_action(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        round_to: Option<u32>,
+    ) -> PyResult<(String, f64)> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let mut memo = self.memo();
+        let mut evs = self.action_evs(pt_total, pt_soft, pair, up, &arr, hole_constraint, self.surrender, &mut memo.map);
+        round_evs(&mut evs, round_to);
+        let (a, ev) = best_action(&evs);
+        Ok((a.to_string(), ev))
+    }
+
+    /// Best action and net round EV on `deck` (the player's cards already
+    /// out) with the legal actions spelled out:
+    /// hitting plays on for up to `dp_depth` cards, a split (of `pair_rank`,
+    /// under `das` or the simulator's rule) counts both hands, and late
+    /// surrender is `-surrender_fraction` once the peek has ruled out a
+    /// natural. Ties resolve stand, hit, double, split, surrender.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, up, deck, hole_constraint, can_double=true, can_split=false, pair_rank=None, das=None, late_surrender=false))]
+    fn best_action_ev(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        can_double: bool,
+        can_split: bool,
+        pair_rank: Option<usize>,
+        das: Option<DasArg>,
+        late_surrender: bool,
+    ) -> PyResult<(String, f64)> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let das = das.map_or(Ok(self.das), |d| d.rule())?;
+        let split_rank = match (can_split, pair_rank) {
+            (false, _) => None,
+            (true, Some(pr)) if pr < 10 => Some(pr),
+            (true, Some(pr)) => return Err(PyValueError::new_err(format!("pair rank index {} out of range 0..9", pr))),
+            (true, None) => return Err(PyValueError::new_err("can_split needs a pair_rank")),
+        };
+        let mut memo = self.memo();
+        let legal = (can_double, split_rank, das, late_surrender);
+        let (a, ev) = best_action(&self.legal_action_evs(pt_total, pt_soft, up, &arr, hole_constraint, legal, &mut memo.map));
+        Ok((a.to_string(), ev))
+    }
+
+    /// EV of taking `action` ("stand", "hit", "double", "split" or
+    /// "surrender") on this two-card hand whether or not it is best; the
+    /// `optimal_action` EV minus this is the cost of the mistake. Errors if
+    /// the action is not legal here.
+    #[allow(clippy::too_many_arguments)]
+    fn ev_with_forced_action(
+        &self,
+        action: &str,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let mut memo = self.memo();
+        let evs = self.action_evs(pt_total, pt_soft, pair, up, &arr, hole_constraint, self.surrender, &mut memo.map);
+        evs.iter().find(|e| e.0 == action).map(|e| e.1).ok_or_else(|| {
+            let legal: Vec<&str> = evs.iter().map(|e| e.0).collect();
+            PyValueError::new_err(format!("action {:?} is not legal here; legal: {}", action, legal.join(", ")))
+        })
+    }
+
+    /// Every legal action with its EV, best first (the same EVs as
+    /// `all_action_evs`). Equal EVs keep the stand, hit, double, split, surrender order,
+    /// so the head always agrees with `optimal_action`; the gap to the second
+    /// entry is the decision's margin.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, is_pair, pair_rank, up, deck, hole_constraint, round_to=None))]
+    fn action_ranking(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        round_to: Option<u32>,
+    ) -> PyResult<Vec<(String, f64)>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let mut memo = self.memo();
+        let mut evs = self.action_evs(pt_total, pt_soft, pair, up, &arr, hole_constraint, self.surrender, &mut memo.map);
+        round_evs(&mut evs, round_to);
+        let best = best_action(&evs).0;
+        evs.sort_by(|a, b| b.1.total_cmp(&a.1));
+        if let Some(i) = evs.iter().position(|e| e.0 == best) {
+            evs[..=i].rotate_right(1);
+        }
+        Ok(evs.into_iter().map(|(a, ev)| (a.to_string(), ev)).collect())
+    }
+
+    /// Optimal-play EV under H17 minus under S17 for this hand and deck: what
+    /// the dealer hitting soft 17 costs the player here. Both runs use the
+    /// stock S17/H17 tables (a custom `dealer_policy` is ignored) and
+    /// otherwise this simulator's rules.
+    #[allow(clippy::too_many_arguments)]
+    fn h17_cost(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<f64> {
+        let hc = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let best = |h17: bool| {
+            let sim = Self { h17, custom_policy: None, h17_by_upcard: None, ..self.copy_rules() };
+            let mut memo = Memo::new();
+            best_action(&sim.action_evs(pt_total, pt_soft, pair, up, &arr, hc, sim.surrender, &mut memo)).1
+        };
+        Ok(best(true) - best(false))
+    }
+
+    /// One row of the strategy chart: `optimal_action` against each upcard
+    /// A,2..9,T, in one call.
+    fn hand_vs_all_upcards(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+    ) -> PyResult<Vec<(String, f64)>> {
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let evs = self.optimal_by_upcard(pt_total, pt_soft, pair, &deck, hole_constraint)?;
+        Ok(evs.into_iter().map(|(a, ev)| (a.to_string(), ev)).collect())
+    }
+
+    /// Upcard (and EV) against which this non-pair hand's `optimal_action`
+    /// EV is lowest; ties keep the lower rank index.
+    fn worst_upcard(&self, pt_total: i32, pt_soft: bool, deck: Vec<Count>, hole_constraint: i32) -> PyResult<(usize, f64)> {
+        let evs = self.optimal_by_upcard(pt_total, pt_soft, None, &deck, hole_constraint)?;
+        Ok(evs.into_iter().enumerate().fold((0, f64::INFINITY), |w, (up, (_, ev))| if ev < w.1 { (up, ev) } else { w }))
+    }
+
+    /// Upcard (and EV) against which this non-pair hand's `optimal_action`
+    /// EV is highest; ties keep the lower rank index.
+    fn best_upcard(&self, pt_total: i32, pt_soft: bool, deck: Vec<Count>, hole_constraint: i32) -> PyResult<(usize, f64)> {
+        let evs = self.optimal_by_upcard(pt_total, pt_soft, None, &deck, hole_constraint)?;
+        Ok(evs.into_iter().enumerate().fold((0, f64::NEG_INFINITY), |b, (up, (_, ev))| if ev > b.1 { (up, ev) } else { b }))
+    }
+
+    /// Hi-Lo true count at which the optimal action for this hand changes from
+    /// its neutral-count (TC 0) choice, searched within ±10 and refined by
+    /// bisection to 0.01. Pairs of aces are soft 12; other hands are hard.
+    /// None if the action never changes in range.
+    #[allow(clippy::too_many_arguments)]
+    fn deviation_index(
+        &self,
+        pt_total: i32,
+        is_pair: bool,
+        pair_rank: usize,
+        up: usize,
+        num_decks: usize,
+        hole_constraint: i32,
+    ) -> PyResult<Option<f64>> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let soft = is_pair && pair_rank == 0;
+        let pair = if is_pair { Some(pair_rank) } else { None };
+        let mut memo = self.memo();
+        self.deviation_in(pt_total, soft, pair, up, num_decks, hole_constraint, &COUNT_SYSTEMS[0].1, 10, &mut memo.map)
+    }
+
+    /// `deviation_index` over every chart cell with a decision to make (hard
+    /// 8-17, soft 13-20, all pairs) plus insurance: `("insurance", tc)` first,
+    /// then `("H16vT", tc)`-style labels in chart order, one for each cell
+    /// whose best action changes from its TC 0 choice within true count
+    /// ±`max_count` (a playable range; indices beyond it rarely pay) under
+    /// `count_system` (balanced systems only, see `running_count`). The shoe
+    /// at each count is `shoe_at_count`. Each upcard's cells share a memo.
+    #[pyo3(signature = (num_decks, hole_constraint, count_system="hi_lo", max_count=6))]
+    fn generate_indices(
+        &self,
+        py: Python<'_>,
+        num_decks: usize,
+        hole_constraint: i32,
+        count_system: &str,
+        max_count: usize,
+    ) -> PyResult<Vec<(String, f64)>> {
+        let (tags, balanced) = count_tags(count_system)?;
+        if !balanced {
+            return Err(PyValueError::new_err(format!("{:?} is unbalanced; indices need a true count", count_system)));
+        }
+        let hands: Vec<GridHand> = grid_hands()
+            .into_iter()
+            .filter(|h| h.pair.is_some() || if h.soft { (13..=20).contains(&h.total) } else { (8..=17).contains(&h.total) })
+            .collect();
+        let columns: Vec<PyResult<(Vec<Option<f64>>, Memo)>> = py.allow_threads(|| {
+            (0..10)
+                .into_par_iter()
+                .map(|up| {
+                    let mut memo = Memo::new();
+                    let hc = self.hole_for(up, hole_constraint);
+                    let col = hands
+                        .iter()
+                        .map(|h| self.deviation_in(h.total, h.soft, h.pair, up, num_decks, hc, &tags, max_count, &mut memo))
+                        .collect::<PyResult<Vec<_>>>()?;
+                    Ok((col, memo))
+                })
+                .collect()
+        });
+
+        let mut out = Vec::new();
+        let ev_at = |tc: f64| -> PyResult<f64> { Ok(self.insurance_in(&self.without_upcard(shoe_at_count(tc, num_decks, &tags), 0)?)) };
+        let (mut lo, mut hi) = (-(max_count as f64), max_count as f64);
+        if ev_at(lo)? < 0.0 && ev_at(hi)? >= 0.0 {
+            while hi - lo > 0.01 {
+                let mid = 0.5 * (lo + hi);
+                if ev_at(mid)? >= 0.0 {
+                    hi = mid;
+                } else {
+                    lo = mid;
+                }
+            }
+            out.push(("insurance".to_string(), 0.5 * (lo + hi)));
+        }
+        let mut cells = vec![Vec::with_capacity(10); hands.len()];
+        let mut shared = self.memo();
+        for column in columns {
+            let (col, memo) = column?;
+            for (i, index) in col.into_iter().enumerate() {
+                cells[i].push(index);
+            }
+            shared.map.extend(memo);
+        }
+        for (h, row) in hands.iter().zip(cells) {
+            for (up, index) in row.into_iter().enumerate() {
+                if let Some(tc) = index {
+                    out.push((format!("{}v{}", h.label, RANK_LABELS[up]), tc));
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Probability of each two-card starting hand drawn from `deck`, keyed by
+    /// (total, soft, is_pair); a natural is (21, true, false) and A,A is
+    /// (12, true, true), in key order. Sums to 1 (empty if fewer than two cards).
+    fn initial_hand_distribution(&self, deck: Vec<Count>) -> PyResult<BTreeMap<(i32, bool, bool), f64>> {
+        let arr = self.play_deck(&deck)?;
+        let n: i32 = arr.iter().sum();
+        let policy = self.policy();
+        let mut out = BTreeMap::new();
+        if n < 2 {
+            return Ok(out);
+        }
+        let pairs = (n as f64) * ((n - 1) as f64);
+        for a in 0..10 {
+            for b in a..10 {
+                let ways = if a == b {
+                    (arr[a] as f64) * ((arr[a] - 1).max(0) as f64)
+                } else {
+                    2.0 * (arr[a] as f64) * (arr[b] as f64)
+                };
+                if ways <= 0.0 {
+                    continue;
+                }
+                let (t0, s0) = policy.add(0, false, a);
+                let (t, s) = policy.add(t0, s0, b);
+                *out.entry((t, s, a == b)).or_insert(0.0) += ways / pairs;
+            }
+        }
+        Ok(out)
+    }
+
+    /// Probability that at least `num_players` of the `hands` two-card hands
+    /// dealt from `deck` (default 7, a full table) are a pair of `rank`, tens
+    /// pairing as one rank as they split. Exact, by inclusion-exclusion over
+    /// which hands pair.
+    #[pyo3(signature = (rank, deck, num_players, hands=7))]
+    fn pair_draw_probability(&self, rank: usize, deck: Vec<Count>, num_players: usize, hands: usize) -> PyResult<f64> {
+        if rank >= 10 {
+            return Err(PyValueError::new_err(format!("card rank index {} out of range 0..9", rank)));
+        }
+        let arr = self.checked_deck(&deck)?;
+        let (c, n) = (arr[rank], arr.iter().sum::<i32>());
+        if (2 * hands) as i32 > n {
+            return Err(PyValueError::new_err(format!("deck holds {} cards, too few to deal {} hands", n, hands)));
+        }
+        if num_players > hands {
+            return Ok(0.0);
+        }
+        // q[m]: a given m hands are all pairs of `rank`.
+        let q: Vec<f64> = (0..=hands)
+            .map(|m| (0..2 * m as i32).fold(1.0, |acc, i| acc * ((c - i).max(0) as f64) / ((n - i) as f64)))
+            .collect();
+        let h = hands as i32;
+        let exactly = |j: usize| -> f64 {
+            (j..=hands)
+                .map(|m| (-1f64).powi((m - j) as i32) * choose(m as i32, j as i32) * choose(h, m as i32) * q[m])
+                .sum()
+        };
+        Ok((num_players..=hands).map(exactly).sum::<f64>().clamp(0.0, 1.0))
+    }
+
+    /// Net EV per unit of a two-card total side bet (Lucky Ladies for 20): the
+    /// player's first two cards, if they make `target_total`, pay
+    /// `payouts[tier]` to one in their best tier of "queen_hearts_pair",
+    /// "matched" (same rank and suit), "suited" and "total", by default
+    /// 200/25/10/4. The premium tiers read suits from `suited` (13 ranks by
+    /// 4 suits, as for `poker_21_3_probs`), used instead of `deck` when given;
+    /// otherwise `deck` is spread evenly over suits and the ten's ranks.
+    /// Tiers that also need a dealer natural are not modelled.
+    #[pyo3(signature = (target_total, deck, suited=None, payouts=None))]
+    fn total_side_bet_ev(&self, target_total: i32, deck: Vec<Count>, suited: Option<Vec<Vec<Count>>>, payouts: Option<HashMap<String, f64>>) -> PyResult<f64> {
+        let pays = paytable(&TOTAL_SIDE_BET, payouts, "total side bet")?;
+        let cells = match suited {
+            Some(rows) => suited_deck(&rows)?.map(|row| row.map(|c| c as f64)),
+            None => spread_suits(&self.checked_deck(&deck)?),
+        };
+        let dist = total_side_bet_dist(&cells, target_total, &self.policy());
+        let hit: f64 = dist.iter().sum();
+        Ok(dist.iter().zip(pays).map(|(p, pay)| p * pay).sum::<f64>() - (1.0 - hit))
+    }
+
+    /// Insurance EV per unit insured against an ace upcard: 2:1 if the hole
+    /// is a ten, else the insurance is lost.
+    fn insurance_ev(&self, deck: Vec<Count>) -> PyResult<f64> {
+        let arr = self.live_deck(&deck, 0)?;
+        Ok(self.insurance_in(&arr))
+    }
+
+    /// Unconditional EV of a two-card hand against an ace, played optimally,
+    /// bundled with a half-stake insurance bet when `insured`. Insurance
+    /// settles first (+1 on a ten in the hole, -0.5 otherwise); the main hand
+    /// then loses its stake to a peeked natural or, without an ace peek, plays
+    /// out under the ENHC rules. A player natural takes the `natural_ev` path,
+    /// so insured it is the even-money line.
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (pt_total, pt_soft, is_pair, pair_rank, deck, insured=true))]
+    fn hand_ev_with_insurance(
+        &self,
+        pt_total: i32,
+        pt_soft: bool,
+        is_pair: bool,
+        pair_rank: usize,
+        deck: Vec<Count>,
+        insured: bool,
     ) -> PyResult<f64> {
-        let mut arr = [0i32; 10];
-        for i in 0..10 {
-            arr[i] = *deck.get(i).unwrap_or(&0);
+        let arr = self.live_deck(&deck, 0)?;
+        let p_nat = if self.aces_hard_only { 0.0 } else { natural_prob(&arr, 0) };
+        let mut memo = self.memo();
+        let main = if pt_total == 21 && pt_soft && !is_pair && !self.aces_hard_only {
+            self.natural_in(0, &arr, HC_NONE, SUITED_NATURAL_PROB)
+        } else {
+            let pair = if is_pair { Some(pair_rank) } else { None };
+            let hc = self.hole_for(0, HC_AUTO);
+            let evs = self.action_evs(pt_total, pt_soft, pair, 0, &arr, hc, self.surrender, &mut memo.map);
+            let best = best_action(&evs).1;
+            if hc == HC_NONE {
+                best
+            } else {
+                (1.0 - p_nat) * best - p_nat
+            }
+        };
+        Ok(if insured { main + 0.5 * self.insurance_in(&arr) } else { main })
+    }
+
+    /// Round EV per unit bet off the top of `deck`: the upcard and both player
+    /// cards are dealt from it, and each starting hand takes its best action
+    /// (hitting plays on for up to `dp_depth` cards, a split counts both hands). A peeked dealer natural
+    /// ends the round first, costing the stake or pushing a player natural;
+    /// a player natural is paid `bj_payout`. Insurance is never taken.
+    /// `suited` (13 ranks A..K by 4 suits, the same cards as `deck`) sets
+    /// the chance a natural earns `blackjack_bonus`.
+    #[pyo3(signature = (deck, suited=None))]
+    fn initial_deal_ev(&self, deck: Vec<Count>, suited: Option<Vec<Vec<Count>>>) -> PyResult<f64> {
+        let arr = self.play_deck(&deck)?;
+        if arr.iter().sum::<i32>() < 4 {
+            return Err(PyValueError::new_err("initial_deal_ev needs at least four cards to deal"));
+        }
+        let suits = suited.map(|rows| suits_of(&rows, &arr)).transpose()?;
+        if suits.is_some() && self.double_exposure {
+            return Err(PyValueError::new_err("initial_deal_ev does not model suits under double_exposure"));
+        }
+        let mut memo = self.memo();
+        Ok(self.deal_ev_in(arr, HC_AUTO, suits.as_ref(), &mut memo.map))
+    }
+
+    /// Change in off-the-top `house_edge` when one card of each rank leaves
+    /// `deck` (0 for ranks already exhausted): positive where removing the
+    /// rank hurts the player. The eleven deals share the simulator memo.
+    fn game_effect_of_removal(&self, deck: Vec<Count>, hole_constraint: i32) -> PyResult<Vec<f64>> {
+        let mut arr = self.play_deck(&deck)?;
+        if arr.iter().sum::<i32>() < 5 {
+            return Err(PyValueError::new_err("game_effect_of_removal needs at least five cards"));
+        }
+        let mut memo = self.memo();
+        let base = self.deal_ev_in(arr, hole_constraint, None, &mut memo.map);
+        let mut out = vec![0.0; 10];
+        for (r, o) in out.iter_mut().enumerate() {
+            if arr[r] <= 0 {
+                continue;
+            }
+            arr[r] -= 1;
+            *o = base - self.deal_ev_in(arr, hole_constraint, None, &mut memo.map);
+            arr[r] += 1;
+        }
+        Ok(out)
+    }
+
+    /// Probability of each net round result (e.g. -2, -1, -0.5, 0, +1, +1.5,
+    /// +2) against `up`, with both player cards dealt from `deck` and every
+    /// hand played as in `initial_deal_ev`; hitting plays on for up to
+    /// `depth` cards (default `dp_depth`). The two hands of a split are
+    /// combined as independent draws from the post-split deck, as `split_ev`
+    /// deals them, so the mean is exact and the spread of splits approximate.
+    /// A natural earns `blackjack_bonus` with the chance `suited` (the same
+    /// cards as `deck`, 13 ranks A..K by 4 suits) gives it. Results come in
+    /// ascending order.
+    #[pyo3(signature = (up, deck, hole_constraint, depth=None, suited=None))]
+    fn round_outcome_distribution<'py>(
+        &self,
+        py: Python<'py>,
+        up: usize,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        depth: Option<usize>,
+        suited: Option<Vec<Vec<Count>>>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let suited = self.suited_for(&deck, up, suited)?;
+        let results = self.round_pmf(up, self.live_deck(&deck, up)?, hole_constraint, depth, suited, "round_outcome_distribution")?;
+        let out = PyDict::new_bound(py);
+        for (net, q) in results {
+            out.set_item(net, q)?;
+        }
+        Ok(out)
+    }
+
+    /// Variance of a whole optimally played round's net result against `up`,
+    /// from the `round_outcome_distribution` PMF (so a split's spread is the
+    /// same approximation, and the same `suited` deck).
+    #[pyo3(signature = (up, deck, hole_constraint, depth=None, suited=None))]
+    fn round_variance(&self, up: usize, deck: Vec<Count>, hole_constraint: i32, depth: Option<usize>, suited: Option<Vec<Vec<Count>>>) -> PyResult<f64> {
+        let suited = self.suited_for(&deck, up, suited)?;
+        let results = self.round_pmf(up, self.live_deck(&deck, up)?, hole_constraint, depth, suited, "round_variance")?;
+        let mean: f64 = results.iter().map(|&(x, q)| x * q).sum();
+        Ok(results.iter().map(|&(x, q)| q * (x - mean) * (x - mean)).sum())
+    }
+
+    /// Kelly bet ramp: for each whole Hi-Lo true count in `count_range`
+    /// (inclusive), the round's EV and `kelly_fraction` of bankroll to bet
+    /// (0 while the game is negative), on a `num_decks` shoe shifted to that
+    /// count (`shoe_at_true_count`). Both come from the round outcome
+    /// distribution averaged over upcards, so the EV is `initial_deal_ev`'s.
+    #[pyo3(signature = (num_decks, count_range, hole_constraint=HC_AUTO))]
+    fn optimal_bet_spread(&self, num_decks: usize, count_range: (f64, f64), hole_constraint: i32) -> PyResult<Vec<(f64, f64)>> {
+        let (lo, hi) = (count_range.0.ceil() as i32, count_range.1.floor() as i32);
+        (lo..=hi)
+            .map(|tc| {
+                let arr = self.play_shoe(shoe_at_true_count(tc as f64, num_decks));
+                let n: i32 = arr.iter().sum();
+                let (mut mean, mut second) = (0.0, 0.0);
+                for up in (0..10).filter(|&up| arr[up] > 0) {
+                    let p_up = (arr[up] as f64) / (n as f64);
+                    for (x, q) in self.round_pmf(up, self.without_upcard(arr, up)?, hole_constraint, None, SUITED_NATURAL_PROB, "optimal_bet_spread")? {
+                        mean += p_up * q * x;
+                        second += p_up * q * x * x;
+                    }
+                }
+                Ok((mean, kelly_fraction(mean, second - mean * mean)))
+            })
+            .collect()
+    }
+
+    /// House edge of the simulator's game off the top of its shoe: the
+    /// negated `initial_deal_ev`, so a player-favourable game is negative.
+    fn house_edge(&self) -> PyResult<f64> {
+        Ok(-self.initial_deal_ev(self.shoe.to_vec(), None)?)
+    }
+
+    /// House edge off the top of `deck` at 6:5 minus at 3:2, all other rules
+    /// held: what a 6:5 table costs. Only the natural's payout differs
+    /// between the two `initial_deal_ev` runs, so just that branch is summed.
+    /// A dealer natural pushes a player natural with or without a peek, so
+    /// `hole_constraint` (HC_AUTO or HC_NONE) leaves the result unchanged.
+    fn payout_penalty(&self, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        if hole_constraint != HC_AUTO && hole_constraint != HC_NONE {
+            return Err(PyValueError::new_err("payout_penalty takes HC_AUTO or HC_NONE"));
+        }
+        let three_two = Self { bj_payout: 1.5, ..self.copy_rules() };
+        let six_five = Self { bj_payout: 1.2, ..self.copy_rules() };
+        let policy = self.policy();
+        let mut arr = self.play_deck(&deck)?;
+        let n: i32 = arr.iter().sum();
+        if n < 4 {
+            return Err(PyValueError::new_err("payout_penalty needs at least four cards to deal"));
+        }
+        let mut penalty = 0.0;
+        for up in 0..10 {
+            if arr[up] <= 0 {
+                continue;
+            }
+            let p_up = (arr[up] as f64) / (n as f64);
+            arr[up] -= 1;
+            for (a, b) in [(0, 9), (9, 0)] {
+                if !policy.natural(a, b) || arr[a] <= 0 {
+                    continue;
+                }
+                let pa = (arr[a] as f64) / ((n - 1) as f64);
+                arr[a] -= 1;
+                if arr[b] > 0 {
+                    let pb = (arr[b] as f64) / ((n - 2) as f64);
+                    arr[b] -= 1;
+                    penalty += p_up * pa * pb * (three_two.natural_in(up, &arr, HC_NONE, SUITED_NATURAL_PROB) - six_five.natural_in(up, &arr, HC_NONE, SUITED_NATURAL_PROB));
+                    arr[b] += 1;
+                }
+                arr[a] += 1;
+            }
+            arr[up] += 1;
+        }
+        Ok(penalty)
+    }
+
+    /// Hi-Lo true count at which insurance breaks even on a `num_decks` shoe
+    /// (`shoe_at_true_count`, ace upcard removed), bisected to 0.01 within ±10.
+    fn insurance_index(&self, num_decks: usize) -> PyResult<f64> {
+        let ev_at = |tc: f64| -> PyResult<f64> { Ok(self.insurance_in(&self.without_upcard(shoe_at_true_count(tc, num_decks), 0)?)) };
+        let (mut lo, mut hi) = (-10.0, 10.0);
+        if ev_at(lo)? >= 0.0 || ev_at(hi)? < 0.0 {
+            return Err(PyValueError::new_err("insurance does not cross break-even within true count ±10"));
+        }
+        while hi - lo > 0.01 {
+            let mid = 0.5 * (lo + hi);
+            if ev_at(mid)? >= 0.0 {
+                hi = mid;
+            } else {
+                lo = mid;
+            }
+        }
+        Ok(0.5 * (lo + hi))
+    }
+
+    /// Optimal play of the two-card soft total `soft_total` (13-20, i.e. A,2
+    /// to A,9) against `up`, with the ace and its kicker removed from `deck`.
+    /// Hitting plays on as in `initial_deal_ev` (up to `dp_depth` cards), so a
+    /// soft hand that hits is not stuck with its first draw; doubling is
+    /// weighed whenever `double_rule` allows it. The EV is per stake, given
+    /// no dealer natural wherever the peek rules it out.
+    fn soft_strategy_row(&self, soft_total: i32, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<(String, f64)> {
+        let hc = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        let (a, ev) = self.soft_row_in(soft_total, up, &arr, hc, &mut memo.map)?;
+        Ok((a.to_string(), ev))
+    }
+
+    /// `soft_strategy_row` for soft 13-20 ("S13".."S20") against every
+    /// upcard, as a grid (`hands`, `actions`, `evs`) that serializes like
+    /// `strategy_grid`'s.
+    fn soft_strategy(&self, deck: Vec<Count>, hole_constraint: i32) -> PyResult<StrategyGrid> {
+        let arr = self.play_deck(&deck)?;
+        let mut memo = self.memo();
+        let mut grid = StrategyGrid { rules_fingerprint: self.fingerprint(), hands: Vec::new(), actions: Vec::new(), evs: Vec::new() };
+        for t in 13..=20 {
+            let (mut acts, mut evs) = (Vec::with_capacity(10), Vec::with_capacity(10));
+            for up in 0..10 {
+                let live = self.without_upcard(arr, up)?;
+                let (a, ev) = self.soft_row_in(t, up, &live, self.hole_for(up, hole_constraint), &mut memo.map)?;
+                acts.push(a.to_string());
+                evs.push(ev);
+            }
+            grid.hands.push(format!("S{}", t));
+            grid.actions.push(acts);
+            grid.evs.push(evs);
+        }
+        Ok(grid)
+    }
+
+    /// Full strategy chart (see `grid_hands` for row order): `strategy_table`
+    /// under the simulator's own DAS and surrender rules, laid out by row
+    /// and upcard, with net round EVs. Upcard columns run
+    /// in parallel with the GIL released; each column fills its own memo and the
+    /// memos are merged into the shared cache afterwards. `progress(done, 10)`
+    /// is called, with the GIL re-acquired, as each column completes (in
+    /// completion order); the grid itself is always assembled in upcard order.
+    #[pyo3(signature = (deck, hole_constraint, progress=None))]
+    fn strategy_grid(
+        &self,
+        py: Python<'_>,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        progress: Option<PyObject>,
+    ) -> PyResult<StrategyGrid> {
+        let arr = self.play_deck(&deck)?;
+        let decks = (0..10).map(|up| self.without_upcard(arr, up)).collect::<PyResult<Vec<_>>>()?;
+        let hands = grid_hands();
+        let done = AtomicUsize::new(0);
+        let cb_err: Mutex<Option<PyErr>> = Mutex::new(None);
+        let columns: Vec<(Vec<(&'static str, f64)>, Memo)> = py.allow_threads(|| {
+            (0..10)
+                .into_par_iter()
+                .map(|up| {
+                    let mut memo = Memo::new();
+                    let hc = self.hole_for(up, hole_constraint);
+                    let col = hands
+                        .iter()
+                        .map(|h| {
+                            let legal = (true, h.pair, self.das, self.surrender);
+                            best_action(&self.legal_action_evs(h.total, h.soft, up, &decks[up], hc, legal, &mut memo))
+                        })
+                        .collect();
+                    if let Some(cb) = &progress {
+                        let n = done.fetch_add(1, Ordering::SeqCst) + 1;
+                        Python::with_gil(|py| {
+                            if let Err(e) = cb.call1(py, (n, 10)) {
+                                cb_err.lock().unwrap_or_else(|e| e.into_inner()).get_or_insert(e);
+                            }
+                        });
+                    }
+                    (col, memo)
+                })
+                .collect()
+        });
+        if let Some(e) = cb_err.into_inner().unwrap_or_else(|e| e.into_inner()) {
+            return Err(e);
+        }
+
+        let mut grid = StrategyGrid {
+            rules_fingerprint: self.fingerprint(),
+            hands: hands.iter().map(|h| h.label.clone()).collect(),
+            actions: vec![Vec::with_capacity(10); hands.len()],
+            evs: vec![Vec::with_capacity(10); hands.len()],
+        };
+        let mut shared = self.memo();
+        for (col, memo) in columns {
+            for (i, (a, ev)) in col.into_iter().enumerate() {
+                grid.actions[i].push(a.to_string());
+                grid.evs[i].push(ev);
+            }
+            shared.map.extend(memo);
+        }
+        Ok(grid)
+    }
+
+    /// `best_action_ev` for every chart row (see `grid_hands`) against every
+    /// upcard, as `(label, up, action, ev)` rows by hand then upcard. Each
+    /// cell runs on `deck` minus its upcard, doubling allowed and pairs split
+    /// under `das` (default the simulator's). Cells are cut into one chunk
+    /// per rayon thread and run with the GIL released, each chunk on its own
+    /// memo, merged into the shared cache afterwards.
+    #[pyo3(signature = (deck, hole_constraint, das=None, late_surrender=false))]
+    fn strategy_table(
+        &self,
+        py: Python<'_>,
+        deck: Vec<Count>,
+        hole_constraint: i32,
+        das: Option<DasArg>,
+        late_surrender: bool,
+    ) -> PyResult<Vec<(String, usize, String, f64)>> {
+        let arr = self.play_deck(&deck)?;
+        let decks = (0..10).map(|up| self.without_upcard(arr, up)).collect::<PyResult<Vec<_>>>()?;
+        let das = das.map_or(Ok(self.das), |d| d.rule())?;
+        let hands = grid_hands();
+        let cells: Vec<(&GridHand, usize)> = hands.iter().flat_map(|h| (0..10).map(move |up| (h, up))).collect();
+        let chunk = cells.len().div_ceil(rayon::current_num_threads()).max(1);
+        let chunks: Vec<(Vec<(&'static str, f64)>, Memo)> = py.allow_threads(|| {
+            cells
+                .par_chunks(chunk)
+                .map(|part| {
+                    let mut memo = Memo::new();
+                    let res = part
+                        .iter()
+                        .map(|&(h, up)| {
+                            let legal = (true, h.pair, das, late_surrender);
+                            let hc = self.hole_for(up, hole_constraint);
+                            best_action(&self.legal_action_evs(h.total, h.soft, up, &decks[up], hc, legal, &mut memo))
+                        })
+                        .collect();
+                    (res, memo)
+                })
+                .collect()
+        });
+        let mut shared = self.memo();
+        let mut best = Vec::with_capacity(cells.len());
+        for (res, memo) in chunks {
+            best.extend(res);
+            shared.map.extend(memo);
+        }
+        Ok(cells.iter().zip(best).map(|((h, up), (a, ev))| (h.label.clone(), *up, a.to_string(), ev)).collect())
+    }
+
+    /// `optimal_action` for every scenario, in input order, in one call. With
+    /// `parallel` the list is cut into one contiguous chunk per rayon thread
+    /// and run with the GIL released, each chunk on its own memo (neighbouring
+    /// scenarios on one deck share runouts), merged into the shared cache
+    /// afterwards; otherwise everything runs on the shared memo in order.
+    #[pyo3(signature = (scenarios, parallel=true))]
+    fn optimal_action_batch(&self, py: Python<'_>, scenarios: Vec<Scenario>, parallel: bool) -> PyResult<Vec<(String, f64)>> {
+        let run = |sc: &Scenario, memo: &mut Memo| -> PyResult<(&'static str, f64)> {
+            let Scenario(pt_total, pt_soft, is_pair, pair_rank, up, deck, hole_constraint) = sc;
+            let arr = self.live_deck(deck, *up)?;
+            let hc = self.hole_for(*up, *hole_constraint);
+            let pair = if *is_pair { Some(*pair_rank) } else { None };
+            Ok(best_action(&self.action_evs(*pt_total, *pt_soft, pair, *up, &arr, hc, self.surrender, memo)))
+        };
+        let out: Vec<(&'static str, f64)> = if parallel {
+            let chunk = scenarios.len().div_ceil(rayon::current_num_threads()).max(1);
+            let chunks: Vec<(PyResult<Vec<_>>, Memo)> = py.allow_threads(|| {
+                scenarios
+                    .par_chunks(chunk)
+                    .map(|part| {
+                        let mut memo = Memo::new();
+                        let res = part.iter().map(|sc| run(sc, &mut memo)).collect();
+                        (res, memo)
+                    })
+                    .collect()
+            });
+            let mut shared = self.memo();
+            let mut out = Vec::with_capacity(scenarios.len());
+            for (res, memo) in chunks {
+                out.extend(res?);
+                shared.map.extend(memo);
+            }
+            out
+        } else {
+            let mut memo = self.memo();
+            scenarios.iter().map(|sc| run(sc, &mut memo.map)).collect::<PyResult<_>>()?
+        };
+        Ok(out.into_iter().map(|(a, ev)| (a.to_string(), ev)).collect())
+    }
+
+    /// Dealer final-total table: one row per upcard (A,2..9,T), columns
+    /// [17,18,19,20,21,bust]. Each row runs on the deck minus its upcard (as-is
+    /// if the deck has none left); rows share one memo, so overlapping
+    /// runouts are computed once.
+    fn dealer_table(&self, deck: Vec<Count>, hole_constraint: i32) -> PyResult<Vec<Vec<f64>>> {
+        let arr = self.play_deck(&deck)?;
+        let policy = self.policy();
+        let mut memo = self.memo();
+        Ok((0..10)
+            .map(|up| match self.without_upcard(arr, up) {
+                Ok(live) => dealer_dist_for_up(&live, up, self.hole_for(up, hole_constraint), &policy, &mut memo.map).to_vec(),
+                Err(_) => vec![0.0; 6],
+            })
+            .collect())
+    }
+
+    /// One `dealer_table` row: the runout against `up` averaged over the
+    /// holes `hole_constraint` allows, renormalized as `stand_ev` does. All
+    /// zeros on an empty deck.
+    fn dealer_distribution(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<Vec<f64>> {
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(dealer_dist_for_up(&arr, up, self.hole_for(up, hole_constraint), &self.policy(), &mut memo.map).to_vec())
+    }
+
+    /// Unconditional dealer runout against `up` ([17,18,19,20,21,bust]): every
+    /// hole the deck holds, weighted by its count, with a dealer natural kept
+    /// in the 21 bin. This is the distribution ENHC settlement needs. A peek
+    /// constraint (`dealer_table` with HC_AUTO/HC_NOT_TEN/HC_NOT_ACE) instead
+    /// drops the natural holes and renormalizes, giving the runout *given no
+    /// natural*; the two agree off an A/T upcard, and on one the unconditional
+    /// row is `p_nat` in 21 plus `1 - p_nat` times the peeked row. Void
+    /// runout mass (push-22, empty-shoe push) is left out, as everywhere.
+    fn dealer_dist_upcard_only(&self, up: usize, deck: Vec<Count>) -> PyResult<[f64; 6]> {
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        Ok(dealer_dist_for_up(&arr, up, HC_NONE, &self.policy(), &mut memo.map))
+    }
+
+    /// Dealer runout against `up` given no two-card natural: the
+    /// `dealer_dist_upcard_only` distribution with the natural holes dropped
+    /// and the rest renormalized by `1 - p_nat`. It is what the player faces
+    /// after a peek, the same row `dealer_table` gives under HC_AUTO for an
+    /// A/T upcard (other upcards cannot make a natural). Errors if every hole
+    /// the deck holds would make one.
+    fn dealer_dist_no_bj(&self, up: usize, deck: Vec<Count>) -> PyResult<[f64; 6]> {
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = self.memo();
+        let (p_nat, dist) = self.natural_and_runout(up, &arr, HC_NONE, &self.policy(), &mut memo.map);
+        if p_nat >= 1.0 {
+            return Err(PyValueError::new_err("every hole card left makes a dealer natural"));
+        }
+        Ok(dist)
+    }
+
+    /// Exact dealer runout against `up` with an unrestricted hole, as bin
+    /// numerators [17,18,19,20,21,bust] over one common denominator.
+    /// Independent of `exact_rational`; errors if the deck is too large.
+    fn dealer_dist_exact(&self, up: usize, deck: Vec<Count>) -> PyResult<(Vec<u128>, u128)> {
+        let arr = self.live_deck(&deck, up)?;
+        let policy = self.policy().for_up(up);
+        let (t, s) = policy.add(0, false, up);
+        let (w, den) = exact_runout(&arr, t, s, 1, &policy)
+            .ok_or_else(|| PyValueError::new_err("deck too large for an exact runout (u128 overflow)"))?;
+        Ok((w.to_vec(), den))
+    }
+
+    /// Probability the dealer finishes on a soft 17 against `up`, averaged over
+    /// the allowed holes like `dealer_table`. Under S17 this is the share of
+    /// the 17 bin an H17 rule would hit again; under H17 it is 0.
+    fn dealer_soft17_prob(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy().for_up(up);
+        let rem: i32 = arr.iter().sum();
+        let mut memo = HashMap::new();
+        let (mut soft17, mut denom) = (0.0, 0.0);
+        for h in 0..10 {
+            let c = arr[h];
+            if c <= 0 || !hole_allowed(hole_constraint, h) {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[h] -= 1;
+            let (t0, s0) = policy.add(0, false, up);
+            let (t, s) = policy.add(t0, s0, h);
+            soft17 += p * dealer_soft17_from(&mut arr, t, s, 2, &policy, &mut memo);
+            denom += p;
+            arr[h] += 1;
+        }
+        Ok(if denom > 0.0 { soft17 / denom } else { 0.0 })
+    }
+
+    /// Expected cards in the dealer's finished hand against `up`, upcard and
+    /// hole included, averaged over the holes the constraint allows (a
+    /// natural is two cards). For card-consumption and rounds-per-shoe
+    /// estimates.
+    fn dealer_expected_cards(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<f64> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let mut arr = self.live_deck(&deck, up)?;
+        let policy = self.policy().for_up(up);
+        let rem: i32 = arr.iter().sum();
+        let mut memo = HashMap::new();
+        let (mut cards, mut denom) = (0.0, 0.0);
+        for h in 0..10 {
+            let c = arr[h];
+            if c <= 0 || !hole_allowed(hole_constraint, h) {
+                continue;
+            }
+            let p = (c as f64) / (rem as f64);
+            arr[h] -= 1;
+            let (t0, s0) = policy.add(0, false, up);
+            let (t, s) = policy.add(t0, s0, h);
+            cards += p * (2.0 + dealer_cards_from(&mut arr, t, s, 2, &policy, &mut memo));
+            denom += p;
+            arr[h] += 1;
+        }
+        // No hole to deal: the upcard is the whole hand.
+        Ok(if denom > 0.0 { cards / denom } else { 1.0 })
+    }
+
+    /// `hit_ev - stand_ev` for hard totals 4..21 (rows) against upcards A..T
+    /// (columns); positive cells favour hitting. Hitting plays on optimally
+    /// for up to `dp_depth` cards, and every cell shares the dealer memo.
+    fn hit_stand_gap_grid(&self, deck: Vec<Count>, hole_constraint: i32) -> PyResult<Vec<Vec<f64>>> {
+        let nat = self.nat_loss();
+        let depth = self.draw_limit(self.dp_depth).max(1);
+        let mut memo = self.memo();
+        let mut rows = vec![Vec::new(); 18];
+        for up in 0..10 {
+            let hc = self.hole_for(up, hole_constraint);
+            let arr = self.live_deck(&deck, up)?;
+            for (row, pt) in rows.iter_mut().zip(4..=21) {
+                let hit = self.hit_optimal_in(pt, false, 2, up, &arr, hc, nat, depth, &mut memo.map);
+                row.push(hit - self.stand_in(pt, up, &arr, hc, &mut memo.map));
+            }
+        }
+        Ok(rows)
+    }
+
+    /// Number of distinct dealer states (`DealerKey`s) one upcard's runout
+    /// memoizes, counted on a fresh memo so earlier calls don't hide any.
+    /// Stand and bust leaves are never stored, so only hitting states count.
+    fn count_dealer_states(&self, up: usize, deck: Vec<Count>, hole_constraint: i32) -> PyResult<usize> {
+        let hole_constraint = self.hole_for(up, hole_constraint);
+        let arr = self.live_deck(&deck, up)?;
+        let mut memo = Memo::new();
+        dealer_dist_for_up(&arr, up, hole_constraint, &self.policy(), &mut memo);
+        Ok(memo.len())
+    }
+
+    /// Entries in the dealer memo shared by every call on this simulator
+    /// (dropped whenever a rule change alters the fingerprint).
+    fn cache_len(&self) -> usize {
+        self.memo().map.len()
+    }
+
+    /// Empty the shared dealer memo, e.g. to bound memory after a long sweep.
+    fn clear_cache(&self) {
+        self.memo().map.clear();
+    }
+
+    /// "New shoe" for a simulation loop: clears the dealer memo like
+    /// `clear_cache` and returns the construction shoe as the deck to deal
+    /// the next shoe from. EV calls never deplete `shoe`, so it is restored
+    /// as built.
+    fn reset_to_shoe(&self) -> Vec<Count> {
+        self.clear_cache();
+        self.shoe.to_vec()
+    }
+}
+
+// ---------- Round outcome distribution ----------
+/// Probability mass over net round results, keyed by the result's bits
+/// (`-0.0` folded into `0.0`) so equal results share one entry.
+type Pmf = HashMap<u64, f64>;
+
+fn pmf_add(pmf: &mut Pmf, net: f64, p: f64) {
+    if p != 0.0 {
+        *pmf.entry((net + 0.0).to_bits()).or_insert(0.0) += p;
+    }
+}
+
+/// Distribution of the sum of two independent results.
+fn pmf_convolve(a: &Pmf, b: &Pmf) -> Pmf {
+    let mut out = Pmf::new();
+    for (x, px) in a {
+        for (y, py) in b {
+            pmf_add(&mut out, f64::from_bits(*x) + f64::from_bits(*y), px * py);
+        }
+    }
+    out
+}
+
+impl BlackjackSimulator {
+    /// `settle_hand` as a distribution: a finished hand of `cards` cards and
+    /// `stake` units (a busted one loses at once), with `nat_loss` per unit
+    /// to a dealer natural the hole constraint still allows. Void runout
+    /// mass pushes.
+    #[allow(clippy::too_many_arguments)]
+    fn leaf_pmf(&self, (pt, cards): (i32, usize), stake: f64, nat_loss: f64, up: usize, arr: &[Count; 10], hole_constraint: i32, p: f64, out: &mut Pmf, memo: &mut Memo) {
+        if pt > 21 {
+            pmf_add(out, -stake, p);
+            return;
+        }
+        if self.is_charlie(pt, cards) {
+            let p_nat = self.charlie_nat_prob(up, arr, hole_constraint);
+            pmf_add(out, stake * self.policy().win_pay, p * (1.0 - p_nat));
+            pmf_add(out, stake * nat_loss, p * p_nat);
+            return;
+        }
+        let policy = self.policy();
+        let t = &policy.settle21;
+        let (p_nat, dist) = self.natural_and_runout(up, arr, hole_constraint, &policy, memo);
+        let vs_nat = if pt == 21 { -t.drawn21_vs_natural } else { 1.0 };
+        pmf_add(out, stake * nat_loss * vs_nat, p * p_nat);
+        for (i, d) in dist.iter().enumerate() {
+            let v = if pt == 21 && i == 4 { t.drawn21_vs_drawn21 } else { settle_vs_player(pt, i, &policy) };
+            pmf_add(out, stake * v, p * (1.0 - p_nat) * d);
+        }
+        pmf_add(out, 0.0, p * (1.0 - p_nat) * (1.0 - dist.iter().sum::<f64>()));
+    }
+
+    /// `play_out_in` as a distribution: the same stand/hit choice at every node.
+    #[allow(clippy::too_many_arguments)]
+    fn play_out_pmf(&self, (pt, soft, cards): (i32, bool, usize), up: usize, arr: &[Count; 10], hole_constraint: i32, nat: f64, depth: usize, p: f64, out: &mut Pmf, memo: &mut Memo) {
+        if depth == 0 || pt >= 21 || arr.iter().sum::<i32>() <= 0 || self.is_charlie(pt, cards) {
+            return self.leaf_pmf((pt, cards), 1.0, nat, up, arr, hole_constraint, p, out, memo);
+        }
+        let stand = settle_stood(arr, pt, up, hole_constraint, &self.policy(), nat, memo);
+        let hit = self.hit_optimal_in(pt, soft, cards, up, arr, hole_constraint, nat, depth, memo);
+        if best_action(&[("stand", stand), ("hit", hit)]).0 == "stand" {
+            return self.leaf_pmf((pt, cards), 1.0, nat, up, arr, hole_constraint, p, out, memo);
         }
+        self.draw_pmf(pt, soft, arr, p, memo, |sim, t, s, rest, q, memo| {
+            sim.play_out_pmf((t, s, cards + 1), up, rest, hole_constraint, nat, depth - 1, q, out, memo)
+        });
+    }
+
+    /// Calls `next` for each card the deck can deal to (pt, soft), with the
+    /// new hand, the deck left and the branch probability.
+    fn draw_pmf(
+        &self,
+        pt: i32,
+        soft: bool,
+        arr: &[Count; 10],
+        p: f64,
+        memo: &mut Memo,
+        mut next: impl FnMut(&Self, i32, bool, &[Count; 10], f64, &mut Memo),
+    ) {
         let rem: i32 = arr.iter().sum();
         if rem <= 0 {
-            return Ok(0.0);
+            // Nothing to draw: the hand carries on as it is.
+            return next(self, pt, soft, arr, p, memo);
         }
-        let mut memo = HashMap::new();
-        let mut acc = 0.0;
-        let mut denom = 0.0;
-        for h in 0..10 {
-            let c = arr[h];
+        let mut rest = *arr;
+        for r in 0..10 {
+            let c = rest[r];
             if c <= 0 {
                 continue;
             }
-            if !hole_allowed(hole_constraint, h) {
+            rest[r] -= 1;
+            let (t, s) = self.policy().add(pt, soft, r);
+            next(self, t, s, &rest, p * (c as f64) / (rem as f64), memo);
+            rest[r] += 1;
+        }
+    }
+
+    /// One split hand as `split_in` plays it, per unit of its own stake.
+    fn split_child_pmf(&self, pair_rank: usize, up: usize, arr: &[Count; 10], hole_constraint: i32, memo: &mut Memo) -> Pmf {
+        let (nat, nat_dbl) = self.split_nat_losses();
+        let mut out = Pmf::new();
+        if arr.iter().sum::<i32>() <= 0 {
+            pmf_add(&mut out, 0.0, 1.0);
+            return out;
+        }
+        let policy = self.policy();
+        let (t0, s0) = policy.add(0, false, pair_rank);
+        self.draw_pmf(t0, s0, arr, 1.0, memo, |sim, t, s, rest, q, memo| {
+            if sim.split_aces_one && pair_rank == 0 {
+                // The sibling ace's one card leaves the deck first (`sibling_average`).
+                return sim.draw_pmf(t, s, rest, q, memo, |sim, _, _, rest2, q2, memo| {
+                    if sim.split_ace_double(t, sim.das, sim.post_split_double_rule) {
+                        let es = settle_stood(rest2, t, up, hole_constraint, &policy, nat, memo);
+                        let ed = 2.0 * sim.hit_once_in(t, s, up, rest2, hole_constraint, nat_dbl, memo);
+                        if ed > es {
+                            return sim.draw_pmf(t, s, rest2, q2, memo, |sim, t2, _, rest3, q3, memo| {
+                                sim.leaf_pmf((t2, 3), 2.0, nat_dbl, up, rest3, hole_constraint, q3, &mut out, memo)
+                            });
+                        }
+                    }
+                    sim.leaf_pmf((t, 2), 1.0, nat, up, rest2, hole_constraint, q2, &mut out, memo)
+                });
+            }
+            let es = settle_stood(rest, t, up, hole_constraint, &policy, nat, memo);
+            let eh = sim.hit_in(t, s, 2, up, rest, hole_constraint, nat, memo);
+            let ed = if sim.das.allows(1) && sim.post_split_double_rule.allows(t) {
+                2.0 * sim.hit_once_in(t, s, up, rest, hole_constraint, nat_dbl, memo)
+            } else {
+                f64::NEG_INFINITY
+            };
+            match best_action(&[("stand", es), ("hit", eh), ("double", ed)]).0 {
+                "stand" => sim.leaf_pmf((t, 2), 1.0, nat, up, rest, hole_constraint, q, &mut out, memo),
+                "hit" => {
+                    let depth = sim.hit_depth(2);
+                    sim.draw_pmf(t, s, rest, q, memo, |sim, t2, s2, rest2, q2, memo| {
+                        sim.play_out_pmf((t2, s2, 3), up, rest2, hole_constraint, nat, depth - 1, q2, &mut out, memo)
+                    });
+                }
+                _ => sim.draw_pmf(t, s, rest, q, memo, |sim, t2, _, rest2, q2, memo| {
+                    sim.leaf_pmf((t2, 3), 2.0, nat_dbl, up, rest2, hole_constraint, q2, &mut out, memo)
+                }),
+            }
+        });
+        out
+    }
+
+    /// `round_outcome_distribution` on a live deck (upcard out) as ascending
+    /// `(net, prob)` pairs, with a `suited` share of naturals earning the
+    /// bonus; `caller` names the API in errors.
+    #[allow(clippy::too_many_arguments)]
+    fn round_pmf(&self, up: usize, mut arr: [Count; 10], hole_constraint: i32, depth: Option<usize>, suited: f64, caller: &str) -> PyResult<Vec<(f64, f64)>> {
+        if self.free_bet {
+            return Err(PyValueError::new_err(format!("{caller} does not model free_bet stakes")));
+        }
+        let hc = self.hole_for(up, hole_constraint);
+        let depth = self.draw_limit(depth.unwrap_or(self.dp_depth));
+        let policy = self.policy();
+        let mut memo = self.memo();
+        let memo = &mut memo.map;
+        let n: i32 = arr.iter().sum();
+        if n < 3 {
+            return Err(PyValueError::new_err(format!("{caller} needs two player cards and a hole card")));
+        }
+        let mut pmf = Pmf::new();
+        for a in 0..10 {
+            if arr[a] <= 0 {
                 continue;
             }
-            let p = (c as f64) / (rem as f64);
-            arr[h] -= 1;
-            let mut cpy = arr;
-            let dist = dealer_dist_with_two(&mut cpy, up, h, self.h17, &mut memo);
-            let mut ev = 0.0;
-            for i in 0..6 {
-                ev += settle_vs_player(pt_total, i) * dist[i];
+            let pa = (arr[a] as f64) / (n as f64);
+            arr[a] -= 1;
+            for b in 0..10 {
+                if arr[b] <= 0 {
+                    continue;
+                }
+                let p = pa * (arr[b] as f64) / ((n - 1) as f64);
+                arr[b] -= 1;
+                let p_nat = if hc == HC_NONE || self.aces_hard_only { 0.0 } else { natural_prob(&arr, up) };
+                if policy.natural(a, b) {
+                    let p_push = weighted_natural_prob(&arr, up, &constraint_weights(HC_NONE));
+                    pmf_add(&mut pmf, policy.settle21.natural_vs_natural, p * p_push);
+                    pmf_add(&mut pmf, policy.pay(self.bj_payout + self.blackjack_bonus), p * (1.0 - p_push) * suited);
+                    pmf_add(&mut pmf, policy.pay(self.bj_payout), p * (1.0 - p_push) * (1.0 - suited));
+                } else {
+                    // A peeked natural ends the round before the hand plays.
+                    pmf_add(&mut pmf, -1.0, p * p_nat);
+                    let (t0, s0) = policy.add(0, false, a);
+                    let (t, s) = policy.add(t0, s0, b);
+                    let pair = if a == b { Some(a) } else { None };
+                    for (k, q) in self.dealt_hand_pmf(t, s, pair, up, &arr, hc, depth, memo) {
+                        pmf_add(&mut pmf, f64::from_bits(k), p * (1.0 - p_nat) * q);
+                    }
+                }
+                arr[b] += 1;
             }
-            acc += p * ev;
-            denom += p;
-            arr[h] += 1;
+            arr[a] += 1;
         }
-        Ok(if denom > 0.0 { acc / denom } else { 0.0 })
+        let mut results: Vec<(f64, f64)> = pmf.into_iter().map(|(k, q)| (f64::from_bits(k), q)).collect();
+        results.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(results)
     }
 
-    /// One-card hit then stand (per-stake), conditional on US peek.
-    fn hit_then_stand_ev(
-        &self,
-        pt_total: i32,
-        pt_soft: bool,
-        up: usize,
-        deck: Vec<Count>,
-        hole_constraint: i32,
-        _depth: Option<usize>,
-    ) -> PyResult<f64> {
-        let mut arr = [0i32; 10];
-        for i in 0..10 {
-            arr[i] = *deck.get(i).unwrap_or(&0);
+    /// Distribution of a dealt (non-natural) starting hand's net result under
+    /// the `deal_options` choice, conditional on the hole constraint.
+    #[allow(clippy::too_many_arguments)]
+    fn dealt_hand_pmf(&self, pt: i32, soft: bool, pair: Option<usize>, up: usize, arr: &[Count; 10], hole_constraint: i32, depth: usize, memo: &mut Memo) -> Pmf {
+        let mut out = Pmf::new();
+        let options = self.deal_options(pt, soft, pair, up, arr, hole_constraint, depth, memo);
+        match best_action(&options).0 {
+            "stand" => self.leaf_pmf((pt, 2), 1.0, self.nat_loss(), up, arr, hole_constraint, 1.0, &mut out, memo),
+            "hit" => self.draw_pmf(pt, soft, arr, 1.0, memo, |sim, t, s, rest, q, memo| {
+                sim.play_out_pmf((t, s, 3), up, rest, hole_constraint, sim.nat_loss(), depth.max(1) - 1, q, &mut out, memo)
+            }),
+            "double" => self.draw_pmf(pt, soft, arr, 1.0, memo, |sim, t, _, rest, q, memo| {
+                sim.leaf_pmf((t, 3), 2.0, sim.double_nat_loss(), up, rest, hole_constraint, q, &mut out, memo)
+            }),
+            "split" => {
+                let child = self.split_child_pmf(pair.unwrap_or(0), up, arr, hole_constraint, memo);
+                out = pmf_convolve(&child, &child);
+            }
+            _ => {
+                let p_nat = if self.aces_hard_only { 0.0 } else { weighted_natural_prob(arr, up, &constraint_weights(hole_constraint)) };
+                pmf_add(&mut out, -self.surrender_fraction, 1.0 - p_nat);
+                pmf_add(&mut out, self.nat_loss(), p_nat);
+            }
         }
-        let rem0: i32 = arr.iter().sum();
-        if rem0 <= 0 {
-            return Ok(0.0);
+        out
+    }
+}
+
+// ---------- Round simulation ----------
+/// SplitMix64: tiny, seedable and identical on every platform, so a seed
+/// replays the same round anywhere.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Take one card from `counts`, each remaining card equally likely.
+    fn draw(&mut self, counts: &mut [Count; 10]) -> PyResult<usize> {
+        let rem: i32 = counts.iter().map(|c| (*c).max(0)).sum();
+        if rem <= 0 {
+            return Err(PyValueError::new_err("shoe ran out mid-round"));
         }
-        let mut total_acc = 0.0;
-        for r in 0..10 {
-            let c = arr[r];
-            if c <= 0 {
-                continue;
+        let mut k = (self.next() % rem as u64) as i32;
+        for (r, c) in counts.iter_mut().enumerate() {
+            if k < (*c).max(0) {
+                *c -= 1;
+                return Ok(r);
             }
-            let p_r = (c as f64) / (rem0 as f64);
-            arr[r] -= 1;
-            let (t2, s2) = add_to(pt_total, pt_soft, r);
+            k -= (*c).max(0);
+        }
+        unreachable!("draw index within the remaining cards")
+    }
+}
 
-            let rem1: i32 = arr.iter().sum();
-            if rem1 <= 0 {
-                total_acc += p_r * 0.0;
+/// One dealt round, as returned by `simulate_round`. Cards are rank labels;
+/// results are in original-bet units.
+#[pyclass]
+pub struct RoundTrace {
+    #[pyo3(get)]
+    seed: u64,
+    /// One card list per player hand (two after a split).
+    #[pyo3(get)]
+    player_hands: Vec<Vec<String>>,
+    #[pyo3(get)]
+    dealer_cards: Vec<String>,
+    /// `(hand, action, ev)` for every decision, in play order.
+    #[pyo3(get)]
+    decisions: Vec<(usize, String, f64)>,
+    /// Net result of each hand.
+    #[pyo3(get)]
+    outcomes: Vec<f64>,
+    #[pyo3(get)]
+    player_natural: bool,
+    #[pyo3(get)]
+    dealer_natural: bool,
+    /// Sum of `outcomes`.
+    #[pyo3(get)]
+    net: f64,
+}
+
+#[pymethods]
+impl RoundTrace {
+    fn __repr__(&self) -> String {
+        format!(
+            "RoundTrace(seed={}, player_hands={:?}, dealer_cards={:?}, decisions={:?}, outcomes={:?}, net={:?})",
+            self.seed, self.player_hands, self.dealer_cards, self.decisions, self.outcomes, self.net,
+        )
+    }
+}
+
+fn labels(cards: &[usize]) -> Vec<String> {
+    cards.iter().map(|&r| RANK_LABELS[r].to_string()).collect()
+}
+
+/// A hand in play (stake 2 once doubled), valued under `policy`'s ace rule.
+struct TraceHand {
+    cards: Vec<usize>,
+    total: i32,
+    soft: bool,
+    stake: f64,
+    surrendered: bool,
+    policy: DealerPolicy,
+}
+
+impl TraceHand {
+    fn new(cards: &[usize], policy: DealerPolicy) -> Self {
+        let (total, soft) = cards.iter().fold((0, false), |(t, s), &r| policy.add(t, s, r));
+        TraceHand {
+            cards: cards.to_vec(),
+            total,
+            soft,
+            stake: 1.0,
+            surrendered: false,
+            policy,
+        }
+    }
+
+    fn take(&mut self, r: usize) {
+        self.cards.push(r);
+        let (t, s) = self.policy.add(self.total, self.soft, r);
+        self.total = t;
+        self.soft = s;
+    }
+}
+
+impl BlackjackSimulator {
+    /// Unseen cards as the player sees them (shoe remainder plus the hole),
+    /// in the simulator's deck convention.
+    fn decision_deck(&self, shoe: &[Count; 10], hole: usize, up: usize) -> PyResult<[Count; 10]> {
+        let mut view = *shoe;
+        view[hole] += 1;
+        if self.remove_upcard {
+            view[up] += 1;
+        }
+        self.live_deck(&view, up)
+    }
+
+    /// Play one hand to completion with the engine's own choice at every
+    /// decision: `first` lists the actions open on the first one (later ones
+    /// are stand or hit). `(nat, nat_dbl)` are the ENHC natural losses the EVs use.
+    #[allow(clippy::too_many_arguments)]
+    fn trace_hand(
+        &self,
+        idx: usize,
+        hand: &mut TraceHand,
+        mut first: Vec<(&'static str, f64)>,
+        nat: f64,
+        up: usize,
+        hole: usize,
+        shoe: &mut [Count; 10],
+        rng: &mut SplitMix64,
+        decisions: &mut Vec<(usize, String, f64)>,
+        memo: &mut Memo,
+    ) -> PyResult<()> {
+        let hc = self.hole_for(up, HC_AUTO);
+        loop {
+            if hand.total >= 21 || self.max_player_cards.is_some_and(|m| hand.cards.len() >= m) {
+                return Ok(());
+            }
+            let evs = if first.is_empty() {
+                let arr = self.decision_deck(shoe, hole, up)?;
+                vec![
+                    ("stand", settle_stood(&arr, hand.total, up, hc, &self.policy(), nat, memo)),
+                    ("hit", self.hit_in(hand.total, hand.soft, hand.cards.len(), up, &arr, hc, nat, memo)),
+                ]
             } else {
-                let mut memo = HashMap::new();
-                let mut acc = 0.0;
-                let mut denom = 0.0;
-                for h in 0..10 {
-                    let ch = arr[h];
-                    if ch <= 0 {
-                        continue;
+                std::mem::take(&mut first)
+            };
+            let (action, ev) = best_action(&evs);
+            decisions.push((idx, action.to_string(), ev));
+            match action {
+                "stand" => return Ok(()),
+                "surrender" => {
+                    hand.surrendered = true;
+                    return Ok(());
+                }
+                "double" => {
+                    hand.stake = 2.0;
+                    hand.take(rng.draw(shoe)?);
+                    return Ok(());
+                }
+                _ => hand.take(rng.draw(shoe)?),
+            }
+        }
+    }
+
+    fn play_round(&self, seed: u64) -> PyResult<RoundTrace> {
+        if self.free_bet {
+            return Err(PyValueError::new_err("simulate_round does not model free_bet stakes"));
+        }
+        if self.double_exposure {
+            return Err(PyValueError::new_err("simulate_round does not model double_exposure"));
+        }
+        let mut rng = SplitMix64(seed);
+        let mut shoe = self.shoe;
+        let p1 = rng.draw(&mut shoe)?;
+        let up = rng.draw(&mut shoe)?;
+        let p2 = rng.draw(&mut shoe)?;
+        let hole = rng.draw(&mut shoe)?;
+        let policy = self.policy();
+        let player_natural = policy.natural(p1, p2);
+        let dealer_natural = policy.natural(up, hole);
+        let peeked = (up == 0 && self.peek_on_ace) || (up == 9 && self.peek_on_ten);
+        let mut trace = RoundTrace {
+            seed,
+            player_hands: vec![labels(&[p1, p2])],
+            dealer_cards: labels(&[up, hole]),
+            decisions: Vec::new(),
+            outcomes: Vec::new(),
+            player_natural,
+            dealer_natural,
+            net: 0.0,
+        };
+        if player_natural || (dealer_natural && peeked) {
+            let out = match (player_natural, dealer_natural) {
+                (true, true) => policy.settle21.natural_vs_natural,
+                (true, false) => policy.pay(self.bj_payout),
+                _ => -1.0,
+            };
+            trace.outcomes = vec![out];
+            trace.net = out;
+            return Ok(trace);
+        }
+
+        let hc = self.hole_for(up, HC_AUTO);
+        let mut memo = self.memo();
+        let arr = self.decision_deck(&shoe, hole, up)?;
+        let pair = if p1 == p2 { Some(p1) } else { None };
+        let opening = TraceHand::new(&[p1, p2], policy);
+        let evs = self.action_evs(opening.total, opening.soft, pair, up, &arr, hc, self.surrender, &mut memo.map);
+        let mut hands = Vec::new();
+        if best_action(&evs).0 == "split" {
+            trace.decisions.push((0, "split".to_string(), best_action(&evs).1));
+            let (nat, nat_dbl) = self.split_nat_losses();
+            for idx in 0..2 {
+                let mut hand = TraceHand::new(&[p1], policy);
+                hand.take(rng.draw(&mut shoe)?);
+                if !(self.split_aces_one && p1 == 0) {
+                    let arr = self.decision_deck(&shoe, hole, up)?;
+                    let mut first = vec![
+                        ("stand", settle_stood(&arr, hand.total, up, hc, &policy, nat, &mut memo.map)),
+                        ("hit", self.hit_in(hand.total, hand.soft, 2, up, &arr, hc, nat, &mut memo.map)),
+                    ];
+                    if self.das.allows(1) && self.post_split_double_rule.allows(hand.total) {
+                        first.push(("double", 2.0 * self.hit_once_in(hand.total, hand.soft, up, &arr, hc, nat_dbl, &mut memo.map)));
                     }
-                    if !hole_allowed(hole_constraint, h) {
-                        continue;
+                    if hand.total < 21 {
+                        self.trace_hand(idx, &mut hand, first, nat, up, hole, &mut shoe, &mut rng, &mut trace.decisions, &mut memo.map)?;
                     }
-                    let p_h = (ch as f64) / (rem1 as f64);
-                    arr[h] -= 1;
-                    let mut cpy = arr;
-                    let dist = dealer_dist_with_two(&mut cpy, up, h, self.h17, &mut memo);
-                    let mut ev = 0.0;
-                    for i in 0..6 {
-                        ev += settle_vs_player(t2, i) * dist[i];
+                }
+                hands.push(hand);
+            }
+            if self.split_aces_one && p1 == 0 {
+                for (idx, hand) in hands.iter_mut().enumerate() {
+                    if !self.split_ace_double(hand.total, self.das, self.post_split_double_rule) {
+                        continue;
                     }
-                    acc += p_h * ev;
-                    denom += p_h;
-                    arr[h] += 1;
+                    let arr = self.decision_deck(&shoe, hole, up)?;
+                    let first = vec![
+                        ("stand", settle_stood(&arr, hand.total, up, hc, &policy, nat, &mut memo.map)),
+                        ("double", 2.0 * self.hit_once_in(hand.total, hand.soft, up, &arr, hc, nat_dbl, &mut memo.map)),
+                    ];
+                    self.trace_hand(idx, hand, first, nat, up, hole, &mut shoe, &mut rng, &mut trace.decisions, &mut memo.map)?;
+                }
+            }
+        } else {
+            let mut hand = opening;
+            let first = evs.into_iter().filter(|e| e.0 != "split").collect();
+            self.trace_hand(0, &mut hand, first, self.nat_loss(), up, hole, &mut shoe, &mut rng, &mut trace.decisions, &mut memo.map)?;
+            hands.push(hand);
+        }
+        drop(memo);
+
+        // The dealer only draws if a hand is still live and there is no
+        // (unpeeked) natural to reveal.
+        let dealer_policy = policy.for_up(up);
+        let mut dealer = TraceHand::new(&[up, hole], dealer_policy);
+        if !dealer_natural && hands.iter().any(|h| h.total <= 21 && !h.surrendered) {
+            while dealer.total <= 21 && dealer_policy.hits_with(dealer.total, dealer.soft, dealer.cards.len() as u8) {
+                dealer.take(rng.draw(&mut shoe)?);
+            }
+        }
+        let bin = (dealer.total.min(22) - 17).max(0) as usize;
+        let n = hands.len() as f64;
+        let t = &policy.settle21;
+        for h in &hands {
+            // ENHC: the natural takes the original stake (OBO), each hand's
+            // own stake (refund-extra), everything (default), or nothing
+            // (full refund).
+            let loss = if self.no_peek_full_refund {
+                0.0
+            } else if self.obo {
+                -1.0 / n
+            } else if self.enhc_refund_extra {
+                -1.0
+            } else {
+                -h.stake
+            };
+            let out = if h.total > 21 {
+                -h.stake
+            } else if h.surrendered && !dealer_natural {
+                -self.surrender_fraction
+            } else if self.is_charlie(h.total, h.cards.len()) {
+                if dealer_natural && !self.charlie_beats_bj {
+                    loss
+                } else {
+                    h.stake * policy.win_pay
                 }
-                if denom > 0.0 {
-                    total_acc += p_r * (acc / denom);
+            } else if dealer_natural {
+                if h.total == 21 && !h.surrendered {
+                    -loss * t.drawn21_vs_natural
+                } else {
+                    loss
                 }
+            } else if self.push_22 && dealer.total == 22 {
+                0.0
+            } else if h.total == 21 && dealer.total == 21 {
+                h.stake * t.drawn21_vs_drawn21
+            } else {
+                h.stake * settle_vs_player(h.total, bin, &policy)
+            };
+            trace.outcomes.push(out);
+        }
+        trace.player_hands = hands.iter().map(|h| labels(&h.cards)).collect();
+        trace.dealer_cards = labels(&dealer.cards);
+        trace.net = trace.outcomes.iter().sum();
+        Ok(trace)
+    }
+}
+
+// ---------- Rule comparison ----------
+/// Stand EV under `sim_a` minus stand EV under `sim_b`, both evaluated on the
+/// same deck and hole constraint (e.g. S17 vs H17 for one hand).
+#[pyfunction]
+fn ev_delta(
+    sim_a: PyRef<'_, BlackjackSimulator>,
+    sim_b: PyRef<'_, BlackjackSimulator>,
+    pt_total: i32,
+    pt_soft: bool,
+    up: usize,
+    deck: Vec<Count>,
+    hole_constraint: i32,
+) -> PyResult<f64> {
+    let _ = pt_soft; // softness is irrelevant once standing
+    let mut ev = [0.0; 2];
+    for (e, sim) in ev.iter_mut().zip([&sim_a, &sim_b]) {
+        let arr = sim.live_deck(&deck, up)?;
+        let mut memo = sim.memo();
+        *e = sim.stand_in(pt_total, up, &arr, sim.hole_for(up, hole_constraint), &mut memo.map);
+    }
+    Ok(ev[0] - ev[1])
+}
+
+/// Optimal-action EV under `sim_a` minus that under `sim_b` on the same deck
+/// and hole constraint: how much the rule change is worth for this decision.
+#[pyfunction]
+#[allow(clippy::too_many_arguments)]
+fn optimal_ev_delta(
+    sim_a: PyRef<'_, BlackjackSimulator>,
+    sim_b: PyRef<'_, BlackjackSimulator>,
+    pt_total: i32,
+    pt_soft: bool,
+    is_pair: bool,
+    pair_rank: usize,
+    up: usize,
+    deck: Vec<Count>,
+    hole_constraint: i32,
+) -> PyResult<f64> {
+    let pair = if is_pair { Some(pair_rank) } else { None };
+    let mut ev = [0.0; 2];
+    for (e, sim) in ev.iter_mut().zip([&sim_a, &sim_b]) {
+        let arr = sim.live_deck(&deck, up)?;
+        let mut memo = sim.memo();
+        let hc = sim.hole_for(up, hole_constraint);
+        *e = best_action(&sim.action_evs(pt_total, pt_soft, pair, up, &arr, hc, sim.surrender, &mut memo.map)).1;
+    }
+    Ok(ev[0] - ev[1])
+}
+
+// ---------- Bankroll math ----------
+// Abramowitz & Stegun 7.1.26 (|error| < 1.5e-7), plenty for risk estimates.
+fn norm_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t * (0.254_829_592 + t * (-0.284_496_736 + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let erf = 1.0 - poly * (-z * z).exp();
+    if x >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// Risk of ruin for a bankroll of `bankroll_units` betting one unit per hand.
+/// With `hands=None` this is the classic `exp(-2 * ev * B / variance)`; with a
+/// horizon it is the Brownian first-passage probability within `hands` hands.
+#[pyfunction]
+#[pyo3(signature = (ev_per_hand, variance_per_hand, bankroll_units, hands=None))]
+fn risk_of_ruin(ev_per_hand: f64, variance_per_hand: f64, bankroll_units: f64, hands: Option<u64>) -> f64 {
+    if bankroll_units <= 0.0 {
+        return 1.0;
+    }
+    if variance_per_hand <= 0.0 {
+        return if ev_per_hand > 0.0 { 0.0 } else { 1.0 };
+    }
+    let tail = (-2.0 * ev_per_hand * bankroll_units / variance_per_hand).exp();
+    match hands {
+        None => {
+            if ev_per_hand <= 0.0 {
+                1.0
+            } else {
+                tail
             }
-            arr[r] += 1;
         }
-        Ok(total_acc)
+        Some(0) => 0.0,
+        Some(n) => {
+            let n = n as f64;
+            let sd = (variance_per_hand * n).sqrt();
+            let drift = ev_per_hand * n;
+            let p = norm_cdf((-bankroll_units - drift) / sd) + tail * norm_cdf((-bankroll_units + drift) / sd);
+            p.clamp(0.0, 1.0)
+        }
     }
+}
 
-    /// Double EV (per-stake): draw exactly one card then settle.
-    fn double_ev(
-        &self,
-        pt_total: i32,
-        pt_soft: bool,
-        up: usize,
-        deck: Vec<Count>,
-        hole_constraint: i32,
-        _depth_dbl: Option<usize>,
-    ) -> PyResult<f64> {
-        self.hit_then_stand_ev(pt_total, pt_soft, up, deck, hole_constraint, None)
+/// Kelly (log-utility) fraction of bankroll to wager on a hand with the given
+/// per-unit EV and variance: `ev / (variance + ev^2)`, i.e. EV over the second
+/// moment. Exact for an even-money bet (a 55% coin flip gives 0.10) and the usual
+/// small-edge approximation otherwise. Never negative.
+#[pyfunction]
+fn kelly_fraction(ev: f64, variance: f64) -> f64 {
+    let second_moment = variance + ev * ev;
+    if ev <= 0.0 || second_moment <= 0.0 {
+        return 0.0;
     }
+    ev / second_moment
+}
 
-    /// Split EV (per original stake): average of the two child hands’ per-stake EV.
-    fn split_ev(
-        &self,
-        pair_rank: usize,
-        up: usize,
-        deck: Vec<Count>,
-        hole_constraint: i32,
-        das: bool,
-        split_aces_one: bool,
-        _depth_split: Option<usize>,
-    ) -> PyResult<f64> {
-        let mut arr = [0i32; 10];
-        for i in 0..10 {
-            arr[i] = *deck.get(i).unwrap_or(&0);
+/// Kelly bet in bankroll units: `kelly_fraction(ev, variance) * bankroll`.
+#[pyfunction]
+fn kelly_bet_units(ev: f64, variance: f64, bankroll: f64) -> f64 {
+    kelly_fraction(ev, variance) * bankroll.max(0.0)
+}
+
+// ---------- Side bets ----------
+/// 21+3 categories, best first, with the usual paytable (to one).
+const POKER_21_3: [(&str, f64); 5] = [
+    ("suited_trips", 100.0),
+    ("straight_flush", 40.0),
+    ("three_of_a_kind", 30.0),
+    ("straight", 10.0),
+    ("flush", 5.0),
+];
+
+/// Two-card total bet tiers, best first, with the Lucky Ladies paytable
+/// (to one); the queen tier is both queens of hearts.
+const TOTAL_SIDE_BET: [(&str, f64); 4] = [
+    ("queen_hearts_pair", 200.0),
+    ("matched", 25.0),
+    ("suited", 10.0),
+    ("total", 4.0),
+];
+
+/// Suit column of hearts in a suited deck (clubs, diamonds, hearts, spades).
+const HEARTS: usize = 2;
+
+/// Pay per category: `base`, with any named entries of `overrides` replaced.
+fn paytable<const N: usize>(base: &[(&str, f64); N], overrides: Option<HashMap<String, f64>>, what: &str) -> PyResult<[f64; N]> {
+    let mut pays = base.map(|(_, pay)| pay);
+    for (name, pay) in overrides.unwrap_or_default() {
+        let i = base
+            .iter()
+            .position(|&(n, _)| n == name)
+            .ok_or_else(|| PyValueError::new_err(format!("unknown {} category {:?}", what, name)))?;
+        pays[i] = pay;
+    }
+    Ok(pays)
+}
+
+/// 13x4 rank-and-suit counts (A,2..T,J,Q,K by four suits): the suited
+/// deck the side bets need, since the play deck buckets every ten together.
+fn suited_deck(deck: &[Vec<Count>]) -> PyResult<[[Count; 4]; 13]> {
+    if deck.len() != 13 || deck.iter().any(|row| row.len() != 4) {
+        return Err(PyValueError::new_err("suited deck must be 13 ranks (A,2..T,J,Q,K) by 4 suits"));
+    }
+    let mut out = [[0; 4]; 13];
+    for (o, row) in out.iter_mut().zip(deck) {
+        for (c, &n) in o.iter_mut().zip(row) {
+            if n < 0 {
+                return Err(PyValueError::new_err("suited deck counts must be non-negative"));
+            }
+            *c = n;
         }
-        let rem0: i32 = arr.iter().sum();
-        if rem0 <= 0 {
-            return Ok(0.0);
+    }
+    Ok(out)
+}
+
+/// `suited_deck` that must hold exactly `deck`'s cards, T,J,Q,K counting
+/// toward the ten bucket.
+fn suits_of(suited: &[Vec<Count>], deck: &[Count; 10]) -> PyResult<[[Count; 4]; 13]> {
+    let suits = suited_deck(suited)?;
+    let ranks: [Count; 10] = std::array::from_fn(|r| if r < 9 { suits[r].iter().sum() } else { suits[9..].iter().flatten().sum() });
+    if ranks != *deck {
+        return Err(PyValueError::new_err(format!("suited deck holds {:?}, not the deck's {:?}", ranks, deck)));
+    }
+    Ok(suits)
+}
+
+/// Chance a natural dealt from `suits` is suited: sum_s a_s t_s / (a t) over
+/// the aces and ten-valued cards of each suit. An ace or ten upcard `up`
+/// dealt from `suits` first is averaged over its rank's suits. Evenly spread
+/// suits give `SUITED_NATURAL_PROB` exactly.
+fn suited_natural_prob(suits: &[[Count; 4]; 13], up: Option<usize>) -> f64 {
+    let aces = suits[0].map(|c| c as f64);
+    let tens: [f64; 4] = std::array::from_fn(|s| suits[9..].iter().map(|row| row[s] as f64).sum());
+    let (a, t): (f64, f64) = (aces.iter().sum(), tens.iter().sum());
+    let same: f64 = (0..4).map(|s| aces[s] * tens[s]).sum();
+    let (same, pairs) = match up {
+        Some(0) if a > 0.0 => (same - (0..4).map(|s| aces[s] / a * tens[s]).sum::<f64>(), (a - 1.0) * t),
+        Some(9) if t > 0.0 => (same - (0..4).map(|s| aces[s] * tens[s] / t).sum::<f64>(), a * (t - 1.0)),
+        _ => (same, a * t),
+    };
+    if pairs > 0.0 {
+        same / pairs
+    } else {
+        SUITED_NATURAL_PROB
+    }
+}
+
+/// The 21+3 category of three (rank, suit) cards, ranks 0..12 with the ace
+/// at 0 playing high or low; `None` if the hand pays nothing.
+fn poker_category(cards: [(usize, usize); 3]) -> Option<usize> {
+    let mut ranks = cards.map(|c| c.0);
+    ranks.sort_unstable();
+    let flush = cards[0].1 == cards[1].1 && cards[1].1 == cards[2].1;
+    let trips = ranks[0] == ranks[2];
+    let straight = (ranks[1] == ranks[0] + 1 && ranks[2] == ranks[1] + 1) || ranks == [0, 11, 12];
+    match (trips, straight, flush) {
+        (true, _, true) => Some(0),
+        (_, true, true) => Some(1),
+        (true, _, _) => Some(2),
+        (_, true, _) => Some(3),
+        (_, _, true) => Some(4),
+        _ => None,
+    }
+}
+
+/// Probability of each 21+3 category for the player's two cards and the
+/// dealer upcard dealt from `deck` (see `suited_deck`).
+fn poker_21_3_dist(deck: &[[Count; 4]; 13]) -> [f64; 5] {
+    let cells: Vec<((usize, usize), Count)> =
+        (0..13).flat_map(|r| (0..4).map(move |s| (r, s))).map(|(r, s)| ((r, s), deck[r][s])).filter(|&(_, n)| n > 0).collect();
+    let n: Count = cells.iter().map(|&(_, c)| c).sum();
+    let mut out = [0.0; 5];
+    if n < 3 {
+        return out;
+    }
+    let hands = choose(n, 3);
+    for i in 0..cells.len() {
+        for j in i..cells.len() {
+            for k in j..cells.len() {
+                // Ways to draw the multiset {i, j, k} of cells.
+                let ways = match (i == j, j == k) {
+                    (true, true) => choose(cells[i].1, 3),
+                    (true, false) => choose(cells[i].1, 2) * cells[k].1 as f64,
+                    (false, true) => cells[i].1 as f64 * choose(cells[j].1, 2),
+                    (false, false) => (cells[i].1 * cells[j].1 * cells[k].1) as f64,
+                };
+                if let Some(cat) = poker_category([cells[i].0, cells[j].0, cells[k].0]) {
+                    out[cat] += ways / hands;
+                }
+            }
         }
+    }
+    out
+}
 
-        let mut total = 0.0;
-        for r in 0..10 {
-            let c = arr[r];
-            if c <= 0 {
+/// A plain [A,2..9,T] deck as a suited one, each rank spread evenly over
+/// the suits and the ten over T,J,Q,K: fractional counts.
+fn spread_suits(deck: &[Count; 10]) -> [[f64; 4]; 13] {
+    std::array::from_fn(|r| [if r < 9 { deck[r] as f64 / 4.0 } else { deck[9] as f64 / 16.0 }; 4])
+}
+
+/// Probability of each `TOTAL_SIDE_BET` tier for the first two cards off
+/// `deck` (13 ranks by 4 suits), totalled under `policy`'s ace rule.
+fn total_side_bet_dist(deck: &[[f64; 4]; 13], target: i32, policy: &DealerPolicy) -> [f64; 4] {
+    let cells: Vec<((usize, usize), f64)> =
+        (0..13).flat_map(|r| (0..4).map(move |s| (r, s))).map(|(r, s)| ((r, s), deck[r][s])).filter(|&(_, n)| n > 0.0).collect();
+    let n: f64 = cells.iter().map(|&(_, c)| c).sum();
+    let mut out = [0.0; 4];
+    if n < 2.0 {
+        return out;
+    }
+    let hands = n * (n - 1.0) / 2.0;
+    for i in 0..cells.len() {
+        for j in i..cells.len() {
+            let ((ri, si), ci) = cells[i];
+            let ((rj, sj), cj) = cells[j];
+            let (t0, s0) = policy.add(0, false, ri.min(9));
+            if policy.add(t0, s0, rj.min(9)).0 != target {
                 continue;
             }
-            let p = (c as f64) / (rem0 as f64);
-            arr[r] -= 1;
-            let (t, s) = add_to(rank_val(pair_rank), false, r);
-
-            let ev_child = if split_aces_one && pair_rank == 0 {
-                self.stand_ev(t, s, up, arr.to_vec(), hole_constraint, None)?
+            let ways = if i == j { ci * (ci - 1.0) / 2.0 } else { ci * cj };
+            let tier = if i == j && ri == 11 && si == HEARTS {
+                0
+            } else if i == j {
+                1
+            } else if si == sj {
+                2
             } else {
-                let es = self.stand_ev(t, s, up, arr.to_vec(), hole_constraint, None)?;
-                let eh = self.hit_then_stand_ev(t, s, up, arr.to_vec(), hole_constraint, None)?;
-                let ed = if das {
-                    self.double_ev(t, s, up, arr.to_vec(), hole_constraint, None)?
-                } else {
-                    f64::NEG_INFINITY
-                };
-                es.max(eh.max(ed))
+                3
             };
-
-            total += p * ev_child;
-            arr[r] += 1;
+            out[tier] += ways / hands;
         }
-        Ok(total)
     }
+    out
+}
+
+/// Probability of each 21+3 category ("suited_trips", "straight_flush",
+/// "three_of_a_kind", "straight", "flush") for the first three cards off a
+/// suited `deck` (13 ranks A..K by 4 suits). Each hand counts once, in its
+/// best category; an ace plays high or low, with no wrap (K,A,2).
+#[pyfunction]
+fn poker_21_3_probs(deck: Vec<Vec<Count>>) -> PyResult<BTreeMap<String, f64>> {
+    let dist = poker_21_3_dist(&suited_deck(&deck)?);
+    Ok(POKER_21_3.iter().zip(dist).map(|(&(name, _), p)| (name.to_string(), p)).collect())
+}
+
+/// Net EV per unit of a 21+3 bet from `poker_21_3_probs`: each category
+/// pays `paytable[name]` to one, defaulting to 100/40/30/10/5 for suited
+/// trips, straight flush, trips, straight and flush; anything else loses.
+#[pyfunction]
+#[pyo3(signature = (deck, paytable=None))]
+fn poker_21_3_ev(deck: Vec<Vec<Count>>, paytable: Option<HashMap<String, f64>>) -> PyResult<f64> {
+    let pays = self::paytable(&POKER_21_3, paytable, "21+3")?;
+    let dist = poker_21_3_dist(&suited_deck(&deck)?);
+    let hit: f64 = dist.iter().sum();
+    Ok(dist.iter().zip(pays).map(|(p, pay)| p * pay).sum::<f64>() - (1.0 - hit))
 }
 
 #[pymodule]
-fn rustcore(_py: Python, m: &PyModule) -> PyResult<()> {
+fn rustcore(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<BlackjackSimulator>()?;
+    m.add_class::<CompositionDecision>()?;
+    m.add_class::<Settlement>()?;
+    m.add_class::<DealerTable>()?;
+    m.add_class::<RoundTrace>()?;
+    m.add_class::<StrategyGrid>()?;
+    m.add_class::<StrategyChart>()?;
+    m.add_function(wrap_pyfunction!(risk_of_ruin, m)?)?;
+    m.add_function(wrap_pyfunction!(running_count, m)?)?;
+    m.add_function(wrap_pyfunction!(true_count, m)?)?;
+    m.add_function(wrap_pyfunction!(kelly_fraction, m)?)?;
+    m.add_function(wrap_pyfunction!(kelly_bet_units, m)?)?;
+    m.add_function(wrap_pyfunction!(poker_21_3_probs, m)?)?;
+    m.add_function(wrap_pyfunction!(poker_21_3_ev, m)?)?;
+    m.add_function(wrap_pyfunction!(ev_delta, m)?)?;
+    m.add_function(wrap_pyfunction!(optimal_ev_delta, m)?)?;
+    m.add_function(wrap_pyfunction!(serialize_grid, m)?)?;
+    m.add_function(wrap_pyfunction!(deserialize_grid, m)?)?;
+    m.add_function(wrap_pyfunction!(s17_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(h17_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(dealer_hit_soft_below, m)?)?;
+    m.add("HC_NONE", HC_NONE)?;
+    m.add("HC_NOT_TEN", HC_NOT_TEN)?;
+    m.add("HC_NOT_ACE", HC_NOT_ACE)?;
+    m.add("HC_KNOWN", HC_KNOWN)?;
+    m.add("HC_AUTO", HC_AUTO)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn shared_types_are_send_sync() {
+        assert_send_sync::<BlackjackSimulator>();
+        assert_send_sync::<StrategyGrid>();
+    }
+
+    // ---------- Brute force ----------
+    // A second, unmemoized implementation: every S17 dealer draw sequence
+    // from the upcard, the hole drawn only from ranks the peek lets through,
+    // and the player's total settled against each final.
+
+    fn card(r: usize) -> i32 {
+        r as i32 + 1
+    }
+
+    fn add_card(pt: i32, r: usize) -> i32 {
+        if r == 0 && pt + 11 <= 21 {
+            pt + 11
+        } else {
+            pt + card(r)
+        }
+    }
+
+    fn draw_out(deck: &mut [Count; 10], hard: i32, ace: bool, p: f64, finals: &mut [f64; 6]) {
+        let t = if ace && hard + 10 <= 21 { hard + 10 } else { hard };
+        if t > 21 {
+            finals[5] += p;
+            return;
+        }
+        if t >= 17 {
+            finals[(t - 17) as usize] += p;
+            return;
+        }
+        let n: Count = deck.iter().sum();
+        for r in 0..10 {
+            let c = deck[r];
+            if c == 0 {
+                continue;
+            }
+            deck[r] -= 1;
+            draw_out(deck, hard + card(r), ace || r == 0, p * c as f64 / n as f64, finals);
+            deck[r] += 1;
+        }
+    }
+
+    fn dealer(deck: &[Count; 10], up: usize) -> [f64; 6] {
+        let barred = match up {
+            0 => Some(9),
+            9 => Some(0),
+            _ => None,
+        };
+        let mut deck = *deck;
+        let n: Count = (0..10).filter(|&r| Some(r) != barred).map(|r| deck[r]).sum();
+        let mut finals = [0.0; 6];
+        for hole in (0..10).filter(|&r| Some(r) != barred) {
+            let c = deck[hole];
+            if c == 0 {
+                continue;
+            }
+            deck[hole] -= 1;
+            draw_out(&mut deck, card(up) + card(hole), up == 0 || hole == 0, c as f64 / n as f64, &mut finals);
+            deck[hole] += 1;
+        }
+        finals
+    }
+
+    fn settle(pt: i32, finals: &[f64; 6]) -> f64 {
+        if pt > 21 {
+            return -1.0;
+        }
+        let bust = finals[5];
+        bust + (0..5)
+            .map(|i| {
+                let d = 17 + i as i32;
+                finals[i] * (pt - d).signum() as f64
+            })
+            .sum::<f64>()
+    }
+
+    fn hit_then_stand(pt: i32, deck: &[Count; 10], up: usize) -> f64 {
+        let n: Count = deck.iter().sum();
+        let mut ev = 0.0;
+        for r in 0..10 {
+            let c = deck[r];
+            if c == 0 {
+                continue;
+            }
+            let mut rest = *deck;
+            rest[r] -= 1;
+            let t = add_card(pt, r);
+            let value = if t > 21 { -1.0 } else { settle(t, &dealer(&rest, up)) };
+            ev += c as f64 / n as f64 * value;
+        }
+        ev
+    }
+
+    #[test]
+    fn stand_hit_and_double_match_brute_force() {
+        let sim = BlackjackSimulator::preset(1, false, true);
+        let mut memo = Memo::new();
+        let mut worst = 0.0f64;
+        for up in 0..10 {
+            let mut deck = [4; 10];
+            deck[9] = 16;
+            deck[up] -= 1;
+            let hc = sim.hole_for(up, HC_AUTO);
+            for pt in 4..=21 {
+                let stand = settle(pt, &dealer(&deck, up));
+                worst = worst.max((sim.stand_in(pt, up, &deck, hc, &mut memo) - stand).abs());
+                if pt > 20 {
+                    continue;
+                }
+                let hit = hit_then_stand(pt, &deck, up);
+                worst = worst.max((sim.hit_once_in(pt, false, up, &deck, hc, sim.nat_loss(), &mut memo) - hit).abs());
+                worst = worst.max((sim.double_in(pt, false, up, &deck, hc, &mut memo) - 2.0 * hit).abs());
+            }
+        }
+        assert!(worst <= 1e-12, "largest gap to the brute force: {worst:e}");
+    }
+}
